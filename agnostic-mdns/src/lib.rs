@@ -12,10 +12,13 @@ mod tests;
 
 use std::{
   io,
-  net::{Ipv4Addr, Ipv6Addr},
+  net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
   time::Duration,
 };
 
+use mdns_proto::client::Endpoint;
+use smallvec_wrapper::SmallVec;
+
 const IPV4_MDNS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
 const IPV6_MDNS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
 const IPV4_SIZE: usize = core::mem::size_of::<Ipv4Addr>();
@@ -23,9 +26,12 @@ const IPV6_SIZE: usize = core::mem::size_of::<Ipv6Addr>();
 const MDNS_PORT: u16 = 5353;
 const MAX_INLINE_PACKET_SIZE: usize = 1500;
 
-pub use mdns_proto::{error, proto::Label};
+pub use mdns_proto::{
+  error,
+  proto::{Label, Question, ResourceType},
+};
 
-/// synchronous mDNS server implementation
+/// synchronous mDNS server and hostname resolver implementation
 pub mod sync;
 
 /// Generic asynchronous mDNS implementation for work stealing runtimes
@@ -36,9 +42,13 @@ pub mod worksteal;
 /// A builtin service that can be used with the mDNS server
 pub mod service;
 
+/// The `key=value` attributes of DNS-SD TXT records
+pub mod txt;
+
 pub use iprobe as netprobe;
 pub use service::{Service, ServiceBuilder};
 pub use smol_str::{SmolStr, format_smolstr};
+pub use txt::TxtRecord;
 
 /// The options for [`Server`].
 #[derive(Clone, Debug)]
@@ -215,10 +225,32 @@ impl ServerOptions {
   }
 }
 
+/// Events emitted by a running server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ServerEvent {
+  /// A name of the zone is owned by another host, and the zone was renamed,
+  /// see RFC 6762, section 9.
+  Renamed {
+    /// The name owned by another host.
+    from: SmolStr,
+    /// The new name of the zone.
+    to: SmolStr,
+  },
+  /// A name of the zone is owned by another host, and the zone cannot be renamed, see
+  /// [`Zone::rename`](crate::sync::Zone::rename). The server stopped, after saying
+  /// goodbye to the records it announced.
+  Lost {
+    /// The name owned by another host.
+    name: SmolStr,
+  },
+}
+
 /// How a lookup is performed.
 #[derive(Clone, Debug)]
 pub struct QueryParam<'a> {
   service: Label<'a>,
+  subtype: Option<Label<'a>>,
   domain: Label<'a>,
   timeout: Duration,
   ipv4_interface: Option<Ipv4Addr>,
@@ -230,6 +262,7 @@ pub struct QueryParam<'a> {
   // Whether to disable usage of IPv6 for MDNS operations. Does not affect discovered addresses.
   disable_ipv6: bool,
   max_payload_size: usize,
+  completeness: Completeness,
 }
 
 impl<'a> QueryParam<'a> {
@@ -238,6 +271,7 @@ impl<'a> QueryParam<'a> {
   pub fn new(service: Label<'a>) -> Self {
     Self {
       service,
+      subtype: None,
       domain: Label::from("local"),
       timeout: Duration::from_secs(1),
       ipv4_interface: None,
@@ -247,6 +281,7 @@ impl<'a> QueryParam<'a> {
       disable_ipv6: false,
       cap: None,
       max_payload_size: 1500,
+      completeness: Completeness::RequireTxt,
     }
   }
 
@@ -310,6 +345,49 @@ impl<'a> QueryParam<'a> {
     &self.service
   }
 
+  /// Sets the subtype to search for, only the instances of the service registered with
+  /// the subtype are then found, see RFC 6763, section 7.1.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::QueryParam;
+  ///
+  /// let params = QueryParam::new("_http._tcp".into())
+  ///   .with_subtype("_printer".into());
+  /// ```
+  pub fn with_subtype(mut self, subtype: Label<'a>) -> Self {
+    self.subtype = Some(subtype);
+    self
+  }
+
+  /// Returns the subtype to search for.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::{QueryParam, Label};
+  ///
+  /// let params = QueryParam::new("_http._tcp".into());
+  /// assert!(params.subtype().is_none());
+  ///
+  /// let params = params.with_subtype("_printer".into());
+  /// assert_eq!(params.subtype(), Some(&Label::from("_printer")));
+  /// ```
+  pub const fn subtype(&self) -> Option<&Label<'a>> {
+    self.subtype.as_ref()
+  }
+
+  /// Returns the name the `PTR` queries are sent for, `<service>.<domain>`, or
+  /// `<subtype>._sub.<service>.<domain>` when a subtype is searched for.
+  #[cfg(feature = "worksteal")]
+  fn name(&self) -> SmolStr {
+    match &self.subtype {
+      Some(subtype) => format_smolstr!("{}._sub.{}.{}", subtype, self.service, self.domain),
+      None => format_smolstr!("{}.{}", self.service, self.domain),
+    }
+  }
+
   /// Sets the timeout for the query.
   ///
   /// ## Example
@@ -546,7 +624,7 @@ impl<'a> QueryParam<'a> {
     self
   }
 
-  /// Returns the channel capacity for the [`Lookup`] stream.
+  /// Returns the channel capacity for the [`Lookup`] and [`Browser`] streams.
   ///
   /// If `None`, the channel is unbounded.
   ///
@@ -567,7 +645,7 @@ impl<'a> QueryParam<'a> {
     self.cap
   }
 
-  /// Sets the channel capacity for the [`Lookup`] stream.
+  /// Sets the channel capacity for the [`Lookup`] and [`Browser`] streams.
   ///
   /// If `None`, the channel is unbounded.
   ///
@@ -586,97 +664,915 @@ impl<'a> QueryParam<'a> {
     self.cap = cap;
     self
   }
-}
 
-/// Types for `tokio` runtime
-#[cfg(feature = "tokio")]
-#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
-pub mod tokio {
-  use std::io;
+  /// Returns which records a discovered service must have before it is reported.
+  ///
+  /// Default is [`Completeness::RequireTxt`].
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::{Completeness, QueryParam};
+  ///
+  /// let params = QueryParam::new("service._tcp".into());
+  ///
+  /// assert_eq!(params.completeness(), Completeness::RequireTxt);
+  /// ```
+  #[inline]
+  pub const fn completeness(&self) -> Completeness {
+    self.completeness
+  }
 
-  use super::{QueryParam, service::Service, worksteal::ServiceEntry};
-  use mdns_proto::proto::Label;
+  /// Sets which records a discovered service must have before it is reported.
+  ///
+  /// Default is [`Completeness::RequireTxt`].
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::{Completeness, QueryParam};
+  ///
+  /// let params = QueryParam::new("service._tcp".into())
+  ///   .with_completeness(Completeness::SrvAndAddress);
+  ///
+  /// assert_eq!(params.completeness(), Completeness::SrvAndAddress);
+  /// ```
+  #[inline]
+  pub const fn with_completeness(mut self, completeness: Completeness) -> Self {
+    self.completeness = completeness;
+    self
+  }
+}
 
-  pub use agnostic_net::{runtime::tokio::TokioRuntime as Runtime, tokio::Net};
-  pub use async_channel as channel;
+/// Which records a discovered service must have before it is reported by a lookup or a
+/// browser, see [`QueryParam::with_completeness`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Completeness {
+  /// A service is reported once its SRV and TXT records, and an address of its host,
+  /// are received.
+  #[default]
+  RequireTxt,
+  /// A service is reported once its SRV record and an address of its host are received,
+  /// its TXT strings are empty until its TXT record is received.
+  SrvAndAddress,
+  /// A service is reported as soon as it is discovered, and again every time one of its
+  /// records is received and changes it. Its port is `0` until its SRV record is
+  /// received, and it has no address until the addresses of its host are received.
+  Partial,
+}
 
-  /// A server that can be used with `tokio` runtime
-  pub type Server = super::worksteal::Server<Net, Service>;
+/// How a single name is resolved.
+#[derive(Clone, Debug)]
+pub struct ResolveParam<'a> {
+  name: Label<'a>,
+  timeout: Duration,
+  ipv4_interface: Option<Ipv4Addr>,
+  ipv6_interface: Option<u32>,
+  want_unicast_response: bool, // Unicast response desired, as per 5.4 in RFC
+  // Whether to disable usage of IPv4 for MDNS operations. Does not affect discovered addresses.
+  disable_ipv4: bool,
+  // Whether to disable usage of IPv6 for MDNS operations. Does not affect discovered addresses.
+  disable_ipv6: bool,
+  max_payload_size: usize,
+}
 
-  /// Looks up a given service, in a domain, waiting at most
-  /// for a timeout before finishing the query. The results are streamed
-  /// to a channel. Sends will not block, so clients should make sure to
-  /// either read or buffer. This method will attempt to stop the query
-  /// on cancellation.
+impl<'a> ResolveParam<'a> {
+  /// Creates a new resolve parameter for a fully qualified name, with default values.
+  ///
+  /// The name is a service instance, e.g. `My Printer._ipp._tcp.local.`, for `resolve`,
+  /// or a host, e.g. `foo.local.`, for `resolve_host`.
   #[inline]
-  pub async fn query(params: QueryParam<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
-    super::worksteal::query::<Net>(params, tx).await
+  pub fn new(name: Label<'a>) -> Self {
+    Self {
+      name,
+      timeout: Duration::from_secs(1),
+      ipv4_interface: None,
+      ipv6_interface: None,
+      want_unicast_response: false,
+      disable_ipv4: false,
+      disable_ipv6: false,
+      max_payload_size: 1500,
+    }
   }
 
-  /// Similar to [`query`], however it uses all the default parameters
-  #[inline]
-  pub async fn lookup(service: Label<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
-    super::worksteal::lookup::<Net>(service, tx).await
+  /// Sets the name to resolve.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_name("bar.local.".into());
+  /// ```
+  pub fn with_name(mut self, name: Label<'a>) -> Self {
+    self.name = name;
+    self
   }
-}
 
-/// Types for `smol` runtime
-#[cfg(feature = "smol")]
-#[cfg_attr(docsrs, doc(cfg(feature = "smol")))]
-pub mod smol {
-  use super::{Label, QueryParam, service::Service, worksteal::ServiceEntry};
-  use std::io;
+  /// Returns the name to resolve.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::{ResolveParam, Label};
+  ///
+  /// let params = ResolveParam::new("foo.local.".into());
+  ///
+  /// assert_eq!(params.name(), &Label::from("foo.local."));
+  /// ```
+  pub const fn name(&self) -> &Label<'a> {
+    &self.name
+  }
 
-  pub use agnostic_net::{runtime::smol::SmolRuntime as Runtime, smol::Net};
-  pub use async_channel as channel;
+  /// Sets how long to wait for the name to be resolved.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_timeout(std::time::Duration::from_secs(1));
+  /// ```
+  pub fn with_timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
 
-  /// A server that can be used with `smol` runtime
-  pub type Server = super::worksteal::Server<Net, Service>;
+  /// Returns how long to wait for the name to be resolved.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_timeout(std::time::Duration::from_secs(1));
+  ///
+  /// assert_eq!(params.timeout(), std::time::Duration::from_secs(1));
+  /// ```
+  pub const fn timeout(&self) -> Duration {
+    self.timeout
+  }
 
-  /// Looks up a given service, in a domain, waiting at most
-  /// for a timeout before finishing the query. The results are streamed
-  /// to a channel. Sends will not block, so clients should make sure to
-  /// either read or buffer. This method will attempt to stop the query
-  /// on cancellation.
-  #[inline]
-  pub async fn query(params: QueryParam<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
-    super::worksteal::query::<Net>(params, tx).await
+  /// Sets the IPv4 interface to use for queries.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_ipv4_interface("0.0.0.0".parse().unwrap());
+  /// ```
+  pub fn with_ipv4_interface(mut self, ipv4_interface: Ipv4Addr) -> Self {
+    self.ipv4_interface = Some(ipv4_interface);
+    self
   }
 
-  /// Similar to [`query`], however it uses all the default parameters
-  #[inline]
-  pub async fn lookup(service: Label<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
-    super::worksteal::lookup::<Net>(service, tx).await
+  /// Returns the IPv4 interface to use for queries.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_ipv4_interface("0.0.0.0".parse().unwrap());
+  ///
+  /// assert_eq!(params.ipv4_interface().unwrap(), &"0.0.0.0".parse::<std::net::Ipv4Addr>().unwrap());
+  /// ```
+  pub const fn ipv4_interface(&self) -> Option<&Ipv4Addr> {
+    self.ipv4_interface.as_ref()
   }
-}
 
-/// Types for `async-std` runtime
-#[cfg(feature = "async-std")]
-#[cfg_attr(docsrs, doc(cfg(feature = "async-std")))]
-pub mod async_std {
-  use super::{Label, QueryParam, service::Service, worksteal::ServiceEntry};
-  use std::io;
+  /// Sets the IPv6 interface to use for queries.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_ipv6_interface(1);
+  /// ```
+  pub fn with_ipv6_interface(mut self, ipv6_interface: u32) -> Self {
+    self.ipv6_interface = Some(ipv6_interface);
+    self
+  }
 
-  pub use agnostic_net::{async_std::Net, runtime::async_std::AsyncStdRuntime as Runtime};
-  pub use async_channel as channel;
+  /// Returns the IPv6 interface to use for queries.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_ipv6_interface(1);
+  /// assert_eq!(params.ipv6_interface().unwrap(), 1);
+  /// ```
+  pub const fn ipv6_interface(&self) -> Option<u32> {
+    self.ipv6_interface
+  }
 
-  /// A server that can be used with `async-std` runtime
-  pub type Server = super::worksteal::Server<Net, Service>;
+  /// Sets whether to request unicast responses.
+  ///
+  /// Ignored by `resolve_host`, its query is sent from an ephemeral port, the responders
+  /// always answer it with a unicast response, see RFC 6762, section 6.7.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_unicast_response(true);
+  /// ```
+  pub fn with_unicast_response(mut self, want_unicast_response: bool) -> Self {
+    self.want_unicast_response = want_unicast_response;
+    self
+  }
 
-  /// Looks up a given service, in a domain, waiting at most
-  /// for a timeout before finishing the query. The results are streamed
-  /// to a channel. Sends will not block, so clients should make sure to
-  /// either read or buffer. This method will attempt to stop the query
-  /// on cancellation.
-  #[inline]
-  pub async fn query(params: QueryParam<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
-    super::worksteal::query::<Net>(params, tx).await
+  /// Returns whether to request unicast responses.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_unicast_response(true);
+  ///
+  /// assert_eq!(params.want_unicast_response(), true);
+  /// ```
+  pub const fn want_unicast_response(&self) -> bool {
+    self.want_unicast_response
   }
 
-  /// Similar to [`query`], however it uses all the default parameters
-  #[inline]
-  pub async fn lookup(service: Label<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
-    super::worksteal::lookup::<Net>(service, tx).await
+  /// Sets whether to disable IPv4 for MDNS operations.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_disable_ipv4(true);
+  /// ```
+  pub fn with_disable_ipv4(mut self, disable_ipv4: bool) -> Self {
+    self.disable_ipv4 = disable_ipv4;
+    self
+  }
+
+  /// Returns whether to disable IPv4 for MDNS operations.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_disable_ipv4(true);
+  ///
+  /// assert_eq!(params.disable_ipv4(), true);
+  /// ```
+  pub const fn disable_ipv4(&self) -> bool {
+    self.disable_ipv4
+  }
+
+  /// Sets whether to disable IPv6 for MDNS operations.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_disable_ipv6(true);
+  /// ```
+  pub fn with_disable_ipv6(mut self, disable_ipv6: bool) -> Self {
+    self.disable_ipv6 = disable_ipv6;
+    self
+  }
+
+  /// Returns whether to disable IPv6 for MDNS operations.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_disable_ipv6(true);
+  ///
+  /// assert_eq!(params.disable_ipv6(), true);
+  /// ```
+  pub const fn disable_ipv6(&self) -> bool {
+    self.disable_ipv6
+  }
+
+  /// Returns the configured maximum payload size for mDNS message packets.
+  ///
+  /// Default is `1500` bytes.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_max_payload_size(1500);
+  ///
+  /// assert_eq!(params.max_payload_size(), 1500);
+  /// ```
+  #[inline]
+  pub const fn max_payload_size(&self) -> usize {
+    self.max_payload_size
+  }
+
+  /// Sets the maximum payload size for mDNS message packets.
+  ///
+  /// Default is `1500` bytes.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ResolveParam;
+  ///
+  /// let params = ResolveParam::new("foo.local.".into())
+  ///   .with_max_payload_size(1500);
+  /// ```
+  #[inline]
+  pub const fn with_max_payload_size(mut self, max_payload_size: usize) -> Self {
+    self.max_payload_size = max_payload_size;
+    self
+  }
+}
+
+/// How the records asked for by a list of questions are queried.
+#[derive(Clone, Debug)]
+pub struct RecordQueryParam<'a> {
+  questions: SmallVec<Question<'a>>,
+  timeout: Duration,
+  ipv4_interface: Option<Ipv4Addr>,
+  ipv6_interface: Option<u32>,
+  // Whether to disable usage of IPv4 for MDNS operations. Does not affect discovered addresses.
+  disable_ipv4: bool,
+  // Whether to disable usage of IPv6 for MDNS operations. Does not affect discovered addresses.
+  disable_ipv6: bool,
+  max_payload_size: usize,
+}
+
+impl<'a> RecordQueryParam<'a> {
+  /// Creates a new record query parameter without any question, with default values.
+  #[inline]
+  pub fn new() -> Self {
+    Self {
+      questions: SmallVec::new(),
+      timeout: Duration::from_secs(1),
+      ipv4_interface: None,
+      ipv6_interface: None,
+      disable_ipv4: false,
+      disable_ipv6: false,
+      max_payload_size: 1500,
+    }
+  }
+
+  /// Adds a question asking for the records of type `ty` of `name`, a fully qualified
+  /// name, e.g. `_http._tcp.local.` or `host.local.`, all the questions are sent in one
+  /// packet.
+  ///
+  /// If `want_unicast_response` is `true`, the question asks for a unicast response, see
+  /// RFC 6762, section 5.4.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::{RecordQueryParam, ResourceType};
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_question("_http._tcp.local.".into(), ResourceType::Ptr, false)
+  ///   .with_question("_ipp._tcp.local.".into(), ResourceType::Ptr, false)
+  ///   .with_question("host.local.".into(), ResourceType::A, true)
+  ///   .with_question("host.local.".into(), ResourceType::AAAA, true);
+  ///
+  /// assert_eq!(params.questions().len(), 4);
+  /// ```
+  pub fn with_question(
+    mut self,
+    name: Label<'a>,
+    ty: ResourceType,
+    want_unicast_response: bool,
+  ) -> Self {
+    self.questions.push(Endpoint::prepare_typed_question(
+      name,
+      ty,
+      want_unicast_response,
+    ));
+    self
+  }
+
+  /// Returns the questions to send.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::{RecordQueryParam, ResourceType};
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_question("host.local.".into(), ResourceType::A, false);
+  ///
+  /// assert_eq!(params.questions()[0].ty(), ResourceType::A);
+  /// ```
+  pub fn questions(&self) -> &[Question<'a>] {
+    &self.questions
+  }
+
+  /// Sets how long to wait for the records.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_timeout(std::time::Duration::from_secs(1));
+  /// ```
+  pub fn with_timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Returns how long to wait for the records.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_timeout(std::time::Duration::from_secs(1));
+  ///
+  /// assert_eq!(params.timeout(), std::time::Duration::from_secs(1));
+  /// ```
+  pub const fn timeout(&self) -> Duration {
+    self.timeout
+  }
+
+  /// Sets the IPv4 interface to use for queries.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_ipv4_interface("0.0.0.0".parse().unwrap());
+  /// ```
+  pub fn with_ipv4_interface(mut self, ipv4_interface: Ipv4Addr) -> Self {
+    self.ipv4_interface = Some(ipv4_interface);
+    self
+  }
+
+  /// Returns the IPv4 interface to use for queries.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_ipv4_interface("0.0.0.0".parse().unwrap());
+  ///
+  /// assert_eq!(params.ipv4_interface().unwrap(), &"0.0.0.0".parse::<std::net::Ipv4Addr>().unwrap());
+  /// ```
+  pub const fn ipv4_interface(&self) -> Option<&Ipv4Addr> {
+    self.ipv4_interface.as_ref()
+  }
+
+  /// Sets the IPv6 interface to use for queries.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_ipv6_interface(1);
+  /// ```
+  pub fn with_ipv6_interface(mut self, ipv6_interface: u32) -> Self {
+    self.ipv6_interface = Some(ipv6_interface);
+    self
+  }
+
+  /// Returns the IPv6 interface to use for queries.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_ipv6_interface(1);
+  /// assert_eq!(params.ipv6_interface().unwrap(), 1);
+  /// ```
+  pub const fn ipv6_interface(&self) -> Option<u32> {
+    self.ipv6_interface
+  }
+
+  /// Sets whether to disable IPv4 for MDNS operations.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_disable_ipv4(true);
+  /// ```
+  pub fn with_disable_ipv4(mut self, disable_ipv4: bool) -> Self {
+    self.disable_ipv4 = disable_ipv4;
+    self
+  }
+
+  /// Returns whether to disable IPv4 for MDNS operations.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_disable_ipv4(true);
+  ///
+  /// assert_eq!(params.disable_ipv4(), true);
+  /// ```
+  pub const fn disable_ipv4(&self) -> bool {
+    self.disable_ipv4
+  }
+
+  /// Sets whether to disable IPv6 for MDNS operations.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_disable_ipv6(true);
+  /// ```
+  pub fn with_disable_ipv6(mut self, disable_ipv6: bool) -> Self {
+    self.disable_ipv6 = disable_ipv6;
+    self
+  }
+
+  /// Returns whether to disable IPv6 for MDNS operations.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_disable_ipv6(true);
+  ///
+  /// assert_eq!(params.disable_ipv6(), true);
+  /// ```
+  pub const fn disable_ipv6(&self) -> bool {
+    self.disable_ipv6
+  }
+
+  /// Returns the configured maximum payload size for mDNS message packets.
+  ///
+  /// Default is `1500` bytes.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_max_payload_size(1500);
+  ///
+  /// assert_eq!(params.max_payload_size(), 1500);
+  /// ```
+  #[inline]
+  pub const fn max_payload_size(&self) -> usize {
+    self.max_payload_size
+  }
+
+  /// Sets the maximum payload size for mDNS message packets.
+  ///
+  /// Default is `1500` bytes.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::RecordQueryParam;
+  ///
+  /// let params = RecordQueryParam::new()
+  ///   .with_max_payload_size(1500);
+  /// ```
+  #[inline]
+  pub const fn with_max_payload_size(mut self, max_payload_size: usize) -> Self {
+    self.max_payload_size = max_payload_size;
+    self
+  }
+}
+
+impl Default for RecordQueryParam<'_> {
+  #[inline]
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// An address of a host, returned when a `.local` hostname is resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HostAddr {
+  ip: IpAddr,
+  zone: Option<u32>,
+}
+
+impl HostAddr {
+  /// Creates a new host address, `zone` is the scope id of a link-local IPv6 address.
+  #[inline]
+  pub const fn new(ip: IpAddr, zone: Option<u32>) -> Self {
+    Self { ip, zone }
+  }
+
+  /// Returns the IP address of the host.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::HostAddr;
+  /// use std::net::{IpAddr, Ipv4Addr};
+  ///
+  /// let addr = HostAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), None);
+  /// assert_eq!(addr.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+  /// ```
+  #[inline]
+  pub const fn ip(&self) -> IpAddr {
+    self.ip
+  }
+
+  /// Returns the scope id of a link-local IPv6 address, the index of the interface
+  /// the address was received on.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::HostAddr;
+  /// use std::net::IpAddr;
+  ///
+  /// let addr = HostAddr::new(IpAddr::V6("fe80::1".parse().unwrap()), Some(2));
+  /// assert_eq!(addr.zone(), Some(2));
+  /// ```
+  #[inline]
+  pub const fn zone(&self) -> Option<u32> {
+    self.zone
+  }
+
+  /// Returns the socket address of the host for `port`, with the scope id set for a
+  /// link-local IPv6 address.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::HostAddr;
+  /// use std::net::{IpAddr, SocketAddr};
+  ///
+  /// let addr = HostAddr::new(IpAddr::V6("fe80::1".parse().unwrap()), Some(2));
+  /// let SocketAddr::V6(addr) = addr.to_socket_addr(80) else { unreachable!() };
+  /// assert_eq!(addr.scope_id(), 2);
+  /// ```
+  #[inline]
+  pub const fn to_socket_addr(&self, port: u16) -> SocketAddr {
+    match self.ip {
+      IpAddr::V4(ip) => SocketAddr::V4(SocketAddrV4::new(ip, port)),
+      IpAddr::V6(ip) => SocketAddr::V6(SocketAddrV6::new(
+        ip,
+        port,
+        0,
+        match self.zone {
+          Some(zone) => zone,
+          None => 0,
+        },
+      )),
+    }
+  }
+}
+
+/// Types for `tokio` runtime
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub mod tokio {
+  use std::io;
+
+  use super::{
+    HostAddr, QueryParam, RecordQueryParam, ResolveParam,
+    service::Service,
+    worksteal::{Browser, RecordEntry, ServiceEntry},
+  };
+  use mdns_proto::proto::Label;
+
+  pub use agnostic_net::{runtime::tokio::TokioRuntime as Runtime, tokio::Net};
+  pub use async_channel as channel;
+
+  /// A server that can be used with `tokio` runtime
+  pub type Server = super::worksteal::Server<Net, Service>;
+
+  /// A `.local` hostname and a port that can be passed to the `connect` calls of `Net`
+  pub type LocalHost = super::worksteal::LocalHost<Net>;
+
+  /// Looks up a given service, in a domain, waiting at most
+  /// for a timeout before finishing the query. The results are streamed
+  /// to a channel. Sends will not block, so clients should make sure to
+  /// either read or buffer. This method will attempt to stop the query
+  /// on cancellation.
+  #[inline]
+  pub async fn query(params: QueryParam<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
+    super::worksteal::query::<Net>(params, tx).await
+  }
+
+  /// Similar to [`query`], however it uses all the default parameters
+  #[inline]
+  pub async fn lookup(service: Label<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
+    super::worksteal::lookup::<Net>(service, tx).await
+  }
+
+  /// Sends the questions of `params` in one packet, waiting at most for a timeout
+  /// before finishing the query. The records answering the questions are streamed
+  /// to a channel.
+  #[inline]
+  pub async fn query_records(
+    params: RecordQueryParam<'_>,
+    tx: channel::Sender<RecordEntry>,
+  ) -> io::Result<()> {
+    super::worksteal::query_records::<Net>(params, tx).await
+  }
+
+  /// Browses a given service, in a domain, until cancelled. The returned stream
+  /// tells when a service is added, updated or removed.
+  #[inline]
+  pub async fn browse(params: QueryParam<'_>) -> io::Result<Browser> {
+    super::worksteal::browse::<Net>(params).await
+  }
+
+  /// Resolves a single service instance, e.g. `My Printer._ipp._tcp.local.`, waiting
+  /// at most for a timeout.
+  #[inline]
+  pub async fn resolve(params: ResolveParam<'_>) -> io::Result<ServiceEntry> {
+    super::worksteal::resolve::<Net>(params).await
+  }
+
+  /// Resolves a `.local` hostname, e.g. `printer.local.`, to its addresses, waiting at
+  /// most for a timeout.
+  #[inline]
+  pub async fn resolve_host(params: ResolveParam<'_>) -> io::Result<Vec<HostAddr>> {
+    super::worksteal::resolve_host::<Net>(params).await
+  }
+}
+
+/// Types for `smol` runtime
+#[cfg(feature = "smol")]
+#[cfg_attr(docsrs, doc(cfg(feature = "smol")))]
+pub mod smol {
+  use super::{
+    HostAddr, Label, QueryParam, RecordQueryParam, ResolveParam,
+    service::Service,
+    worksteal::{Browser, RecordEntry, ServiceEntry},
+  };
+  use std::io;
+
+  pub use agnostic_net::{runtime::smol::SmolRuntime as Runtime, smol::Net};
+  pub use async_channel as channel;
+
+  /// A server that can be used with `smol` runtime
+  pub type Server = super::worksteal::Server<Net, Service>;
+
+  /// A `.local` hostname and a port that can be passed to the `connect` calls of `Net`
+  pub type LocalHost = super::worksteal::LocalHost<Net>;
+
+  /// Looks up a given service, in a domain, waiting at most
+  /// for a timeout before finishing the query. The results are streamed
+  /// to a channel. Sends will not block, so clients should make sure to
+  /// either read or buffer. This method will attempt to stop the query
+  /// on cancellation.
+  #[inline]
+  pub async fn query(params: QueryParam<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
+    super::worksteal::query::<Net>(params, tx).await
+  }
+
+  /// Similar to [`query`], however it uses all the default parameters
+  #[inline]
+  pub async fn lookup(service: Label<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
+    super::worksteal::lookup::<Net>(service, tx).await
+  }
+
+  /// Sends the questions of `params` in one packet, waiting at most for a timeout
+  /// before finishing the query. The records answering the questions are streamed
+  /// to a channel.
+  #[inline]
+  pub async fn query_records(
+    params: RecordQueryParam<'_>,
+    tx: channel::Sender<RecordEntry>,
+  ) -> io::Result<()> {
+    super::worksteal::query_records::<Net>(params, tx).await
+  }
+
+  /// Browses a given service, in a domain, until cancelled. The returned stream
+  /// tells when a service is added, updated or removed.
+  #[inline]
+  pub async fn browse(params: QueryParam<'_>) -> io::Result<Browser> {
+    super::worksteal::browse::<Net>(params).await
+  }
+
+  /// Resolves a single service instance, e.g. `My Printer._ipp._tcp.local.`, waiting
+  /// at most for a timeout.
+  #[inline]
+  pub async fn resolve(params: ResolveParam<'_>) -> io::Result<ServiceEntry> {
+    super::worksteal::resolve::<Net>(params).await
+  }
+
+  /// Resolves a `.local` hostname, e.g. `printer.local.`, to its addresses, waiting at
+  /// most for a timeout.
+  #[inline]
+  pub async fn resolve_host(params: ResolveParam<'_>) -> io::Result<Vec<HostAddr>> {
+    super::worksteal::resolve_host::<Net>(params).await
+  }
+}
+
+/// Types for `async-std` runtime
+#[cfg(feature = "async-std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-std")))]
+pub mod async_std {
+  use super::{
+    HostAddr, Label, QueryParam, RecordQueryParam, ResolveParam,
+    service::Service,
+    worksteal::{Browser, RecordEntry, ServiceEntry},
+  };
+  use std::io;
+
+  pub use agnostic_net::{async_std::Net, runtime::async_std::AsyncStdRuntime as Runtime};
+  pub use async_channel as channel;
+
+  /// A server that can be used with `async-std` runtime
+  pub type Server = super::worksteal::Server<Net, Service>;
+
+  /// A `.local` hostname and a port that can be passed to the `connect` calls of `Net`
+  pub type LocalHost = super::worksteal::LocalHost<Net>;
+
+  /// Looks up a given service, in a domain, waiting at most
+  /// for a timeout before finishing the query. The results are streamed
+  /// to a channel. Sends will not block, so clients should make sure to
+  /// either read or buffer. This method will attempt to stop the query
+  /// on cancellation.
+  #[inline]
+  pub async fn query(params: QueryParam<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
+    super::worksteal::query::<Net>(params, tx).await
+  }
+
+  /// Similar to [`query`], however it uses all the default parameters
+  #[inline]
+  pub async fn lookup(service: Label<'_>, tx: channel::Sender<ServiceEntry>) -> io::Result<()> {
+    super::worksteal::lookup::<Net>(service, tx).await
+  }
+
+  /// Sends the questions of `params` in one packet, waiting at most for a timeout
+  /// before finishing the query. The records answering the questions are streamed
+  /// to a channel.
+  #[inline]
+  pub async fn query_records(
+    params: RecordQueryParam<'_>,
+    tx: channel::Sender<RecordEntry>,
+  ) -> io::Result<()> {
+    super::worksteal::query_records::<Net>(params, tx).await
+  }
+
+  /// Browses a given service, in a domain, until cancelled. The returned stream
+  /// tells when a service is added, updated or removed.
+  #[inline]
+  pub async fn browse(params: QueryParam<'_>) -> io::Result<Browser> {
+    super::worksteal::browse::<Net>(params).await
+  }
+
+  /// Resolves a single service instance, e.g. `My Printer._ipp._tcp.local.`, waiting
+  /// at most for a timeout.
+  #[inline]
+  pub async fn resolve(params: ResolveParam<'_>) -> io::Result<ServiceEntry> {
+    super::worksteal::resolve::<Net>(params).await
+  }
+
+  /// Resolves a `.local` hostname, e.g. `printer.local.`, to its addresses, waiting at
+  /// most for a timeout.
+  #[inline]
+  pub async fn resolve_host(params: ResolveParam<'_>) -> io::Result<Vec<HostAddr>> {
+    super::worksteal::resolve_host::<Net>(params).await
   }
 }
 
@@ -9,10 +9,12 @@ use std::{
 
 use super::{IPV4_SIZE, IPV6_SIZE, invalid_input_err, is_fqdn};
 
-use mdns_proto::proto::{Label, ResourceRecord, ResourceType};
+use mdns_proto::{
+  proto::{Label, ResourceRecord, ResourceType},
+  utils::label_eq,
+};
 use smallvec_wrapper::{SmallVec, TinyVec};
 use smol_str::{SmolStr, ToSmolStr, format_smolstr};
-use triomphe::Arc;
 
 const DEFAULT_TTL: u32 = 120;
 const DNS_CLASS_IN: u16 = 1;
@@ -38,6 +40,9 @@ enum ServiceError {
   /// The TXT data is too long
   #[error("TXT record is too long")]
   TxtDataTooLong,
+  /// The subtype is not a single label of at most 63 bytes
+  #[error("{0:?} is not a valid subtype")]
+  InvalidSubtype(SmolStr),
 }
 
 use ptr::PTR;
@@ -179,7 +184,8 @@ pub struct ServiceBuilder<'a> {
   port: Option<u16>,
   ipv4s: TinyVec<Ipv4Addr>,
   ipv6s: TinyVec<Ipv6Addr>,
-  txt: TinyVec<SmolStr>,
+  txt: TinyVec<Vec<u8>>,
+  subtypes: TinyVec<SmolStr>,
   ttl: u32,
   srv_priority: u16,
   srv_weight: u16,
@@ -197,6 +203,7 @@ impl<'a> ServiceBuilder<'a> {
       ipv4s: TinyVec::new(),
       ipv6s: TinyVec::new(),
       txt: TinyVec::new(),
+      subtypes: TinyVec::new(),
       ttl: DEFAULT_TTL,
       srv_priority: 10,
       srv_weight: 1,
@@ -523,31 +530,47 @@ impl<'a> ServiceBuilder<'a> {
   /// ## Example
   ///
   /// ```rust
-  /// use agnostic_mdns::{ServiceBuilder, SmolStr};
+  /// use agnostic_mdns::ServiceBuilder;
   ///
   /// let builder = ServiceBuilder::new("hostname".into(), "_http._tcp".into());
-  /// assert!(builder.txt_records().is_empty());
+  /// assert!(builder.txt_records().next().is_none());
   ///
-  /// let builder = builder.with_txt_record("info".into());
+  /// let builder = builder.with_txt_record("info");
   ///
-  /// assert_eq!(builder.txt_records(), &[SmolStr::new("info")]);
+  /// assert_eq!(builder.txt_records().collect::<Vec<_>>(), [b"info"]);
   /// ```
-  pub fn txt_records(&self) -> &[SmolStr] {
-    &self.txt
+  pub fn txt_records(&self) -> impl Iterator<Item = &[u8]> + '_ {
+    self.txt.iter().map(Vec::as_slice)
   }
 
   /// Sets the TXT records for the service.
   ///
+  /// The records are opaque bytes, they are not required to be UTF-8, the
+  /// `key=value` attributes of a [`TxtRecord`](crate::TxtRecord) are set with
+  /// [`TxtRecord::strings`](crate::TxtRecord::strings).
+  ///
   /// ## Example
   ///
   /// ```rust
-  /// use agnostic_mdns::{ServiceBuilder, SmolStr};
+  /// use agnostic_mdns::{ServiceBuilder, TxtRecord};
   ///
   /// let builder = ServiceBuilder::new("hostname".into(), "_http._tcp".into())
-  ///   .with_txt_records([SmolStr::new("info")].into_iter().collect());
+  ///   .with_txt_records(["info"]);
+  ///
+  /// let txt = TxtRecord::new().with("id", [0xff, 0x00]).unwrap().with_flag("paper").unwrap();
+  /// let builder = builder.with_txt_records(txt.strings());
+  ///
+  /// assert_eq!(
+  ///   builder.txt_records().collect::<Vec<_>>(),
+  ///   [&b"id=\xff\x00"[..], b"paper"],
+  /// );
   /// ```
-  pub fn with_txt_records(mut self, txt: TinyVec<SmolStr>) -> Self {
-    self.txt = txt;
+  pub fn with_txt_records<I, S>(mut self, txt: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<[u8]>,
+  {
+    self.txt = txt.into_iter().map(|s| s.as_ref().to_vec()).collect();
     self
   }
 
@@ -556,22 +579,80 @@ impl<'a> ServiceBuilder<'a> {
   /// ## Example
   ///
   /// ```rust
-  /// use agnostic_mdns::{ServiceBuilder, SmolStr};
+  /// use agnostic_mdns::ServiceBuilder;
   ///
   /// let builder = ServiceBuilder::new("hostname".into(), "_http._tcp".into())
-  ///  .with_txt_record("info".into());
+  ///  .with_txt_record("info")
+  ///  .with_txt_record([b'i', b'd', b'=', 0xff]);
   /// ```
-  pub fn with_txt_record(mut self, txt: SmolStr) -> Self {
-    self.txt.push(txt);
+  pub fn with_txt_record(mut self, txt: impl AsRef<[u8]>) -> Self {
+    self.txt.push(txt.as_ref().to_vec());
+    self
+  }
+
+  /// Gets the current subtypes.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ServiceBuilder;
+  ///
+  /// let builder = ServiceBuilder::new("hostname".into(), "_http._tcp".into());
+  /// assert!(builder.subtypes().is_empty());
+  ///
+  /// let builder = builder.with_subtypes(["_printer"]);
+  /// assert_eq!(builder.subtypes(), ["_printer"]);
+  /// ```
+  pub fn subtypes(&self) -> &[SmolStr] {
+    &self.subtypes
+  }
+
+  /// Sets the subtypes of the service, see RFC 6763, section 7.1.
+  ///
+  /// The service then also answers the `PTR` queries for each subtype name, e.g.
+  /// `_printer._sub._http._tcp.local.`, which are browsed by
+  /// [`QueryParam::with_subtype`](crate::QueryParam::with_subtype).
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ServiceBuilder;
+  ///
+  /// let builder = ServiceBuilder::new("hostname".into(), "_http._tcp".into())
+  ///   .with_subtypes(["_printer", "_scanner"]);
+  ///
+  /// assert_eq!(builder.subtypes(), ["_printer", "_scanner"]);
+  /// ```
+  pub fn with_subtypes<I, S>(mut self, subtypes: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<SmolStr>,
+  {
+    self.subtypes = subtypes.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Pushes a subtype to the list of subtypes.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::ServiceBuilder;
+  ///
+  /// let builder = ServiceBuilder::new("hostname".into(), "_http._tcp".into())
+  ///   .with_subtype("_printer");
+  ///
+  /// assert_eq!(builder.subtypes(), ["_printer"]);
+  /// ```
+  pub fn with_subtype(mut self, subtype: impl Into<SmolStr>) -> Self {
+    self.subtypes.push(subtype.into());
     self
   }
 
   /// Finalize the builder and try to create a new [`Service`].
-  // TODO(reddaly): This interface may need to change to account for "unique
-  // record" conflict rules of the mDNS protocol.  Upon startup, the server should
-  // check to ensure that the instance name does not conflict with other instance
-  // names, and, if required, select a new name.  There may also be conflicting
-  // hostName A/AAAA records.
+  ///
+  /// The instance name and the host name may be changed by the server, if they are
+  /// already owned by another host on the network.
   pub fn finalize(self) -> io::Result<Service> {
     let domain = self.domain.as_ref().map(|d| format_smolstr!("{}.", d));
     let domain = match domain {
@@ -625,6 +706,19 @@ impl<'a> ServiceBuilder<'a> {
     let service_addr = format_smolstr!("{}.{}.", self.service, domain.as_str().trim_matches('.'));
     let instance_addr = format_smolstr!("{}.{}.{}.", self.instance, self.service, domain);
     let enum_addr = format_smolstr!("_services._dns-sd._udp.{}.", domain);
+    let subtype_addrs = self
+      .subtypes
+      .iter()
+      .map(|subtype| {
+        // a subtype is a single label, see RFC 6763, section 7.2
+        if subtype.is_empty() || subtype.len() > 63 || subtype.contains('.') {
+          return Err(invalid_input_err(ServiceError::InvalidSubtype(
+            subtype.clone(),
+          )));
+        }
+        PTR::new(format_smolstr!("{}._sub.{}", subtype, service_addr)).map_err(invalid_input_err)
+      })
+      .collect::<io::Result<TinyVec<_>>>()?;
 
     let srv = SRV::new(self.srv_priority, self.srv_weight, port, hostname.clone())
       .map_err(invalid_input_err)?;
@@ -638,10 +732,12 @@ impl<'a> ServiceBuilder<'a> {
       ipv6s: ipv6s.iter().map(|ip| AAAA::from(*ip)).collect(),
       ipv4s_origin: ipv4s,
       ipv6s_origin: ipv6s,
-      txt: TXT::new(Arc::from_iter(self.txt)).map_err(invalid_input_err)?,
+      txt: TXT::new(self.txt).map_err(invalid_input_err)?,
       service_addr: PTR::new(service_addr).map_err(invalid_input_err)?,
       instance_addr: PTR::new(instance_addr).map_err(invalid_input_err)?,
       enum_addr: PTR::new(enum_addr).map_err(invalid_input_err)?,
+      subtypes: self.subtypes,
+      subtype_addrs,
       ttl: AtomicU32::new(self.ttl),
       srv,
     })
@@ -675,6 +771,10 @@ pub struct Service {
   instance_addr: PTR,
   /// _services._dns-sd._udp.<domain>
   enum_addr: PTR,
+  /// Subtypes of the service (e.g. "_printer")
+  subtypes: TinyVec<SmolStr>,
+  /// Fully qualified subtype addresses, <subtype>._sub.<service>.<domain>
+  subtype_addrs: TinyVec<PTR>,
   ttl: AtomicU32,
   srv: SRV,
 }
@@ -728,12 +828,147 @@ impl Service {
     &self.ipv6s_origin
   }
 
-  /// Returns the TXT records of the mdns service.
+  /// Returns the TXT records of the mdns service, as opaque bytes.
   #[inline]
-  pub fn txt_records(&self) -> &[SmolStr] {
+  pub fn txt_records(&self) -> impl Iterator<Item = &[u8]> + '_ {
     self.txt.strings()
   }
 
+  /// Returns the subtypes of the mdns service.
+  #[inline]
+  pub fn subtypes(&self) -> &[SmolStr] {
+    &self.subtypes
+  }
+
+  /// Returns the shared records of the service.
+  ///
+  /// These are the `PTR` records of the service type, pointing to the instance, and of
+  /// the service type enumeration, pointing to the service type, see RFC 6763, section 9,
+  /// along with the `PTR` records of each subtype, pointing to the instance, see
+  /// RFC 6763, section 7.1.
+  pub fn shared_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> {
+    let ttl = self.ttl();
+
+    [
+      ResourceRecord::new(
+        Label::from(self.service_addr.name()),
+        ResourceType::Ptr,
+        DNS_CLASS_IN,
+        ttl,
+        self.instance_addr.data(),
+      ),
+      ResourceRecord::new(
+        Label::from(self.enum_addr.name()),
+        ResourceType::Ptr,
+        DNS_CLASS_IN,
+        ttl,
+        self.service_addr.data(),
+      ),
+    ]
+    .into_iter()
+    .chain(self.subtype_addrs.iter().map(move |subtype| {
+      ResourceRecord::new(
+        Label::from(subtype.name()),
+        ResourceType::Ptr,
+        DNS_CLASS_IN,
+        ttl,
+        self.instance_addr.data(),
+      )
+    }))
+  }
+
+  /// Returns the unique records of the service.
+  ///
+  /// These are the `SRV` and `TXT` records of the instance and the `A`/`AAAA`
+  /// records of the host, which must be probed for before being announced, see
+  /// [`mdns_proto::prober`].
+  pub fn unique_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> {
+    let instance = Label::from(self.instance_addr.name());
+    let hostname = Label::from(self.hostname.as_str());
+    let ttl = self.ttl();
+
+    [
+      ResourceRecord::new(
+        instance,
+        ResourceType::Srv,
+        DNS_CLASS_IN,
+        ttl,
+        self.srv.data(),
+      ),
+      ResourceRecord::new(
+        instance,
+        ResourceType::Txt,
+        DNS_CLASS_IN,
+        ttl,
+        self.txt.data(),
+      ),
+    ]
+    .into_iter()
+    .chain(
+      self.ipv4s.iter().map(move |ip| {
+        ResourceRecord::new(hostname, ResourceType::A, DNS_CLASS_IN, ttl, ip.data())
+      }),
+    )
+    .chain(self.ipv6s.iter().map(move |ip| {
+      ResourceRecord::new(hostname, ResourceType::AAAA, DNS_CLASS_IN, ttl, ip.data())
+    }))
+  }
+
+  /// Returns a copy of the service in which `name`, owned by another host, is replaced
+  /// by a new name, along with the new name.
+  ///
+  /// `name` must be either the instance name or the host name of the service. A new
+  /// instance name is made by appending or incrementing a number in parentheses,
+  /// e.g. `My Printer (2)`, and a new host name by appending or incrementing a number
+  /// to its first label, e.g. `host-2.local.`.
+  pub(super) fn rename(&self, name: Label<'_>) -> Option<(Self, SmolStr)> {
+    if Label::from(self.instance_addr.name()) == name {
+      let instance = next_instance_name(&self.instance);
+      let service = self.renamed(instance, self.hostname.clone()).ok()?;
+      let name = Label::from(service.instance_addr.name()).to_smolstr();
+      return Some((service, name));
+    }
+
+    if Label::from(self.hostname.as_str()) == name {
+      let hostname = next_host_name(&self.hostname);
+      let service = self.renamed(self.instance.clone(), hostname).ok()?;
+      let name = Label::from(service.hostname.as_str()).to_smolstr();
+      return Some((service, name));
+    }
+
+    None
+  }
+
+  fn renamed(&self, instance: SmolStr, hostname: SmolStr) -> io::Result<Self> {
+    let instance_addr = format_smolstr!("{}.{}.{}.", instance, self.service, self.domain);
+    let srv = SRV::new(
+      self.srv.priority(),
+      self.srv.weight(),
+      self.srv.port(),
+      hostname.clone(),
+    )
+    .map_err(invalid_input_err)?;
+
+    Ok(Self {
+      instance,
+      service: self.service.clone(),
+      domain: self.domain.clone(),
+      hostname,
+      ipv4s_origin: self.ipv4s_origin.clone(),
+      ipv6s_origin: self.ipv6s_origin.clone(),
+      ipv4s: self.ipv4s.clone(),
+      ipv6s: self.ipv6s.clone(),
+      txt: self.txt.clone(),
+      service_addr: self.service_addr.clone(),
+      instance_addr: PTR::new(instance_addr).map_err(invalid_input_err)?,
+      enum_addr: self.enum_addr.clone(),
+      subtypes: self.subtypes.clone(),
+      subtype_addrs: self.subtype_addrs.clone(),
+      ttl: AtomicU32::new(self.ttl()),
+      srv,
+    })
+  }
+
   #[auto_enums::auto_enum(Iterator)]
   pub(super) fn fetch_answers<'a>(
     &'a self,
@@ -745,11 +980,21 @@ impl Service {
     let instance_addr_label = Label::from(self.instance_addr.name());
     let hostname_label = Label::from(self.hostname.as_str());
 
+    // the question may be compressed, and differ in case
     match () {
-      () if enum_addr_label.eq(&qn) => self.service_enum(qn, rt),
-      () if service_addr_label.eq(&qn) => self.service_records(qn, rt),
-      () if instance_addr_label.eq(&qn) => self.instance_records(qn, rt),
-      () if hostname_label.eq(&qn) && matches!(rt, ResourceType::A | ResourceType::AAAA) => {
+      () if label_eq(&enum_addr_label, &qn) => self.service_enum(qn, rt),
+      () if label_eq(&service_addr_label, &qn) => self.service_records(qn, rt),
+      () if self
+        .subtype_addrs
+        .iter()
+        .any(|subtype| label_eq(&Label::from(subtype.name()), &qn)) =>
+      {
+        self.service_records(qn, rt)
+      }
+      () if label_eq(&instance_addr_label, &qn) => self.instance_records(qn, rt),
+      () if label_eq(&hostname_label, &qn)
+        && matches!(rt, ResourceType::A | ResourceType::AAAA) =>
+      {
         self.instance_records(qn, rt)
       }
       _ => core::iter::empty(),
@@ -835,10 +1080,10 @@ impl Service {
           self.srv.data(),
         ));
         recs
-          // Add the A record
-          .chain(self.instance_records(self.instance_addr.name().into(), ResourceType::A))
+          // Add the A record, owned by the target host of the SRV record
+          .chain(self.instance_records(self.hostname.as_str().into(), ResourceType::A))
           // Add the AAAA record
-          .chain(self.instance_records(self.instance_addr.name().into(), ResourceType::AAAA))
+          .chain(self.instance_records(self.hostname.as_str().into(), ResourceType::AAAA))
           .collect::<SmallVec<_>>()
           .into_iter()
       }
@@ -856,3 +1101,39 @@ impl Service {
     }
   }
 }
+
+/// Returns the next instance name after a conflict, `My Printer` becomes `My Printer (2)`,
+/// and `My Printer (2)` becomes `My Printer (3)`.
+fn next_instance_name(instance: &str) -> SmolStr {
+  let numbered = instance
+    .strip_suffix(')')
+    .and_then(|s| s.rsplit_once(" ("))
+    .and_then(|(base, n)| n.parse::<u32>().ok().map(|n| (base, n)));
+
+  match numbered {
+    Some((base, n)) => format_smolstr!("{base} ({})", n.saturating_add(1)),
+    None => format_smolstr!("{instance} (2)"),
+  }
+}
+
+/// Returns the next host name after a conflict, `host.local.` becomes `host-2.local.`,
+/// and `host-2.local.` becomes `host-3.local.`.
+fn next_host_name(hostname: &str) -> SmolStr {
+  let (first, rest) = match hostname.split_once('.') {
+    Some((first, rest)) => (first, Some(rest)),
+    None => (hostname, None),
+  };
+
+  let (base, n) = match first
+    .rsplit_once('-')
+    .and_then(|(base, n)| n.parse::<u32>().ok().map(|n| (base, n)))
+  {
+    Some((base, n)) => (base, n.saturating_add(1)),
+    None => (first, 2),
+  };
+
+  match rest {
+    Some(rest) => format_smolstr!("{base}-{n}.{rest}"),
+    None => format_smolstr!("{base}-{n}"),
+  }
+}
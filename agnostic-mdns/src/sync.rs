@@ -1,10 +1,16 @@
 use core::convert::Infallible;
 use mdns_proto::proto::{Label, ResourceRecord, ResourceType};
+use smol_str::SmolStr;
 
 use crate::service::Service;
 
+mod client;
 mod server;
 
+#[cfg(test)]
+mod tests;
+
+pub use client::resolve_host;
 pub use server::{Closer, Server};
 
 /// The interface used to integrate with the server and
@@ -26,6 +32,35 @@ pub trait Zone {
     name: Label<'a>,
     rt: ResourceType,
   ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error>;
+
+  /// Returns the shared records of the zone.
+  ///
+  /// The server announces these records, along with the unique records, once the unique
+  /// records are claimed, see [`mdns_proto::announcer`]. Defaults to no shared records.
+  fn shared_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    core::iter::empty()
+  }
+
+  /// Returns the unique records of the zone.
+  ///
+  /// The server probes for these records before answering any query, see
+  /// [`mdns_proto::prober`]. Defaults to no unique records.
+  fn unique_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    core::iter::empty()
+  }
+
+  /// Returns a copy of the zone in which `name`, one of the names of the unique records
+  /// owned by another host, is replaced by a new name, along with the new name.
+  ///
+  /// The server probes again with the renamed zone. Defaults to `None`, the zone
+  /// cannot be renamed and the server stops serving it.
+  fn rename(&self, name: Label<'_>) -> Option<(Self, SmolStr)>
+  where
+    Self: Sized,
+  {
+    let _ = name;
+    None
+  }
 }
 
 macro_rules! auto_impl {
@@ -49,6 +84,18 @@ macro_rules! auto_impl {
         ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
           (**self).additionals(name, rt)
         }
+
+        fn shared_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+          (**self).shared_records()
+        }
+
+        fn unique_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+          (**self).unique_records()
+        }
+
+        fn rename(&self, name: Label<'_>) -> Option<(Self, SmolStr)> {
+          (**self).rename(name).map(|(zone, name)| (<$name>::new(zone), name))
+        }
       }
     )*
   };
@@ -74,4 +121,16 @@ impl Zone for Service {
   ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
     Ok(std::iter::empty())
   }
+
+  fn shared_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    Service::shared_records(self)
+  }
+
+  fn unique_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    Service::unique_records(self)
+  }
+
+  fn rename(&self, name: Label<'_>) -> Option<(Self, SmolStr)> {
+    Service::rename(self, name)
+  }
 }
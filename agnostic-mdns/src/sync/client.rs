@@ -0,0 +1,150 @@
+use std::{
+  io::{self, ErrorKind},
+  net::{IpAddr, UdpSocket},
+  time::{Duration, Instant},
+};
+
+use iprobe::{ipv4, ipv6};
+use smallvec_wrapper::SmallVec;
+use smol_str::ToSmolStr;
+
+use crate::{
+  Buffer, HostAddr, IPV4_MDNS, IPV6_MDNS, MDNS_PORT, ResolveParam,
+  utils::{
+    HOST_QUERY_INTERVAL, HOST_QUIET_PERIOD, encode_host_query, host_addrs, unicast_udp4_socket,
+    unicast_udp6_socket,
+  },
+};
+
+/// How long a socket is waited on before the other one is.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Resolves a `.local` hostname, e.g. `printer.local.`, to its addresses, waiting at most
+/// for a timeout.
+///
+/// The `A` and `AAAA` records of the host are asked for, from an ephemeral port, the
+/// responders then answer with a conventional unicast response, see RFC 6762,
+/// section 6.7. The query is sent again until a host answers, after 1 second, then at
+/// doubling intervals, see RFC 6762, section 5.2. The addresses of the responses naming
+/// the host are collected until no response is received for a short while, a link-local
+/// IPv6 address has the scope id of the interface it was received on.
+/// Returns an error of kind [`io::ErrorKind::TimedOut`] if no host answers in time.
+pub fn resolve_host(params: ResolveParam<'_>) -> io::Result<Vec<HostAddr>> {
+  let host = params.name.to_smolstr();
+  let id = fastrand::u16(..);
+  let query = encode_host_query(id, &host)?;
+
+  let mut conns = Vec::with_capacity(2);
+  if !params.disable_ipv4 && ipv4() {
+    match unicast_udp4_socket(params.ipv4_interface) {
+      Ok(conn) => conns.push((conn, IpAddr::from(IPV4_MDNS))),
+      Err(e) => tracing::error!(err=%e, "mdns client: failed to bind to udp4 port"),
+    }
+  }
+  if !params.disable_ipv6 && ipv6() {
+    match unicast_udp6_socket(params.ipv6_interface) {
+      Ok(conn) => conns.push((conn, IpAddr::from(IPV6_MDNS))),
+      Err(e) => tracing::error!(err=%e, "mdns client: failed to bind to udp6 port"),
+    }
+  }
+
+  if conns.is_empty() {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "at least one of IPv4 and IPv6 must be enabled for querying",
+    ));
+  }
+
+  tracing::info!("mdns client: resolving host {}", host);
+  for (conn, _) in &conns {
+    conn.set_nonblocking(false)?;
+  }
+  send(&conns, &query)?;
+
+  let deadline = Instant::now() + params.timeout;
+  let mut interval = HOST_QUERY_INTERVAL;
+  let mut resend = Instant::now() + interval;
+  // once a host answered, the other responses are only waited for a short while
+  let mut quiet = deadline;
+  let mut buf = Buffer::zerod(params.max_payload_size);
+  let mut addrs = Vec::new();
+  loop {
+    // until then the query is sent again
+    if addrs.is_empty() && Instant::now() >= resend {
+      tracing::debug!("mdns client: resending the query for host {}", host);
+      if let Err(e) = send(&conns, &query) {
+        tracing::error!(err=%e, "mdns client: failed to resend query");
+      }
+      interval *= 2;
+      resend = Instant::now() + interval;
+    }
+
+    for (conn, _) in &conns {
+      let remaining = quiet.saturating_duration_since(Instant::now());
+      if remaining.is_zero() {
+        if addrs.is_empty() {
+          return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "mdns client: resolve host timed out",
+          ));
+        }
+        return Ok(addrs);
+      }
+
+      if let Some(received) = recv(conn, &mut buf, id, &host, remaining.min(POLL_INTERVAL)) {
+        for addr in received {
+          if !addrs.contains(&addr) {
+            addrs.push(addr);
+          }
+        }
+        quiet = deadline.min(Instant::now() + HOST_QUIET_PERIOD);
+      }
+    }
+  }
+}
+
+/// Sends the host `query` to the mDNS groups, on all the sockets.
+///
+/// Returns the error of the last send if none of them succeeds.
+fn send(conns: &[(UdpSocket, IpAddr)], query: &[u8]) -> io::Result<()> {
+  let mut res = Ok(());
+  let mut sent = false;
+  for (conn, group) in conns {
+    match conn.send_to(query, (*group, MDNS_PORT)) {
+      Ok(_) => sent = true,
+      Err(e) => {
+        tracing::error!(err=%e, "mdns client: failed to send query");
+        res = Err(e);
+      }
+    }
+  }
+  if sent { Ok(()) } else { res }
+}
+
+/// Waits at most `timeout` for a response to the query `id` naming `host` on `conn`.
+fn recv(
+  conn: &UdpSocket,
+  buf: &mut [u8],
+  id: u16,
+  host: &str,
+  timeout: Duration,
+) -> Option<SmallVec<HostAddr>> {
+  if let Err(e) = conn.set_read_timeout(Some(timeout)) {
+    tracing::error!(err=%e, "mdns client: failed to set read timeout");
+    return None;
+  }
+
+  match conn.recv_from(buf) {
+    Ok((size, src)) => {
+      tracing::trace!(from=%src, data=?&buf[..size], "mdns client: received packet");
+      let addrs = host_addrs(src, &buf[..size], id, host);
+      (!addrs.is_empty()).then_some(addrs)
+    }
+    Err(e) => {
+      if !matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) {
+        tracing::error!(err=%e, "mdns client: failed to receive packet");
+      }
+      None
+    }
+  }
+}
@@ -1,23 +1,32 @@
 use std::{
   io::{self, ErrorKind},
   net::{SocketAddr, UdpSocket},
+  ops::ControlFlow,
   sync::{
     Arc,
     atomic::{AtomicBool, Ordering},
   },
+  time::Instant,
 };
 
 use crate::{
-  Buffer, MDNS_PORT, ServerOptions,
-  utils::{multicast_udp4_socket, multicast_udp6_socket},
+  Buffer, MDNS_PORT, ServerEvent, ServerOptions,
+  utils::{
+    Announce, Claim, Multicasted, Response, Truncated, multicast_addr, multicast_udp4_socket,
+    multicast_udp6_socket,
+  },
 };
+use async_channel::{Receiver, Sender};
 use iprobe::{ipv4, ipv6};
 use mdns_proto::{
   error::{BufferType, ProtoError},
-  proto::{Message, Question, ResourceRecord},
+  prober::{ProbeEvent, Prober},
+  proto::{Label, Message, MessageType, Question, ResourceRecord},
   server::{Endpoint, SlabEndpoint},
+  suppression::SlabSuppression,
 };
 use smallvec_wrapper::SmallVec;
+use smol_str::ToSmolStr;
 
 use super::Zone;
 
@@ -30,7 +39,8 @@ impl Closer {
     Self(Arc::new(AtomicBool::new(false)))
   }
 
-  /// Closes the server.
+  /// Closes the server, a goodbye for the announced records is sent by [`Server::run`]
+  /// before it returns, see RFC 6762, section 10.1.
   ///
   /// Returns `true` if this invocation closed the server, `false` if the server was already closed.
   pub fn close(&self) -> bool {
@@ -65,6 +75,11 @@ pub struct Server<Z> {
   closer: Closer,
   log_empty_responses: bool,
   max_payload_size: usize,
+  prober: Prober<Instant>,
+  events_tx: Sender<ServerEvent>,
+  events_rx: Receiver<ServerEvent>,
+  updates_tx: Sender<Z>,
+  updates_rx: Receiver<Z>,
 }
 
 impl<Z> Server<Z>
@@ -72,6 +87,10 @@ where
   Z: Zone,
 {
   /// Creates a new server with the given zone and options.
+  ///
+  /// Blocks the current thread until the unique records of the zone are claimed, see
+  /// RFC 6762, section 8.1. The zone is renamed if some of its names are owned by
+  /// another host, see [`Zone::rename`], and this fails if it cannot be renamed.
   pub fn new(zone: Z, opts: ServerOptions) -> io::Result<(Self, Closer)> {
     let v4 = if ipv4() {
       match multicast_udp4_socket(opts.ipv4_interface, MDNS_PORT) {
@@ -97,16 +116,69 @@ where
       None
     };
 
+    let (events_tx, events_rx) = async_channel::unbounded();
+    let mut endpoint = Endpoint::new();
+    let mut buf = Buffer::zerod(opts.max_payload_size);
+    let mut zone = zone;
+    let prober = loop {
+      let mut claim = Claim::new(zone.unique_records());
+      let index = loop {
+        if claim.is_claimed() {
+          break None;
+        }
+
+        if let ControlFlow::Break(index) = Self::poll(
+          &mut endpoint,
+          v4.as_ref(),
+          v6.as_ref(),
+          &mut buf,
+          &zone,
+          &mut claim,
+          &mut None,
+          &mut Truncated::default(),
+          &mut Multicasted::default(),
+          &mut SlabSuppression::new(),
+          opts.log_empty_responses,
+          opts.max_payload_size,
+        ) {
+          break Some(index);
+        }
+      };
+
+      let Some(index) = index else {
+        break claim.into_prober();
+      };
+
+      match Self::rename(&zone, claim.name(index), &events_tx) {
+        Some(renamed) => {
+          drop(claim);
+          zone = renamed;
+        }
+        None => {
+          return Err(io::Error::new(
+            ErrorKind::AddrInUse,
+            "the unique records of the zone are already owned by another host",
+          ));
+        }
+      }
+    };
+
     let closer = Closer::new();
+    let (updates_tx, updates_rx) = async_channel::unbounded();
     Ok((
       Self {
         zone,
-        endpoint: Endpoint::new(),
+        endpoint,
         v4_udp: v4,
         v6_udp: v6,
         closer: closer.clone(),
         log_empty_responses: opts.log_empty_responses,
         max_payload_size: opts.max_payload_size,
+        prober,
+        events_tx,
+        events_rx,
+        updates_tx,
+        updates_rx,
       },
       closer,
     ))
@@ -117,82 +189,339 @@ where
     &self.zone
   }
 
-  /// Runs the server, blocking the current thread until the server is stopped.
+  /// Returns a receiver of the events of the server, e.g. the zone was renamed
+  /// because one of its names is owned by another host.
+  ///
+  /// Events happened while creating the server are also received.
+  pub fn events(&self) -> Receiver<ServerEvent> {
+    self.events_rx.clone()
+  }
+
+  /// Returns a sender to replace the zone of the server, e.g. when its records changed.
+  ///
+  /// A goodbye is sent for the records removed from the zone, the unique records of the
+  /// new zone are probed for if their names changed, and all its records are announced
+  /// again, see RFC 6762, section 8.4 and 10.1.
+  pub fn updater(&self) -> Sender<Z> {
+    self.updates_tx.clone()
+  }
+
+  /// Runs the server, blocking the current thread until the server is stopped by its
+  /// [`Closer`], or because a name of the zone is owned by another host and the zone
+  /// cannot be renamed, see [`ServerEvent::Lost`].
+  ///
+  /// All the records of the zone are announced first, see RFC 6762, section 8.3.
   pub fn run(self) {
     let Self {
-      zone,
+      mut zone,
       mut endpoint,
       v4_udp,
       v6_udp,
       closer,
       log_empty_responses,
       max_payload_size,
+      prober,
+      events_tx,
+      events_rx: _,
+      updates_tx: _,
+      updates_rx,
     } = self;
 
     let mut buf = Buffer::zerod(max_payload_size);
+    let mut prober = Some(prober);
+    let mut owned = None;
+    let mut truncated = Truncated::default();
+    let mut multicasted = Multicasted::default();
+    let mut suppression = SlabSuppression::new();
 
     loop {
-      if closer.is_closed() {
-        endpoint.close();
-        return;
-      }
+      let mut claim = match prober.take() {
+        Some(prober) => Claim::with_prober(prober, zone.unique_records()),
+        None => Claim::with_owned(zone.unique_records(), owned.take()),
+      };
 
-      if let Some(udp) = v4_udp.as_ref() {
-        let v4_data = match udp.recv_from(&mut buf) {
-          Ok((size, addr)) => {
-            if size == 0 {
-              None
-            } else {
-              Some((size, addr))
+      let mut announce = claim
+        .is_claimed()
+        .then(|| Announce::new(zone.shared_records(), zone.unique_records()));
+
+      let next = loop {
+        if closer.is_closed() {
+          // the announced records are no longer valid, see RFC 6762, section 10.1
+          if let Some(goodbye) = announce.as_ref().and_then(|a| a.encode_goodbye(&[])) {
+            Self::send_goodbye(v4_udp.as_ref(), v6_udp.as_ref(), goodbye);
+          }
+          endpoint.close();
+          return;
+        }
+
+        if let ControlFlow::Break(index) = Self::poll(
+          &mut endpoint,
+          v4_udp.as_ref(),
+          v6_udp.as_ref(),
+          &mut buf,
+          &zone,
+          &mut claim,
+          &mut announce,
+          &mut truncated,
+          &mut multicasted,
+          &mut suppression,
+          log_empty_responses,
+          max_payload_size,
+        ) {
+          let name = claim.name(index);
+          match Self::rename(&zone, name, &events_tx) {
+            Some(renamed) => break renamed,
+            // the zone is no longer served, the server stops
+            None => {
+              if closer.close() {
+                let name = name.to_smolstr();
+                let _ = events_tx.try_send(ServerEvent::Lost { name });
+              }
+              continue;
             }
           }
-          Err(e) => match e.kind() {
-            ErrorKind::WouldBlock => None,
-            _ => {
-              tracing::error!(err=%e, "mdns server: fail to receive data");
-              None
+        }
+
+        if let Ok(updated) = updates_rx.try_recv() {
+          // say goodbye to the records removed from the zone
+          if let Some(announce) = announce.as_ref() {
+            let kept = updated
+              .shared_records()
+              .chain(updated.unique_records())
+              .collect::<SmallVec<_>>();
+            if let Some(goodbye) = announce.encode_goodbye(&kept) {
+              Self::send_goodbye(v4_udp.as_ref(), v6_udp.as_ref(), goodbye);
             }
-          },
-        };
+          }
+          owned = claim.owned_names();
+          break updated;
+        }
+      };
+
+      drop(announce);
+      drop(claim);
+      zone = next;
+    }
+  }
 
-        if let Some((size, addr)) = v4_data {
-          let data = &buf[..size];
-          Self::handle_query(&mut endpoint, udp, addr, data, &zone, log_empty_responses);
+  /// Renames the zone after `name` is found to be owned by another host.
+  fn rename(zone: &Z, name: Label<'_>, events_tx: &Sender<ServerEvent>) -> Option<Z> {
+    let Some((renamed, to)) = zone.rename(name) else {
+      tracing::error!(name=%name, "mdns server: name already owned by another host");
+      return None;
+    };
+
+    let from = name.to_smolstr();
+    tracing::warn!(from=%from, to=%to, "mdns server: name already owned by another host, renamed");
+    let _ = events_tx.try_send(ServerEvent::Renamed { from, to });
+    Some(renamed)
+  }
+
+  /// Drives the timers of the prober and the announcer, and handles the pending packets,
+  /// without blocking.
+  ///
+  /// Breaks with the index of the first unique record whose name is owned by another host.
+  #[allow(clippy::too_many_arguments)]
+  fn poll<'a>(
+    endpoint: &mut SlabEndpoint,
+    v4_udp: Option<&UdpSocket>,
+    v6_udp: Option<&UdpSocket>,
+    buf: &mut [u8],
+    zone: &'a Z,
+    claim: &mut Claim<'a>,
+    announce: &mut Option<Announce<'a>>,
+    truncated: &mut Truncated,
+    multicasted: &mut Multicasted,
+    suppression: &mut SlabSuppression<Instant>,
+    log_empty_responses: bool,
+    max_payload_size: usize,
+  ) -> ControlFlow<usize> {
+    let now = Instant::now();
+    if let Some(announce) = announce.as_mut() {
+      if announce
+        .poll_timeout()
+        .is_some_and(|deadline| deadline <= now)
+      {
+        match announce.handle_timeout(now, multicasted) {
+          Some(Ok(data)) => Self::multicast(v4_udp, v6_udp, &data, "announcement"),
+          Some(Err(e)) => tracing::error!(err=%e, "mdns server: fail to serialize announcement"),
+          None => {}
         }
       }
+    }
 
-      if let Some(udp) = v6_udp.as_ref() {
-        let v6_data = match udp.recv_from(&mut buf) {
-          Ok((size, addr)) => Some((size, addr)),
-          Err(e) => match e.kind() {
-            ErrorKind::WouldBlock => None,
-            _ => {
-              tracing::error!(err=%e, "mdns server: fail to receive data");
-              None
-            }
-          },
-        };
+    let mut events = SmallVec::<ProbeEvent>::new();
+    if let Some(deadline) = claim.poll_timeout() {
+      if deadline <= now {
+        events.extend(claim.handle_timeout(now));
+      }
+    }
+
+    if truncated
+      .poll_timeout()
+      .is_some_and(|deadline| deadline <= now)
+    {
+      for (addr, query) in truncated.handle_timeout(now) {
+        let udp = if addr.is_ipv4() { v4_udp } else { v6_udp };
+        match (udp, query) {
+          (Some(udp), Ok(data)) => events.extend(Self::handle_query(
+            endpoint,
+            udp,
+            addr,
+            &data,
+            zone,
+            claim,
+            multicasted,
+            suppression,
+            log_empty_responses,
+            max_payload_size,
+          )),
+          (None, _) => {}
+          (_, Err(e)) => {
+            tracing::error!(from=%addr, err=%e, "mdns server: fail to merge truncated query")
+          }
+        }
+      }
+    }
+
+    if let Some(udp) = v4_udp {
+      let v4_data = match udp.recv_from(buf) {
+        Ok((size, addr)) => {
+          if size == 0 {
+            None
+          } else {
+            Some((size, addr))
+          }
+        }
+        Err(e) => match e.kind() {
+          ErrorKind::WouldBlock => None,
+          _ => {
+            tracing::error!(err=%e, "mdns server: fail to receive data");
+            None
+          }
+        },
+      };
+
+      if let Some((size, addr)) =
+        v4_data.filter(|(size, addr)| !truncated.hold(*addr, &buf[..*size]))
+      {
+        let data = &buf[..size];
+        events.extend(Self::handle_query(
+          endpoint,
+          udp,
+          addr,
+          data,
+          zone,
+          claim,
+          multicasted,
+          suppression,
+          log_empty_responses,
+          max_payload_size,
+        ));
+      }
+    }
+
+    if let Some(udp) = v6_udp {
+      let v6_data = match udp.recv_from(buf) {
+        Ok((size, addr)) => Some((size, addr)),
+        Err(e) => match e.kind() {
+          ErrorKind::WouldBlock => None,
+          _ => {
+            tracing::error!(err=%e, "mdns server: fail to receive data");
+            None
+          }
+        },
+      };
+
+      if let Some((size, addr)) =
+        v6_data.filter(|(size, addr)| !truncated.hold(*addr, &buf[..*size]))
+      {
+        let data = &buf[..size];
+        events.extend(Self::handle_query(
+          endpoint,
+          udp,
+          addr,
+          data,
+          zone,
+          claim,
+          multicasted,
+          suppression,
+          log_empty_responses,
+          max_payload_size,
+        ));
+      }
+    }
+
+    for event in events {
+      match event {
+        ProbeEvent::Probe { unicast_response } => match claim.encode_probe(unicast_response) {
+          Ok(probe) => Self::multicast(v4_udp, v6_udp, &probe, "probe"),
+          Err(e) => tracing::error!(err=%e, "mdns server: fail to serialize probe"),
+        },
+        ProbeEvent::Won => {
+          tracing::info!("mdns server: claimed the unique records of the zone");
+          *announce = Some(Announce::new(zone.shared_records(), zone.unique_records()));
+        }
+        ProbeEvent::Deferred => {
+          tracing::info!("mdns server: lost a simultaneous probe tiebreak, probing again");
+        }
+        ProbeEvent::Lost { index } => return ControlFlow::Break(index),
+        ProbeEvent::Conflict => {
+          tracing::warn!("mdns server: conflicting records detected, probing again");
+          claim.restart();
+          *announce = None;
+        }
+      }
+    }
+
+    ControlFlow::Continue(())
+  }
+
+  fn send_goodbye(
+    v4_udp: Option<&UdpSocket>,
+    v6_udp: Option<&UdpSocket>,
+    goodbye: io::Result<Vec<u8>>,
+  ) {
+    match goodbye {
+      Ok(data) => Self::multicast(v4_udp, v6_udp, &data, "goodbye"),
+      Err(e) => tracing::error!(err=%e, "mdns server: fail to serialize goodbye"),
+    }
+  }
 
-        if let Some((size, addr)) = v6_data {
-          let data = &buf[..size];
-          Self::handle_query(&mut endpoint, udp, addr, data, &zone, log_empty_responses);
+  /// Sends `data` to the mDNS multicast group on every socket.
+  fn multicast(v4_udp: Option<&UdpSocket>, v6_udp: Option<&UdpSocket>, data: &[u8], what: &str) {
+    for udp in v4_udp.into_iter().chain(v6_udp) {
+      let to = match udp.local_addr() {
+        Ok(local) => multicast_addr(&local),
+        Err(e) => {
+          tracing::error!(err=%e, "mdns server: fail to get local address");
+          continue;
         }
+      };
+      tracing::trace!(to=%to, data=?data, "mdns server: sending {what}");
+      if let Err(e) = udp.send_to(data, to) {
+        tracing::error!(to=%to, err=%e, "mdns server: fail to send {what}");
       }
     }
   }
 
+  #[allow(clippy::too_many_arguments)]
   fn handle_query(
     endpoint: &mut SlabEndpoint,
     conn: &UdpSocket,
     addr: SocketAddr,
     data: &[u8],
     zone: &Z,
+    claim: &mut Claim<'_>,
+    multicasted: &mut Multicasted,
+    suppression: &mut SlabSuppression<Instant>,
     log_empty_responses: bool,
-  ) {
+    max_payload_size: usize,
+  ) -> Option<ProbeEvent> {
     let ch = match endpoint.accept() {
       Err(e) => {
         tracing::error!(from=%addr, err=%e, "mdns server: fail to accept connection");
-        return;
+        return None;
       }
       Ok(ch) => ch,
     };
@@ -236,38 +565,65 @@ where
               if let Err(e) = endpoint.drain_connection(ch) {
                 tracing::error!(from=%addr, err=%e, "mdns server: fail to drain connection");
               }
-              return;
+              return None;
             }
           },
         }
       }
     };
 
-    let q = match endpoint.recv(ch, req) {
+    let event = claim.recv(data, &req);
+
+    // the questions and answers of the other hosts suppress ours, see RFC 6762, sections
+    // 7.3 and 7.4, a query from another port than 5353 is answered by unicast only
+    if addr.port() == MDNS_PORT {
+      if let Err(e) = suppression.observe(data, &req, Instant::now()) {
+        tracing::error!(from=%addr, err=%e, "mdns server: fail to observe message");
+      }
+    }
+
+    // Responses are only used to detect conflicts, and no query is answered before
+    // the unique records are claimed, see RFC 6762, section 8.1.
+    if req.flags().qr() == MessageType::Reply || !claim.is_claimed() {
+      if let Err(e) = endpoint.drain_connection(ch) {
+        tracing::error!(from=%addr, err=%e, "mdns server: fail to drain connection");
+      }
+      return event;
+    }
+
+    let q = match endpoint.recv(ch, data, req) {
       Err(e) => {
         tracing::error!(from=%addr, err=%e, "mdns server: fail to handle event");
         if let Err(e) = endpoint.drain_connection(ch) {
           tracing::error!(from=%addr, err=%e, "mdns server: fail to drain connection");
         }
-        return;
+        return event;
       }
       Ok(q) => q,
     };
 
+    let mut response = Response::default();
     for question in q.questions() {
-      match endpoint.response(q.query_handle(), *question) {
+      // RFC 6762, section 6.7: a query from a source port other than 5353 is sent by a
+      // simple resolver, which expects a conventional unicast response
+      let outgoing = if addr.port() == MDNS_PORT {
+        endpoint.response(q.query_handle(), *question)
+      } else {
+        endpoint.legacy_response(q.query_handle(), *question)
+      };
+      match outgoing {
         Err(e) => {
           tracing::error!(from=%addr, err=%e, "mdns server: fail to handle question");
         }
         Ok(outgoing) => {
-          let mut answers = match zone.answers(question.name(), question.ty()) {
+          let answers = match zone.answers(question.name(), question.ty()) {
             Err(e) => {
               tracing::error!(from=%addr, err=%e, "mdns server: fail to get answers from zone");
               continue;
             }
             Ok(records) => records.collect::<SmallVec<_>>(),
           };
-          let mut additionals = match zone.additionals(question.name(), question.ty()) {
+          let additionals = match zone.additionals(question.name(), question.ty()) {
             Err(e) => {
               tracing::error!(from=%addr, err=%e, "mdns server: fail to get additionals from zone");
               continue;
@@ -275,39 +631,58 @@ where
             Ok(records) => records.collect::<SmallVec<_>>(),
           };
 
-          if log_empty_responses && (answers.is_empty() && additionals.is_empty()) {
+          if answers.is_empty() && additionals.is_empty() && log_empty_responses {
             tracing::info!(
               class=%question.class(),
               ty=?question.ty(),
               name=%question.name(),
               "mdns server: no responses for question",
             );
-            continue;
-          }
-
-          let msg = Message::new(
-            outgoing.id(),
-            outgoing.flags(),
-            &mut [],
-            &mut answers,
-            &mut [],
-            &mut additionals,
-          );
-          let encoded_len = msg.space_needed();
-
-          let mut buf = Buffer::zerod(encoded_len);
-
-          if let Err(e) = msg.write(&mut buf) {
-            tracing::error!(from=%addr, err=%e, "mdns server: fail to serialize response message");
-            continue;
           }
+          response.push(&outgoing, *question, answers, additionals);
+        }
+      };
+    }
 
-          if let Err(e) = conn.send_to(&buf[..encoded_len], addr) {
-            tracing::error!(from=%addr, err=%e, "mdns server: fail to send response message");
-            continue;
+    // Known-answer suppression, see RFC 6762, section 7.1
+    let empty = response.is_empty();
+    response.retain(|record| !q.is_known_answer(record));
+    if response.is_empty() {
+      if !empty {
+        tracing::debug!(
+          from=%addr,
+          "mdns server: all responses are already known by the querier",
+        );
+      }
+    } else {
+      let now = Instant::now();
+      response.route(multicasted, now);
+      // probes are answered immediately, see RFC 6762, section 6
+      if q.authorities().is_empty() {
+        response.suppress(|record| suppression.is_answer_suppressed(record, now));
+      }
+      let group = conn.local_addr().map(|local| multicast_addr(&local));
+      match response.encode(max_payload_size) {
+        Err(e) => {
+          tracing::error!(from=%addr, err=%e, "mdns server: fail to serialize response message");
+        }
+        Ok(packets) => {
+          for (unicast, packet) in packets {
+            let to = match (unicast, &group) {
+              (true, _) => addr,
+              (false, Ok(group)) => *group,
+              (false, Err(e)) => {
+                tracing::error!(err=%e, "mdns server: fail to get local address");
+                continue;
+              }
+            };
+            tracing::trace!(from=%addr, to=%to, data=?packet, "mdns server: sending response message");
+            if let Err(e) = conn.send_to(&packet, to) {
+              tracing::error!(to=%to, err=%e, "mdns server: fail to send response message");
+            }
           }
         }
-      };
+      }
     }
 
     if let Err(e) = endpoint.drain_query(q.query_handle()) {
@@ -317,5 +692,7 @@ where
     if let Err(e) = endpoint.drain_connection(ch) {
       tracing::error!(from=%addr, err=%e, "mdns server: fail to drain connection");
     }
+
+    event
   }
 }
@@ -0,0 +1,35 @@
+use smol_str::format_smolstr;
+
+use crate::{
+  ServerEvent, ServerOptions,
+  tests::{initialize_tests_tracing, make_conflicting_service, make_service_with_service_name},
+};
+
+use super::Server;
+
+#[test]
+fn server_name_conflict() {
+  initialize_tests_tracing();
+
+  // a service name of its own, so the other tests are not disturbed
+  let name = format_smolstr!("_conflict{}._tcp", fastrand::u16(..));
+  let s = make_service_with_service_name(&name);
+  let (serv, serv_closer) = Server::new(s, ServerOptions::default()).unwrap();
+  let handle = std::thread::spawn(move || serv.run());
+
+  let (renamed, closer) =
+    Server::new(make_conflicting_service(&name), ServerOptions::default()).unwrap();
+  assert_eq!(renamed.zone().instance().as_str(), "hostname (2)");
+  assert_eq!(renamed.zone().port(), 8080);
+  assert_eq!(
+    renamed.events().try_recv().unwrap(),
+    ServerEvent::Renamed {
+      from: format_smolstr!("hostname.{name}.local"),
+      to: format_smolstr!("hostname (2).{name}.local"),
+    }
+  );
+
+  closer.close();
+  serv_closer.close();
+  handle.join().unwrap();
+}
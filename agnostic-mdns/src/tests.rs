@@ -1,5 +1,6 @@
 use super::service::{Service, ServiceBuilder};
 
+#[cfg(feature = "worksteal")]
 pub(crate) fn make_service() -> Service {
   make_service_with_service_name("_http._tcp")
 }
@@ -11,7 +12,21 @@ pub(crate) fn make_service_with_service_name(name: &str) -> Service {
     .with_port(80)
     .with_ip("192.168.0.42".parse().unwrap())
     .with_ip("2620:0:1000:1900:b0c2:d0b2:c411:18bc".parse().unwrap())
-    .with_txt_record("Local web server".into())
+    .with_txt_record("Local web server")
+    .finalize()
+    .unwrap()
+}
+
+/// Returns a service named `hostname` for `service`, on another port than
+/// [`make_service_with_service_name`].
+pub(crate) fn make_conflicting_service(service: &str) -> Service {
+  ServiceBuilder::new("hostname".into(), service.into())
+    .with_domain("local.".into())
+    .with_hostname("testhost.".into())
+    .with_port(8080)
+    .with_ip("192.168.0.42".parse().unwrap())
+    .with_ip("2620:0:1000:1900:b0c2:d0b2:c411:18bc".parse().unwrap())
+    .with_txt_record("Local web server")
     .finalize()
     .unwrap()
 }
@@ -0,0 +1,287 @@
+use smol_str::SmolStr;
+use triomphe::Arc;
+
+/// The maximum length of a character-string of a TXT record, see RFC 1035, section 3.3.
+const MAX_STRING_LEN: usize = 255;
+
+/// The error returned when an attribute cannot be added to a [`TxtRecord`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum TxtError {
+  /// The key is empty.
+  #[error("TXT key is empty")]
+  EmptyKey,
+  /// The key contains a character other than printable US-ASCII, or `=`.
+  #[error("TXT key {0:?} must be printable US-ASCII, excluding '='")]
+  InvalidKey(SmolStr),
+  /// The `key=value` string is longer than 255 bytes.
+  #[error("TXT attribute {0:?} is longer than 255 bytes")]
+  TooLong(SmolStr),
+}
+
+/// The `key=value` attributes of a DNS-SD TXT record, see RFC 6763, section 6.
+///
+/// - Keys are compared case-insensitively, the case of a key is kept as it was added.
+/// - A key without `=` is a boolean attribute, present with no value, which is distinct
+///   from a key with an empty value, `key=`.
+/// - Values are opaque bytes, they are not required to be UTF-8.
+/// - When a record is decoded, only the first occurrence of a key is kept, and the
+///   strings without a key are ignored.
+///
+/// ## Example
+///
+/// ```rust
+/// use agnostic_mdns::TxtRecord;
+///
+/// let txt = TxtRecord::new()
+///   .with("txtvers", "1")
+///   .unwrap()
+///   .with_flag("paper")
+///   .unwrap();
+///
+/// assert_eq!(txt.get_str("TXTVERS"), Some("1"));
+/// assert_eq!(txt.get("paper"), Some(None));
+/// assert_eq!(txt.get("color"), None);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct TxtRecord {
+  attrs: Vec<(SmolStr, Option<Arc<[u8]>>)>,
+}
+
+impl TxtRecord {
+  /// The size of the encoded TXT record above which a warning is logged.
+  ///
+  /// A TXT record should be kept under 400 bytes to fit in a single 512-byte DNS
+  /// message, TXT records larger than 1300 bytes are not recommended, as they do not fit
+  /// in a single Ethernet packet, see RFC 6763, section 6.2.
+  pub const SIZE_WARNING_THRESHOLD: usize = 1300;
+
+  /// Creates an empty TXT record.
+  #[inline]
+  pub const fn new() -> Self {
+    Self { attrs: Vec::new() }
+  }
+
+  /// Decodes the character-strings of a TXT record.
+  ///
+  /// Only the first occurrence of a key is kept, and the strings with an empty or
+  /// invalid key are ignored, see RFC 6763, section 6.4.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::TxtRecord;
+  ///
+  /// let txt = TxtRecord::from_strings(["path=/", "PATH=/other", "=ignored", "debug"]);
+  ///
+  /// assert_eq!(txt.len(), 2);
+  /// assert_eq!(txt.get_str("path"), Some("/"));
+  /// assert_eq!(txt.get("debug"), Some(None));
+  /// ```
+  pub fn from_strings<I, S>(strings: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<[u8]>,
+  {
+    let mut txt = Self::new();
+    for s in strings {
+      let s = s.as_ref();
+      let (key, value) = match s.iter().position(|b| *b == b'=') {
+        Some(idx) => (&s[..idx], Some(&s[idx + 1..])),
+        None => (s, None),
+      };
+
+      let Ok(key) = core::str::from_utf8(key) else {
+        continue;
+      };
+      if validate_key(key).is_err() || txt.contains_key(key) {
+        continue;
+      }
+      txt.attrs.push((SmolStr::new(key), value.map(Arc::from)));
+    }
+    txt
+  }
+
+  /// Returns the number of attributes.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.attrs.len()
+  }
+
+  /// Returns `true` if there is no attribute.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.attrs.is_empty()
+  }
+
+  /// Returns `true` if the attribute `key` is present, with or without a value.
+  #[inline]
+  pub fn contains_key(&self, key: &str) -> bool {
+    self.position(key).is_some()
+  }
+
+  /// Returns the value of the attribute `key`.
+  ///
+  /// Returns `None` if the attribute is absent, and `Some(None)` if it is a boolean
+  /// attribute, present with no value.
+  #[inline]
+  pub fn get(&self, key: &str) -> Option<Option<&[u8]>> {
+    self.position(key).map(|idx| self.attrs[idx].1.as_deref())
+  }
+
+  /// Returns the value of the attribute `key`, if it has a UTF-8 value.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::TxtRecord;
+  ///
+  /// let txt = TxtRecord::new()
+  ///   .with("name", "printer")
+  ///   .unwrap()
+  ///   .with("id", [0xff, 0x00])
+  ///   .unwrap();
+  ///
+  /// assert_eq!(txt.get_str("name"), Some("printer"));
+  /// assert_eq!(txt.get_str("id"), None);
+  /// assert_eq!(txt.get("id"), Some(Some(&[0xff, 0x00][..])));
+  /// ```
+  #[inline]
+  pub fn get_str(&self, key: &str) -> Option<&str> {
+    self
+      .get(key)
+      .flatten()
+      .and_then(|value| core::str::from_utf8(value).ok())
+  }
+
+  /// Returns an iterator over the keys and values of the attributes, in the order they
+  /// were added.
+  #[inline]
+  pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&[u8]>)> + '_ {
+    self
+      .attrs
+      .iter()
+      .map(|(key, value)| (key.as_str(), value.as_deref()))
+  }
+
+  /// Sets the attribute `key` to `value`, replacing the previous value of the key.
+  ///
+  /// Returns an error if the key is not printable US-ASCII, contains `=`, or if
+  /// `key=value` is longer than 255 bytes.
+  #[inline]
+  pub fn insert(&mut self, key: &str, value: impl AsRef<[u8]>) -> Result<(), TxtError> {
+    self.insert_in(key, Some(value.as_ref()))
+  }
+
+  /// Sets the boolean attribute `key`, present with no value, replacing the previous
+  /// value of the key.
+  #[inline]
+  pub fn insert_flag(&mut self, key: &str) -> Result<(), TxtError> {
+    self.insert_in(key, None)
+  }
+
+  /// Sets the attribute `key` to `value`, see [`TxtRecord::insert`].
+  #[inline]
+  pub fn with(mut self, key: &str, value: impl AsRef<[u8]>) -> Result<Self, TxtError> {
+    self.insert(key, value).map(|_| self)
+  }
+
+  /// Sets the boolean attribute `key`, see [`TxtRecord::insert_flag`].
+  #[inline]
+  pub fn with_flag(mut self, key: &str) -> Result<Self, TxtError> {
+    self.insert_flag(key).map(|_| self)
+  }
+
+  /// Removes the attribute `key`.
+  ///
+  /// Returns `true` if the attribute was present.
+  #[inline]
+  pub fn remove(&mut self, key: &str) -> bool {
+    match self.position(key) {
+      Some(idx) => {
+        self.attrs.remove(idx);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Returns the size of the encoded TXT record, in bytes.
+  ///
+  /// ## Example
+  ///
+  /// ```rust
+  /// use agnostic_mdns::TxtRecord;
+  ///
+  /// // an empty TXT record is a single empty string
+  /// assert_eq!(TxtRecord::new().encoded_len(), 1);
+  ///
+  /// let txt = TxtRecord::new().with("a", "b").unwrap().with_flag("c").unwrap();
+  /// assert_eq!(txt.encoded_len(), 4 + 2);
+  /// ```
+  pub fn encoded_len(&self) -> usize {
+    self
+      .attrs
+      .iter()
+      .map(|(key, value)| 1 + key.len() + value.as_ref().map_or(0, |value| 1 + value.len()))
+      .sum::<usize>()
+      .max(1)
+  }
+
+  /// Returns the character-strings of the TXT record, `key=value` or `key`, see
+  /// [`ServiceBuilder::with_txt_records`](crate::ServiceBuilder::with_txt_records).
+  pub fn strings(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+    self.attrs.iter().map(|(key, value)| {
+      let mut s = Vec::with_capacity(key.len() + value.as_ref().map_or(0, |value| 1 + value.len()));
+      s.extend_from_slice(key.as_bytes());
+      if let Some(value) = value {
+        s.push(b'=');
+        s.extend_from_slice(value);
+      }
+      s
+    })
+  }
+
+  fn position(&self, key: &str) -> Option<usize> {
+    self
+      .attrs
+      .iter()
+      .position(|(k, _)| k.eq_ignore_ascii_case(key))
+  }
+
+  fn insert_in(&mut self, key: &str, value: Option<&[u8]>) -> Result<(), TxtError> {
+    validate_key(key)?;
+    if key.len() + value.map_or(0, |value| 1 + value.len()) > MAX_STRING_LEN {
+      return Err(TxtError::TooLong(SmolStr::new(key)));
+    }
+
+    let value = value.map(Arc::from);
+    match self.position(key) {
+      Some(idx) => self.attrs[idx] = (SmolStr::new(key), value),
+      None => self.attrs.push((SmolStr::new(key), value)),
+    }
+
+    let size = self.encoded_len();
+    if size > Self::SIZE_WARNING_THRESHOLD {
+      tracing::warn!(
+        size = size,
+        threshold = Self::SIZE_WARNING_THRESHOLD,
+        "mdns: the TXT record does not fit in a single Ethernet packet, see RFC 6763, section 6.2"
+      );
+    }
+    Ok(())
+  }
+}
+
+/// Returns `Ok(())` if `key` is a valid key, see RFC 6763, section 6.4.
+fn validate_key(key: &str) -> Result<(), TxtError> {
+  if key.is_empty() {
+    return Err(TxtError::EmptyKey);
+  }
+
+  if !key.bytes().all(|b| (b' '..=b'~').contains(&b) && b != b'=') {
+    return Err(TxtError::InvalidKey(SmolStr::new(key)));
+  }
+
+  Ok(())
+}
@@ -1,9 +1,1132 @@
 use std::{
+  collections::HashMap,
   io,
   net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket as StdUdpSocket},
+  time::{Duration, Instant},
 };
 
-use crate::{IPV4_MDNS, IPV6_MDNS};
+#[cfg(feature = "worksteal")]
+use mdns_proto::server::{SHARED_RESPONSE_DELAY_MAX, SHARED_RESPONSE_DELAY_MIN};
+use mdns_proto::{
+  announcer::{Announcer, cache_flush, goodbye},
+  client::{Endpoint, Response as Answer},
+  error::{BufferType, ProtoError},
+  prober::{self, ProbeEvent, Prober},
+  proto::{
+    Cursor, Deserialize, Flags, Label, LabelSegment, Message, MessageType, Question, Rdata,
+    ResourceRecord, ResourceType,
+  },
+  server::{
+    Outgoing, TRUNCATED_QUERY_DELAY_MAX, TRUNCATED_QUERY_DELAY_MIN, clear_truncated, is_truncated,
+    legacy_unicast,
+  },
+  utils::record_eq,
+};
+use smallvec_wrapper::SmallVec;
+use smol_str::{SmolStr, format_smolstr};
+
+use crate::{HostAddr, IPV4_MDNS, IPV6_MDNS, MDNS_PORT};
+
+/// Returns the mDNS multicast group address of the same family as `local`.
+pub(crate) fn multicast_addr(local: &SocketAddr) -> SocketAddr {
+  match local {
+    SocketAddr::V4(_) => (IPV4_MDNS, MDNS_PORT).into(),
+    SocketAddr::V6(_) => (IPV6_MDNS, MDNS_PORT).into(),
+  }
+}
+
+/// The unique records of a zone, and the prober claiming them on the network.
+pub(crate) struct Claim<'a> {
+  prober: Prober<Instant>,
+  records: SmallVec<ResourceRecord<'a>>,
+}
+
+impl<'a> Claim<'a> {
+  /// Starts probing for the given unique records.
+  pub(crate) fn new(records: impl Iterator<Item = ResourceRecord<'a>>) -> Self {
+    Self {
+      prober: Prober::new(Instant::now() + probe_delay()),
+      records: records.collect(),
+    }
+  }
+
+  /// Starts claiming the given unique records, without probing if all their names are
+  /// already `owned`, see [`Claim::owned_names`].
+  ///
+  /// RFC 6762, section 8.4: when only the data of the records changed, the records are
+  /// announced again without probing.
+  pub(crate) fn with_owned(
+    records: impl Iterator<Item = ResourceRecord<'a>>,
+    owned: Option<SmallVec<SmolStr>>,
+  ) -> Self {
+    let records = records.collect::<SmallVec<_>>();
+    let prober = match owned {
+      Some(owned) if names(&records).all(|name| owned.contains(&name)) => Prober::claimed(),
+      _ => Prober::new(Instant::now() + probe_delay()),
+    };
+    Self { prober, records }
+  }
+
+  /// Returns the names of the unique records, if they are claimed.
+  pub(crate) fn owned_names(&self) -> Option<SmallVec<SmolStr>> {
+    self.is_claimed().then(|| names(&self.records).collect())
+  }
+
+  /// Continues claiming the given unique records with an existing prober.
+  pub(crate) fn with_prober(
+    prober: Prober<Instant>,
+    records: impl Iterator<Item = ResourceRecord<'a>>,
+  ) -> Self {
+    Self {
+      prober,
+      records: records.collect(),
+    }
+  }
+
+  /// Returns the prober, releasing the records.
+  pub(crate) fn into_prober(self) -> Prober<Instant> {
+    self.prober
+  }
+
+  /// Returns the name of the record at `index`.
+  pub(crate) fn name(&self, index: usize) -> Label<'a> {
+    self.records[index].name()
+  }
+
+  /// Returns `true` if the records are owned by us, and queries can be answered.
+  pub(crate) fn is_claimed(&self) -> bool {
+    self.records.is_empty() || self.prober.state().is_won()
+  }
+
+  /// Returns the instant at which [`Claim::handle_timeout`] should be called next.
+  pub(crate) fn poll_timeout(&self) -> Option<Instant> {
+    if self.records.is_empty() {
+      return None;
+    }
+    self.prober.poll_timeout()
+  }
+
+  pub(crate) fn handle_timeout(&mut self, now: Instant) -> Option<ProbeEvent> {
+    if self.records.is_empty() {
+      return None;
+    }
+    self.prober.handle_timeout(now)
+  }
+
+  /// Handles an incoming message, read from the raw message `packet`.
+  pub(crate) fn recv(&mut self, packet: &[u8], msg: &Message<'_, '_>) -> Option<ProbeEvent> {
+    if self.records.is_empty() {
+      return None;
+    }
+    self.prober.recv(Instant::now(), packet, msg, &self.records)
+  }
+
+  /// Starts probing again, e.g. after a conflict.
+  pub(crate) fn restart(&mut self) {
+    self.prober.restart(Instant::now() + probe_delay());
+  }
+
+  /// Encodes a probe query, with the records in the Authority section.
+  pub(crate) fn encode_probe(&self, unicast_response: bool) -> io::Result<Vec<u8>> {
+    let mut questions = prober::questions(&self.records, unicast_response).collect::<SmallVec<_>>();
+    let mut authorities = self.records.clone();
+    let msg = Message::new(
+      0,
+      Flags::new(),
+      &mut questions,
+      &mut [],
+      &mut authorities,
+      &mut [],
+    );
+
+    let mut buf = vec![0; msg.space_needed()];
+    let len = msg
+      .write(&mut buf)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    buf.truncate(len);
+    Ok(buf)
+  }
+}
+
+/// The records of a zone, and the announcer announcing them on the network.
+pub(crate) struct Announce<'a> {
+  announcer: Announcer<Instant>,
+  records: SmallVec<ResourceRecord<'a>>,
+}
+
+impl<'a> Announce<'a> {
+  /// Starts announcing the given shared and unique records, the cache-flush bit is set
+  /// on the unique records.
+  pub(crate) fn new(
+    shared: impl Iterator<Item = ResourceRecord<'a>>,
+    unique: impl Iterator<Item = ResourceRecord<'a>>,
+  ) -> Self {
+    Self {
+      announcer: Announcer::new(Instant::now()),
+      records: shared.chain(unique.map(cache_flush)).collect(),
+    }
+  }
+
+  /// Returns the instant at which [`Announce::handle_timeout`] should be called next.
+  pub(crate) fn poll_timeout(&self) -> Option<Instant> {
+    if self.records.is_empty() {
+      return None;
+    }
+    self.announcer.poll_timeout()
+  }
+
+  /// Returns the encoded announcement, if one should be sent now, the announced records are
+  /// marked as multicast.
+  pub(crate) fn handle_timeout(
+    &mut self,
+    now: Instant,
+    multicasted: &mut Multicasted,
+  ) -> Option<io::Result<Vec<u8>>> {
+    if self.records.is_empty() || !self.announcer.handle_timeout(now) {
+      return None;
+    }
+    for record in self.records.iter() {
+      multicasted.mark(record, now);
+    }
+    Some(self.encode())
+  }
+
+  /// Encodes a goodbye response for the announced records which are not `kept`, e.g.
+  /// all of them on shutdown, or the records removed from the zone.
+  ///
+  /// Returns `None` if there is no record to say goodbye to.
+  pub(crate) fn encode_goodbye(&self, kept: &[ResourceRecord<'_>]) -> Option<io::Result<Vec<u8>>> {
+    let records = self
+      .records
+      .iter()
+      .filter(|record| !kept.iter().any(|k| record_eq(&[], record, k)))
+      .map(|record| goodbye(*record))
+      .collect::<SmallVec<_>>();
+
+    if records.is_empty() {
+      return None;
+    }
+    Some(encode_response(records))
+  }
+
+  /// Encodes an unsolicited response, with the records in the Answer section.
+  fn encode(&self) -> io::Result<Vec<u8>> {
+    encode_response(self.records.clone())
+  }
+}
+
+/// The truncated queries held until the continuation packets carrying the rest of their
+/// known answers are received, see RFC 6762, section 7.2.
+#[derive(Default)]
+pub(crate) struct Truncated {
+  queries: HashMap<SocketAddr, HeldQuery>,
+}
+
+struct HeldQuery {
+  packets: SmallVec<Vec<u8>>,
+  deadline: Instant,
+}
+
+impl Truncated {
+  /// Holds `packet` if it is a truncated query, or a continuation of a query held for `addr`.
+  ///
+  /// Returns `true` if the packet is held, and must not be handled now.
+  pub(crate) fn hold(&mut self, addr: SocketAddr, packet: &[u8]) -> bool {
+    // only the queries are held, see RFC 6762, section 18.5
+    if packet.len() < 12 || packet[2] & 0x80 != 0 {
+      return false;
+    }
+
+    // the continuation packets carry known answers only, without questions
+    if let Some(held) = self.queries.get_mut(&addr) {
+      if u16::from_be_bytes([packet[4], packet[5]]) == 0 {
+        tracing::trace!(from=%addr, "mdns server: received a known-answer continuation packet");
+        held.packets.push(Self::copy(packet));
+        return true;
+      }
+    }
+
+    if !is_truncated(packet) {
+      return false;
+    }
+
+    let delay = fastrand::u64(
+      TRUNCATED_QUERY_DELAY_MIN.as_millis() as u64..=TRUNCATED_QUERY_DELAY_MAX.as_millis() as u64,
+    );
+    tracing::trace!(from=%addr, delay_ms=%delay, "mdns server: holding a truncated query");
+    let mut packets = SmallVec::new();
+    packets.push(Self::copy(packet));
+    // a new truncated query from the same host replaces the pending one
+    self.queries.insert(
+      addr,
+      HeldQuery {
+        packets,
+        deadline: Instant::now() + Duration::from_millis(delay),
+      },
+    );
+    true
+  }
+
+  /// Copies a held packet, with its TC bit cleared, as the merged query is complete.
+  fn copy(packet: &[u8]) -> Vec<u8> {
+    let mut packet = packet.to_vec();
+    clear_truncated(&mut packet);
+    packet
+  }
+
+  /// Returns the instant at which [`Truncated::handle_timeout`] should be called next.
+  pub(crate) fn poll_timeout(&self) -> Option<Instant> {
+    self.queries.values().map(|held| held.deadline).min()
+  }
+
+  /// Releases the queries held long enough, with the known answers of their continuation
+  /// packets merged in the Answer section of a single query.
+  pub(crate) fn handle_timeout(
+    &mut self,
+    now: Instant,
+  ) -> SmallVec<(SocketAddr, io::Result<Vec<u8>>)> {
+    let expired = self
+      .queries
+      .iter()
+      .filter(|(_, held)| held.deadline <= now)
+      .map(|(addr, _)| *addr)
+      .collect::<SmallVec<_>>();
+
+    expired
+      .into_iter()
+      .filter_map(|addr| {
+        let held = self.queries.remove(&addr)?;
+        Some((addr, held.merge()))
+      })
+      .collect()
+  }
+}
+
+impl HeldQuery {
+  /// Encodes the query with the questions of the truncated packet, and the records of all
+  /// the packets.
+  ///
+  /// The names of the questions and the records, and the names inside the data of the
+  /// records, are uncompressed, as the pointers only make sense in the packet they were
+  /// read from.
+  fn merge(&self) -> io::Result<Vec<u8>> {
+    let mut questions = SmallVec::<(Vec<u8>, ResourceType, u16)>::new();
+    let mut records = SmallVec::<HeldRecord>::new();
+    let mut header = None;
+
+    for packet in self.packets.iter() {
+      let mut q = SmallVec::new();
+      let mut an = SmallVec::from([ResourceRecord::default(); 4]);
+      let mut ns = SmallVec::new();
+      let mut ar = SmallVec::from([ResourceRecord::default(); 4]);
+
+      let msg = loop {
+        match Message::read(packet, &mut q, &mut an, &mut ns, &mut ar) {
+          Ok(msg) => break msg,
+          Err(ProtoError::NotEnoughWriteSpace {
+            tried_to_write,
+            buffer_type,
+            ..
+          }) => match buffer_type {
+            BufferType::Question => q.resize(tried_to_write.into(), Question::default()),
+            BufferType::Answer => an.resize(tried_to_write.into(), ResourceRecord::default()),
+            BufferType::Authority => ns.resize(tried_to_write.into(), ResourceRecord::default()),
+            BufferType::Additional => ar.resize(tried_to_write.into(), ResourceRecord::default()),
+          },
+          Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+      };
+
+      header.get_or_insert((msg.id(), msg.flags()));
+      questions.extend(
+        msg
+          .questions()
+          .iter()
+          .map(|q| (uncompressed(packet, &q.name()), q.ty(), q.class())),
+      );
+      let sections = [msg.answers(), msg.authorities(), msg.additional()];
+      for (section, section_records) in sections.into_iter().enumerate() {
+        records.extend(
+          section_records
+            .iter()
+            .map(|record| HeldRecord::new(packet, section, record)),
+        );
+      }
+    }
+
+    let mut questions = questions
+      .iter()
+      .map(|(name, ty, class)| Question::new(label(name), *ty, *class))
+      .collect::<SmallVec<_>>();
+    let section_of = |section| {
+      records
+        .iter()
+        .filter(|record| record.section == section)
+        .map(HeldRecord::as_record)
+        .collect::<SmallVec<_>>()
+    };
+    let mut answers = section_of(0);
+    let mut authorities = section_of(1);
+    let mut additionals = section_of(2);
+
+    let (id, flags) = header.unwrap_or_default();
+    let msg = Message::new(
+      id,
+      flags,
+      &mut questions,
+      &mut answers,
+      &mut authorities,
+      &mut additionals,
+    );
+    let mut buf = vec![0; msg.space_needed()];
+    let len = msg
+      .write(&mut buf)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    buf.truncate(len);
+    Ok(buf)
+  }
+}
+
+/// A record of a held packet, with its name and the name inside its data uncompressed,
+/// see [`HeldQuery::merge`].
+struct HeldRecord {
+  /// The Answer, Authority or Additional section, in this order.
+  section: usize,
+  name: Vec<u8>,
+  ty: ResourceType,
+  class: u16,
+  ttl: u32,
+  data: Vec<u8>,
+}
+
+impl HeldRecord {
+  fn new(packet: &[u8], section: usize, record: &ResourceRecord<'_>) -> Self {
+    Self {
+      section,
+      name: uncompressed(packet, &record.name()),
+      ty: record.ty(),
+      class: record.class(),
+      ttl: record.ttl(),
+      data: Rdata::new(packet, record).to_vec(),
+    }
+  }
+
+  fn as_record(&self) -> ResourceRecord<'_> {
+    ResourceRecord::new(label(&self.name), self.ty, self.class, self.ttl, &self.data)
+  }
+}
+
+/// Returns `name`, read from the raw message `packet`, in wire format, with the
+/// compression pointers followed.
+///
+/// A pointer must refer to a prior name, see RFC 1035, section 4.1.4, so a malformed
+/// message cannot make the name loop forever, it ends where it stops making sense.
+fn uncompressed(packet: &[u8], name: &Label<'_>) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  let mut name = *name;
+  let mut limit = packet.len();
+  'name: loop {
+    for segment in name.segments() {
+      match segment {
+        LabelSegment::String(label) => {
+          bytes.push(label.len() as u8);
+          bytes.extend_from_slice(label.as_bytes());
+        }
+        LabelSegment::Pointer(at) if (at as usize) < limit => {
+          limit = at as usize;
+          name = Label::default();
+          if name.deserialize(Cursor::new(packet).at(limit)).is_err() {
+            break 'name;
+          }
+          continue 'name;
+        }
+        _ => break 'name,
+      }
+    }
+    break;
+  }
+  bytes.push(0);
+  bytes
+}
+
+/// Returns the name written by [`uncompressed`].
+fn label(bytes: &[u8]) -> Label<'_> {
+  let mut label = Label::default();
+  // a name written by `uncompressed` always ends
+  let _ = label.deserialize(Cursor::new(bytes));
+  label
+}
+
+/// The response to all the questions of a query.
+///
+/// The records answering the different questions are merged in a single response, without
+/// duplicates, see RFC 6762, section 6. The records answering the questions which ask for a
+/// unicast response are kept apart, to be sent to the querier only.
+///
+/// A legacy unicast response repeats the questions, and is sent to the querier only, see
+/// RFC 6762, section 6.7.
+#[derive(Default)]
+pub(crate) struct Response<'a> {
+  id: u16,
+  flags: Flags,
+  legacy: bool,
+  questions: SmallVec<Question<'a>>,
+  multicast: Records<'a>,
+  unicast: Records<'a>,
+}
+
+impl<'a> Response<'a> {
+  /// Adds the records answering `question`, `outgoing` is the response to the question.
+  pub(crate) fn push(
+    &mut self,
+    outgoing: &Outgoing,
+    question: Question<'a>,
+    answers: impl IntoIterator<Item = ResourceRecord<'a>>,
+    additionals: impl IntoIterator<Item = ResourceRecord<'a>>,
+  ) {
+    self.flags = outgoing.flags();
+    if outgoing.is_legacy() {
+      self.id = outgoing.id();
+      self.legacy = true;
+      self.questions.push(question);
+      answers
+        .into_iter()
+        .for_each(|record| self.unicast.push_answer(record));
+      additionals
+        .into_iter()
+        .for_each(|record| self.unicast.push_additional(record));
+    } else if outgoing.is_unicast() {
+      self.id = outgoing.id();
+      // the multicast response reaches the querier too
+      for record in answers {
+        if !self.multicast.contains(&record) {
+          self.unicast.push_answer(record);
+        }
+      }
+      for record in additionals {
+        if !self.multicast.contains(&record) {
+          self.unicast.push_additional(record);
+        }
+      }
+    } else {
+      for record in answers {
+        self.unicast.remove(&record);
+        self.multicast.push_answer(record);
+      }
+      for record in additionals {
+        self.unicast.remove(&record);
+        self.multicast.push_additional(record);
+      }
+    }
+  }
+
+  /// Adds the records of `other`, a response to other questions of the same query.
+  #[cfg(feature = "worksteal")]
+  pub(crate) fn extend(&mut self, other: Response<'a>) {
+    if other.is_empty() {
+      return;
+    }
+
+    self.flags = other.flags;
+    for record in other.multicast.answers {
+      self.unicast.remove(&record);
+      self.multicast.push_answer(record);
+    }
+    for record in other.multicast.additionals {
+      self.unicast.remove(&record);
+      self.multicast.push_additional(record);
+    }
+
+    if !other.unicast.is_empty() {
+      self.id = other.id;
+    }
+    for record in other.unicast.answers {
+      if !self.multicast.contains(&record) {
+        self.unicast.push_answer(record);
+      }
+    }
+    for record in other.unicast.additionals {
+      if !self.multicast.contains(&record) {
+        self.unicast.push_additional(record);
+      }
+    }
+  }
+
+  /// Returns `true` if the response has no records.
+  pub(crate) fn is_empty(&self) -> bool {
+    self.multicast.is_empty() && self.unicast.is_empty()
+  }
+
+  /// Retains only the records for which `f` returns `true`.
+  pub(crate) fn retain(&mut self, mut f: impl FnMut(&ResourceRecord<'a>) -> bool) {
+    self.multicast.retain(&mut f);
+    self.unicast.retain(&mut f);
+  }
+
+  /// Moves the records which have not been multicast recently to the multicast response,
+  /// and marks the records of the multicast response as multicast at `now`.
+  ///
+  /// RFC 6762, section 5.4: if the responder has not multicast a record recently (within one
+  /// quarter of its TTL), then the responder SHOULD instead multicast the response so as to
+  /// keep all the peer caches up to date.
+  ///
+  /// A legacy unicast response is never multicast.
+  pub(crate) fn route(&mut self, multicasted: &mut Multicasted, now: Instant) {
+    if self.legacy {
+      return;
+    }
+
+    let unicast = core::mem::take(&mut self.unicast);
+    for record in unicast.answers {
+      if multicasted.recently(&record, now) {
+        self.unicast.push_answer(record);
+      } else {
+        self.multicast.push_answer(record);
+      }
+    }
+    for record in unicast.additionals {
+      if multicasted.recently(&record, now) {
+        self.unicast.push_additional(record);
+      } else {
+        self.multicast.push_additional(record);
+      }
+    }
+
+    multicasted.forget_expired(now);
+    for record in self.multicast.iter() {
+      multicasted.mark(record, now);
+    }
+  }
+
+  /// Drops the records of the multicast response for which `f` returns `true`, as another
+  /// host has just multicast them, see RFC 6762, section 7.4.
+  pub(crate) fn suppress(&mut self, mut f: impl FnMut(&ResourceRecord<'a>) -> bool) {
+    self.multicast.retain(|record| !f(record));
+  }
+
+  /// Encodes the response, in as few packets as possible.
+  ///
+  /// Returns the packets with `true` if they must be sent to the querier only, or `false`
+  /// if they must be sent to the multicast group.
+  ///
+  /// A response is split into multiple packets only when it does not fit in
+  /// `max_payload_size` bytes, a record larger than `max_payload_size` is sent alone.
+  pub(crate) fn encode(&self, max_payload_size: usize) -> io::Result<SmallVec<(bool, Vec<u8>)>> {
+    let mut packets = SmallVec::new();
+    // RFC 6762, section 18.1: in multicast responses, the Query Identifier MUST be zero
+    for packet in self
+      .multicast
+      .encode(0, self.flags, &[], max_payload_size)?
+    {
+      packets.push((false, packet));
+    }
+
+    if self.legacy {
+      let legacy = Records {
+        answers: self
+          .unicast
+          .answers
+          .iter()
+          .copied()
+          .map(legacy_unicast)
+          .collect(),
+        additionals: self
+          .unicast
+          .additionals
+          .iter()
+          .copied()
+          .map(legacy_unicast)
+          .collect(),
+      };
+      for packet in legacy.encode(self.id, self.flags, &self.questions, max_payload_size)? {
+        packets.push((true, packet));
+      }
+    } else {
+      for packet in self
+        .unicast
+        .encode(self.id, self.flags, &[], max_payload_size)?
+      {
+        packets.push((true, packet));
+      }
+    }
+    Ok(packets)
+  }
+}
+
+/// The records of a response, without duplicates.
+#[derive(Default)]
+struct Records<'a> {
+  answers: SmallVec<ResourceRecord<'a>>,
+  additionals: SmallVec<ResourceRecord<'a>>,
+}
+
+impl<'a> Records<'a> {
+  fn iter(&self) -> impl Iterator<Item = &ResourceRecord<'a>> {
+    self.answers.iter().chain(self.additionals.iter())
+  }
+
+  fn contains(&self, record: &ResourceRecord<'_>) -> bool {
+    self.iter().any(|r| record_eq(&[], r, record))
+  }
+
+  fn push_answer(&mut self, record: ResourceRecord<'a>) {
+    if !self.answers.iter().any(|r| record_eq(&[], r, &record)) {
+      self.additionals.retain(|r| !record_eq(&[], r, &record));
+      self.answers.push(record);
+    }
+  }
+
+  fn push_additional(&mut self, record: ResourceRecord<'a>) {
+    if !self.contains(&record) {
+      self.additionals.push(record);
+    }
+  }
+
+  fn remove(&mut self, record: &ResourceRecord<'_>) {
+    self.retain(|r| !record_eq(&[], r, record));
+  }
+
+  fn is_empty(&self) -> bool {
+    self.answers.is_empty() && self.additionals.is_empty()
+  }
+
+  fn retain(&mut self, mut f: impl FnMut(&ResourceRecord<'a>) -> bool) {
+    self.answers.retain(|record| f(record));
+    self.additionals.retain(|record| f(record));
+  }
+
+  /// Encodes the records in packets of at most `max_payload_size` bytes, each packet
+  /// repeats `questions`.
+  fn encode(
+    &self,
+    id: u16,
+    flags: Flags,
+    questions: &[Question<'a>],
+    max_payload_size: usize,
+  ) -> io::Result<SmallVec<Vec<u8>>> {
+    let mut questions = questions.iter().copied().collect::<SmallVec<_>>();
+    let mut packets = SmallVec::new();
+    let mut answers = SmallVec::<ResourceRecord<'_>>::new();
+    let mut additionals = SmallVec::<ResourceRecord<'_>>::new();
+
+    let records = self
+      .answers
+      .iter()
+      .map(|record| (true, *record))
+      .chain(self.additionals.iter().map(|record| (false, *record)));
+    for (answer, record) in records {
+      if answer {
+        answers.push(record);
+      } else {
+        additionals.push(record);
+      }
+
+      let len = Message::new(
+        id,
+        flags,
+        &mut questions,
+        &mut answers,
+        &mut [],
+        &mut additionals,
+      )
+      .space_needed();
+      if len > max_payload_size && answers.len() + additionals.len() > 1 {
+        // the record does not fit, the packet is sent without it
+        if answer {
+          answers.pop();
+        } else {
+          additionals.pop();
+        }
+        packets.push(encode_message(
+          id,
+          flags,
+          &mut questions,
+          &mut answers,
+          &mut additionals,
+        )?);
+        answers.clear();
+        additionals.clear();
+        if answer {
+          answers.push(record);
+        } else {
+          additionals.push(record);
+        }
+      }
+    }
+
+    if !answers.is_empty() || !additionals.is_empty() {
+      packets.push(encode_message(
+        id,
+        flags,
+        &mut questions,
+        &mut answers,
+        &mut additionals,
+      )?);
+    }
+    Ok(packets)
+  }
+}
+
+/// The responses to the questions answered with shared records, delayed so that the
+/// responses of the other hosts do not collide with ours, see RFC 6762, section 6.
+///
+/// The records answering questions received before a delayed response is sent join it,
+/// so that several answers go out in one packet.
+#[cfg(feature = "worksteal")]
+#[derive(Default)]
+pub(crate) struct Delayed {
+  multicast: Option<Pending>,
+  unicast: HashMap<SocketAddr, Pending>,
+}
+
+/// A delayed response, owning its records.
+#[cfg(feature = "worksteal")]
+pub(crate) struct Pending {
+  addr: SocketAddr,
+  deadline: Instant,
+  id: u16,
+  flags: Flags,
+  unicast: bool,
+  answers: SmallVec<OwnedRecord>,
+  additionals: SmallVec<OwnedRecord>,
+}
+
+#[cfg(feature = "worksteal")]
+struct OwnedRecord {
+  name: SmolStr,
+  ty: ResourceType,
+  class: u16,
+  ttl: u32,
+  data: Vec<u8>,
+}
+
+#[cfg(feature = "worksteal")]
+impl Delayed {
+  /// Delays `response`, to the question of `addr`, by a random amount of time in the range
+  /// [`SHARED_RESPONSE_DELAY_MIN`] to [`SHARED_RESPONSE_DELAY_MAX`], or until the pending
+  /// response it joins is sent.
+  pub(crate) fn schedule(&mut self, addr: SocketAddr, response: &Response<'_>) {
+    if !response.multicast.is_empty() {
+      self
+        .multicast
+        .get_or_insert_with(|| Pending::new(addr, response, false))
+        .extend(&response.multicast);
+    }
+
+    if !response.unicast.is_empty() {
+      self
+        .unicast
+        .entry(addr)
+        .or_insert_with(|| Pending::new(addr, response, true))
+        .extend(&response.unicast);
+    }
+  }
+
+  /// Returns the instant at which [`Delayed::handle_timeout`] should be called next.
+  pub(crate) fn poll_timeout(&self) -> Option<Instant> {
+    self
+      .multicast
+      .iter()
+      .chain(self.unicast.values())
+      .map(|pending| pending.deadline)
+      .min()
+  }
+
+  /// Releases the responses delayed long enough.
+  pub(crate) fn handle_timeout(&mut self, now: Instant) -> SmallVec<Pending> {
+    let mut due = SmallVec::new();
+    if self
+      .multicast
+      .as_ref()
+      .is_some_and(|pending| pending.deadline <= now)
+    {
+      due.extend(self.multicast.take());
+    }
+
+    let expired = self
+      .unicast
+      .iter()
+      .filter(|(_, pending)| pending.deadline <= now)
+      .map(|(addr, _)| *addr)
+      .collect::<SmallVec<_>>();
+    due.extend(
+      expired
+        .into_iter()
+        .filter_map(|addr| self.unicast.remove(&addr)),
+    );
+    due
+  }
+}
+
+#[cfg(feature = "worksteal")]
+impl Pending {
+  fn new(addr: SocketAddr, response: &Response<'_>, unicast: bool) -> Self {
+    let delay = fastrand::u64(
+      SHARED_RESPONSE_DELAY_MIN.as_millis() as u64..=SHARED_RESPONSE_DELAY_MAX.as_millis() as u64,
+    );
+    tracing::trace!(from=%addr, delay_ms=%delay, "mdns server: delaying a response");
+    Self {
+      addr,
+      deadline: Instant::now() + Duration::from_millis(delay),
+      id: response.id,
+      flags: response.flags,
+      unicast,
+      answers: SmallVec::new(),
+      additionals: SmallVec::new(),
+    }
+  }
+
+  /// Returns the address of the querier whose question started the response.
+  pub(crate) fn addr(&self) -> SocketAddr {
+    self.addr
+  }
+
+  /// Returns the response, borrowing the records.
+  pub(crate) fn response(&self) -> Response<'_> {
+    let mut response = Response {
+      id: self.id,
+      flags: self.flags,
+      ..Default::default()
+    };
+    let records = if self.unicast {
+      &mut response.unicast
+    } else {
+      &mut response.multicast
+    };
+    self
+      .answers
+      .iter()
+      .for_each(|record| records.push_answer(record.as_record()));
+    self
+      .additionals
+      .iter()
+      .for_each(|record| records.push_additional(record.as_record()));
+    response
+  }
+
+  fn extend(&mut self, records: &Records<'_>) {
+    for record in records.answers.iter() {
+      self
+        .additionals
+        .retain(|r| !record_eq(&[], &r.as_record(), record));
+      if !self
+        .answers
+        .iter()
+        .any(|r| record_eq(&[], &r.as_record(), record))
+      {
+        self.answers.push(OwnedRecord::new(record));
+      }
+    }
+
+    for record in records.additionals.iter() {
+      if !self
+        .answers
+        .iter()
+        .chain(self.additionals.iter())
+        .any(|r| record_eq(&[], &r.as_record(), record))
+      {
+        self.additionals.push(OwnedRecord::new(record));
+      }
+    }
+  }
+}
+
+#[cfg(feature = "worksteal")]
+impl OwnedRecord {
+  fn new(record: &ResourceRecord<'_>) -> Self {
+    Self {
+      name: format_smolstr!("{}", record.name()),
+      ty: record.ty(),
+      class: record.class(),
+      ttl: record.ttl(),
+      data: record.data().to_vec(),
+    }
+  }
+
+  fn as_record(&self) -> ResourceRecord<'_> {
+    ResourceRecord::new(
+      self.name.as_str(),
+      self.ty,
+      self.class,
+      self.ttl,
+      &self.data,
+    )
+  }
+}
+
+/// The name in lowercase, type, class without the cache-flush bit, and data of a record.
+type RecordKey = (SmolStr, u16, u16, Vec<u8>);
+
+/// The instants at which the records were last multicast, see [`Response::route`].
+#[derive(Default)]
+pub(crate) struct Multicasted {
+  records: HashMap<RecordKey, (Instant, u32)>,
+}
+
+impl Multicasted {
+  /// Returns `true` if `record` has been multicast within one quarter of its TTL.
+  pub(crate) fn recently(&self, record: &ResourceRecord<'_>, now: Instant) -> bool {
+    self
+      .records
+      .get(&record_key(record))
+      .is_some_and(|(at, _)| now.duration_since(*at) < Duration::from_secs(record.ttl() as u64) / 4)
+  }
+
+  /// Marks `record` as multicast at `now`.
+  pub(crate) fn mark(&mut self, record: &ResourceRecord<'_>, now: Instant) {
+    self.records.insert(record_key(record), (now, record.ttl()));
+  }
+
+  /// Forgets the records whose TTL has elapsed since they were last multicast.
+  pub(crate) fn forget_expired(&mut self, now: Instant) {
+    self
+      .records
+      .retain(|_, (at, ttl)| now.duration_since(*at) < Duration::from_secs(*ttl as u64));
+  }
+}
+
+fn record_key(record: &ResourceRecord<'_>) -> RecordKey {
+  (
+    format_smolstr!("{}", record.name())
+      .to_ascii_lowercase()
+      .into(),
+    record.ty().into(),
+    record.class() & !(1 << 15),
+    record.data().to_vec(),
+  )
+}
+
+fn encode_message<'a>(
+  id: u16,
+  flags: Flags,
+  questions: &mut [Question<'a>],
+  answers: &mut [ResourceRecord<'a>],
+  additionals: &mut [ResourceRecord<'a>],
+) -> io::Result<Vec<u8>> {
+  let msg = Message::new(id, flags, questions, answers, &mut [], additionals);
+  let mut buf = vec![0; msg.space_needed()];
+  let len = msg
+    .write(&mut buf)
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+  buf.truncate(len);
+  Ok(buf)
+}
+
+/// Encodes an unsolicited response, with `answers` in the Answer section.
+fn encode_response(mut answers: SmallVec<ResourceRecord<'_>>) -> io::Result<Vec<u8>> {
+  let mut flags = Flags::new();
+  flags.set_qr(MessageType::Reply).set_authoritative(true);
+
+  encode_message(0, flags, &mut [], &mut answers, &mut [])
+}
+
+/// How long the responses to a host query are still waited for after the last one, see
+/// [`encode_host_query`].
+pub(crate) const HOST_QUIET_PERIOD: Duration = Duration::from_millis(200);
+
+/// How long a host query is waited on before it is sent again, the interval then doubles
+/// every time, see RFC 6762, section 5.2.
+pub(crate) const HOST_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Encodes a query with the message ID `id`, asking for the `A` and `AAAA` records of
+/// `host`, e.g. `foo.local.`.
+///
+/// The query is meant to be sent from an ephemeral port, which makes it a legacy unicast
+/// query, the responders then answer with a conventional unicast response repeating `id`,
+/// see RFC 6762, section 6.7. The unicast-response bit is then meaningless, and not set.
+pub(crate) fn encode_host_query(id: u16, host: &str) -> io::Result<Vec<u8>> {
+  let mut questions = [
+    Question::new(host, ResourceType::A, 1),
+    Question::new(host, ResourceType::AAAA, 1),
+  ];
+  encode_message(id, Flags::new(), &mut questions, &mut [], &mut [])
+}
+
+/// Returns the addresses of `host` in a response to the query with the message ID `id`,
+/// received from `from`.
+///
+/// A goodbye, a record with a TTL of zero, is not an address of the host, see RFC 6762,
+/// section 10.1.
+pub(crate) fn host_addrs(from: SocketAddr, data: &[u8], id: u16, host: &str) -> SmallVec<HostAddr> {
+  let mut questions = SmallVec::new();
+  let mut answers = SmallVec::from([ResourceRecord::default(); 4]);
+  let mut authorities = SmallVec::new();
+  let mut additionals = SmallVec::from([ResourceRecord::default(); 4]);
+
+  let msg = loop {
+    match Message::read(
+      data,
+      &mut questions,
+      &mut answers,
+      &mut authorities,
+      &mut additionals,
+    ) {
+      Ok(msg) => break msg,
+      Err(ProtoError::NotEnoughWriteSpace {
+        tried_to_write,
+        buffer_type,
+        ..
+      }) => match buffer_type {
+        BufferType::Question => questions.resize(tried_to_write.into(), Question::default()),
+        BufferType::Answer => answers.resize(tried_to_write.into(), ResourceRecord::default()),
+        BufferType::Authority => {
+          authorities.resize(tried_to_write.into(), ResourceRecord::default())
+        }
+        BufferType::Additional => {
+          additionals.resize(tried_to_write.into(), ResourceRecord::default())
+        }
+      },
+      Err(e) => {
+        tracing::error!(err=%e, "mdns client: failed to read message");
+        return SmallVec::new();
+      }
+    }
+  };
+
+  // a response to another query, e.g. a late one to the previous user of the port
+  if msg.flags().qr() != MessageType::Reply || msg.id() != id {
+    return SmallVec::new();
+  }
+
+  let host = host.trim_end_matches('.');
+  let mut addrs = SmallVec::<HostAddr>::new();
+  for record in Endpoint::recv(from, data, &msg) {
+    let record = match record {
+      Ok(record) if record.ttl() != 0 => record,
+      Ok(_) => continue,
+      Err(e) => {
+        tracing::error!(err=%e, "mdns client: failed to parse record");
+        continue;
+      }
+    };
+
+    let (name, addr) = match record.into_response() {
+      Answer::A { name, addr } => (name, HostAddr::new(addr.into(), None)),
+      Answer::AAAA { name, addr, zone } => (name, HostAddr::new(addr.into(), zone)),
+      _ => continue,
+    };
+    let name = format_smolstr!("{}", name);
+    if name.trim_end_matches('.').eq_ignore_ascii_case(host) && !addrs.contains(&addr) {
+      addrs.push(addr);
+    }
+  }
+  addrs
+}
+
+/// Returns the names of `records`, in lowercase, as DNS names are case insensitive.
+fn names<'a>(records: &'a [ResourceRecord<'_>]) -> impl Iterator<Item = SmolStr> + 'a {
+  records.iter().map(|record| {
+    format_smolstr!("{}", record.name())
+      .to_ascii_lowercase()
+      .into()
+  })
+}
+
+/// RFC 6762, section 8.1: when the host is ready to send its probe query, it should first
+/// wait for a short random delay time, uniformly distributed in the range 0-250 ms.
+fn probe_delay() -> Duration {
+  Duration::from_millis(fastrand::u64(0..250))
+}
 
 #[cfg(unix)]
 pub(crate) use unix_impl::*;
@@ -1,5 +1,9 @@
 use mdns_proto::proto::{Label, ResourceRecord, ResourceType};
 
+use smol_str::SmolStr;
+
+use crate::service::Service;
+
 pub use agnostic_net as net;
 pub use async_channel as channel;
 pub use client::*;
@@ -30,9 +34,38 @@ pub trait Zone: core::fmt::Debug + Send + Sync + 'static {
     name: Label<'a>,
     rt: ResourceType,
   ) -> impl Future<Output = Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error>> + Send + 'a;
+
+  /// Returns the shared records of the zone.
+  ///
+  /// The server announces these records, along with the unique records, once the unique
+  /// records are claimed, see [`mdns_proto::announcer`]. Defaults to no shared records.
+  fn shared_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    core::iter::empty()
+  }
+
+  /// Returns the unique records of the zone.
+  ///
+  /// The server probes for these records before answering any query, see
+  /// [`mdns_proto::prober`]. Defaults to no unique records.
+  fn unique_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    core::iter::empty()
+  }
+
+  /// Returns a copy of the zone in which `name`, one of the names of the unique records
+  /// owned by another host, is replaced by a new name, along with the new name.
+  ///
+  /// The server probes again with the renamed zone. Defaults to `None`, the zone
+  /// cannot be renamed and the server stops serving it.
+  fn rename(&self, name: Label<'_>) -> Option<(Self, SmolStr)>
+  where
+    Self: Sized,
+  {
+    let _ = name;
+    None
+  }
 }
 
-impl Zone for super::service::Service {
+impl Zone for Service {
   type Error = core::convert::Infallible;
 
   async fn answers<'a>(
@@ -50,4 +83,16 @@ impl Zone for super::service::Service {
   ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
     Ok(core::iter::empty())
   }
+
+  fn shared_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    Service::shared_records(self)
+  }
+
+  fn unique_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    Service::unique_records(self)
+  }
+
+  fn rename(&self, name: Label<'_>) -> Option<(Self, SmolStr)> {
+    Service::rename(self, name)
+  }
 }
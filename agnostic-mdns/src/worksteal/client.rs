@@ -1,43 +1,63 @@
 use core::{
   net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+  str::Utf8Error,
   time::Duration,
 };
 use std::{
-  collections::{HashMap, hash_map::Entry},
+  collections::{HashMap, HashSet, hash_map::Entry},
   io,
+  marker::PhantomData,
   net::IpAddr,
   pin::Pin,
   task::{Context, Poll},
+  time::Instant,
 };
 
-use agnostic_net::{Net, UdpSocket, runtime::RuntimeLite};
+use agnostic_net::{Net, ToSocketAddrs, UdpSocket, runtime::RuntimeLite};
 use async_channel::{Receiver, Sender};
-use either::Either;
 use futures::{FutureExt, Stream};
 use iprobe::{ipv4, ipv6};
 use mdns_proto::{
-  client::{Endpoint, Response},
+  cache::{CacheEvent, SlabCache},
+  client::{Endpoint, QuerySchedule, Response},
   error::BufferType,
-  proto::{Flags, Label, Message, Question, ResourceRecord},
+  proto::{Flags, Label, Message, MessageType, Question, ResourceRecord, ResourceType},
+  suppression::SlabSuppression,
+  utils::label_eq,
 };
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
 use smallvec_wrapper::SmallVec;
-use smol_str::{SmolStr, ToSmolStr, format_smolstr};
+use smol_str::{SmolStr, ToSmolStr};
 use triomphe::Arc;
 
 use crate::{
-  Buffer, IPV4_MDNS, IPV6_MDNS, MDNS_PORT, QueryParam,
-  utils::{multicast_udp4_socket, multicast_udp6_socket, unicast_udp4_socket, unicast_udp6_socket},
+  Buffer, Completeness, HostAddr, IPV4_MDNS, IPV6_MDNS, MDNS_PORT, QueryParam, RecordQueryParam,
+  ResolveParam, TxtRecord,
+  utils::{
+    HOST_QUERY_INTERVAL, HOST_QUIET_PERIOD, encode_host_query, host_addrs, multicast_udp4_socket,
+    multicast_udp6_socket, unicast_udp4_socket, unicast_udp6_socket,
+  },
 };
 
+/// The interval between the first two follow-up queries of an incomplete entry, it
+/// doubles after every follow-up, until the query times out.
+const FOLLOW_UP_INTERVAL: Duration = Duration::from_millis(250);
+
 /// Returned after we query for a service.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ServiceEntry {
   name: SmolStr,
   host: SmolStr,
-  socket_v4: Option<SocketAddrV4>,
-  socket_v6: Option<SocketAddrV6>,
-  txt: Arc<[SmolStr]>,
+  port: u16,
+  priority: u16,
+  weight: u16,
+  socket_v4s: Arc<[SocketAddrV4]>,
+  socket_v6s: Arc<[SocketAddrV6]>,
+  txt: Arc<[Arc<[u8]>]>,
+  expires: Option<Instant>,
+  source: Option<SocketAddr>,
+  ipv4_interface: Option<Ipv4Addr>,
+  ipv6_interface: Option<u32>,
 }
 
 impl ServiceEntry {
@@ -53,51 +73,277 @@ impl ServiceEntry {
     &self.host
   }
 
-  /// Returns the IPv4 address of the service.
+  /// Returns the first IPv4 address of the service.
   #[inline]
   pub fn ipv4_addr(&self) -> Option<&Ipv4Addr> {
-    self.socket_v4.as_ref().map(|addr| addr.ip())
+    self.socket_v4s.first().map(|addr| addr.ip())
   }
 
-  /// Returns the IPv6 address of the service.
+  /// Returns the first IPv6 address of the service.
   #[inline]
   pub fn ipv6_addr(&self) -> Option<&Ipv6Addr> {
-    self.socket_v6.as_ref().map(|addr| addr.ip())
+    self.socket_v6s.first().map(|addr| addr.ip())
+  }
+
+  /// Returns all the IPv4 addresses of the host of the service.
+  #[inline]
+  pub fn ipv4_addrs(&self) -> impl Iterator<Item = &Ipv4Addr> + '_ {
+    self.socket_v4s.iter().map(|addr| addr.ip())
+  }
+
+  /// Returns all the IPv6 addresses of the host of the service.
+  #[inline]
+  pub fn ipv6_addrs(&self) -> impl Iterator<Item = &Ipv6Addr> + '_ {
+    self.socket_v6s.iter().map(|addr| addr.ip())
+  }
+
+  /// Returns the socket addresses of the service, the IPv4 ones first, a link-local IPv6
+  /// address has the scope id of the interface it was received on.
+  #[inline]
+  pub fn socket_addrs(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+    self
+      .socket_v4s
+      .iter()
+      .map(|addr| SocketAddr::V4(*addr))
+      .chain(self.socket_v6s.iter().map(|addr| SocketAddr::V6(*addr)))
   }
 
   /// Returns the port of the service.
   #[inline]
-  pub fn port(&self) -> u16 {
-    if let Some(ref addr) = self.socket_v4 {
-      return addr.port();
-    }
+  pub const fn port(&self) -> u16 {
+    self.port
+  }
+
+  /// Returns the priority of the host of the service, from the SRV record, a client
+  /// should reach the host with the lowest priority first, see RFC 2782.
+  #[inline]
+  pub const fn priority(&self) -> u16 {
+    self.priority
+  }
+
+  /// Returns the weight of the host of the service, from the SRV record, among the
+  /// hosts of the same priority, see RFC 2782.
+  #[inline]
+  pub const fn weight(&self) -> u16 {
+    self.weight
+  }
+
+  /// Returns the character-strings of the TXT record of the service, as opaque bytes.
+  #[inline]
+  pub fn txt(&self) -> impl Iterator<Item = &[u8]> + '_ {
+    self.txt.iter().map(|s| &s[..])
+  }
+
+  /// Returns the character-strings of the TXT record of the service, as strings, if
+  /// they are valid UTF-8.
+  #[inline]
+  pub fn txt_strs(&self) -> impl Iterator<Item = Result<&str, Utf8Error>> + '_ {
+    self.txt().map(core::str::from_utf8)
+  }
+
+  /// Returns the `key=value` attributes of the TXT record of the service, see RFC 6763,
+  /// section 6.
+  #[inline]
+  pub fn txt_record(&self) -> TxtRecord {
+    TxtRecord::from_strings(self.txt())
+  }
+
+  /// Returns the instant at which the first of the records the entry was assembled from
+  /// expires, the entry is stale after it, unless the records are received again.
+  ///
+  /// Returns `None` if the entry was assembled from none of its SRV, TXT and address
+  /// records, e.g. a partial entry, see [`Completeness::Partial`].
+  #[inline]
+  pub const fn expires(&self) -> Option<Instant> {
+    self.expires
+  }
+
+  /// Returns the address of the responder which sent the SRV record of the service.
+  #[inline]
+  pub const fn source(&self) -> Option<SocketAddr> {
+    self.source
+  }
+
+  /// Returns the IPv4 interface the SRV record of the service was received on, the one
+  /// the lookup is bound to, see [`QueryParam::with_ipv4_interface`].
+  ///
+  /// Returns `None` if the record was received over IPv6, or if the lookup is bound to
+  /// no interface.
+  #[inline]
+  pub const fn ipv4_interface(&self) -> Option<&Ipv4Addr> {
+    self.ipv4_interface.as_ref()
+  }
+
+  /// Returns the index of the IPv6 interface the SRV record of the service was received
+  /// on, the one the lookup is bound to, see [`QueryParam::with_ipv6_interface`].
+  ///
+  /// If the lookup is bound to no interface, the interface is only known when the record
+  /// was received from a link-local address, it is then the scope id of the address.
+  /// Returns `None` if the record was received over IPv4.
+  #[inline]
+  pub const fn ipv6_interface(&self) -> Option<u32> {
+    self.ipv6_interface
+  }
 
-    if let Some(ref addr) = self.socket_v6 {
-      return addr.port();
+  /// Returns `true` if the service described by `other` differs from this one, the
+  /// expiry and the responder are not part of the service, and the order of the
+  /// addresses does not matter.
+  fn changed(&self, other: &Self) -> bool {
+    fn same<T: PartialEq>(a: &[T], b: &[T]) -> bool {
+      a.len() == b.len() && a.iter().all(|addr| b.contains(addr))
     }
 
-    unreachable!("must have a socket address")
+    self.name != other.name
+      || self.host != other.host
+      || self.port != other.port
+      || self.priority != other.priority
+      || self.weight != other.weight
+      || self.txt != other.txt
+      || !same(&self.socket_v4s, &other.socket_v4s)
+      || !same(&self.socket_v6s, &other.socket_v6s)
+  }
+}
+
+/// A record received in response to a question of [`query_records`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordEntry {
+  name: SmolStr,
+  ttl: u32,
+  data: RecordData,
+}
+
+impl RecordEntry {
+  /// Returns the name of the record.
+  #[inline]
+  pub fn name(&self) -> &SmolStr {
+    &self.name
+  }
+
+  /// Returns the TTL of the record, in seconds.
+  #[inline]
+  pub const fn ttl(&self) -> u32 {
+    self.ttl
   }
 
-  /// Returns the additional information of the service.
+  /// Returns the type of the record.
   #[inline]
-  pub fn txt(&self) -> &[SmolStr] {
-    &self.txt
+  pub const fn ty(&self) -> ResourceType {
+    match self.data {
+      RecordData::A(_) => ResourceType::A,
+      RecordData::AAAA { .. } => ResourceType::AAAA,
+      RecordData::Ptr(_) => ResourceType::Ptr,
+      RecordData::Srv { .. } => ResourceType::Srv,
+      RecordData::Txt(_) => ResourceType::Txt,
+      RecordData::Other { ty, .. } => ty,
+    }
+  }
+
+  /// Returns the data of the record.
+  #[inline]
+  pub const fn data(&self) -> &RecordData {
+    &self.data
+  }
+
+  /// Returns the entry of a cached record, the names inside its data are uncompressed.
+  fn new(from: SocketAddr, record: &ResourceRecord<'_>) -> Option<Self> {
+    let data = match Endpoint::parse(from, &[], record) {
+      Ok(Response::A { addr, .. }) => RecordData::A(addr),
+      Ok(Response::AAAA { addr, zone, .. }) => RecordData::AAAA { addr, zone },
+      Ok(Response::Ptr(name)) => RecordData::Ptr(name.to_smolstr()),
+      Ok(Response::Srv { srv, .. }) => RecordData::Srv {
+        priority: srv.priority(),
+        weight: srv.weight(),
+        port: srv.port(),
+        target: srv.target().to_smolstr(),
+      },
+      Ok(Response::Txt { txt, .. }) => {
+        match txt
+          .strings()
+          .map(|res| res.map(|s| Arc::from(s.as_bytes())))
+          .collect::<Result<Arc<[_]>, _>>()
+        {
+          Ok(txt) => RecordData::Txt(txt),
+          Err(e) => {
+            tracing::error!(err=%e, "mdns client: failed to parse txt record");
+            return None;
+          }
+        }
+      }
+      Ok(Response::Other { ty, data, .. }) => RecordData::Other {
+        ty,
+        data: Arc::from(data),
+      },
+      Err(e) => {
+        tracing::error!(err=%e, "mdns client: failed to parse record");
+        return None;
+      }
+    };
+
+    Some(Self {
+      name: record.name().to_smolstr(),
+      ttl: record.ttl(),
+      data,
+    })
   }
 }
 
+/// The data of a [`RecordEntry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RecordData {
+  /// The address of an `A` record.
+  A(Ipv4Addr),
+  /// The address of an `AAAA` record.
+  AAAA {
+    /// The IPv6 address.
+    addr: Ipv6Addr,
+    /// The scope id of a link-local address.
+    zone: Option<u32>,
+  },
+  /// The name pointed to by a `PTR` record, e.g. a service instance.
+  Ptr(SmolStr),
+  /// The data of a `SRV` record.
+  Srv {
+    /// The priority of the target host.
+    priority: u16,
+    /// The weight of the target host, among the hosts of the same priority.
+    weight: u16,
+    /// The port of the service on the target host.
+    port: u16,
+    /// The target host.
+    target: SmolStr,
+  },
+  /// The character-strings of a `TXT` record, as opaque bytes.
+  Txt(Arc<[Arc<[u8]>]>),
+  /// The undecoded data of a record of another type.
+  Other {
+    /// The type of the record.
+    ty: ResourceType,
+    /// The data of the record.
+    data: Arc<[u8]>,
+  },
+}
+
 /// Returned after we query for a service.
 #[derive(Clone, Debug)]
 struct ServiceEntryBuilder {
   name: SmolStr,
   host: SmolStr,
   port: u16,
-  ipv4: Option<Ipv4Addr>,
-  ipv6: Option<Ipv6Addr>,
-  zone: Option<u32>,
-  txts: Option<Arc<[SmolStr]>>,
-  sent: bool,
+  priority: u16,
+  weight: u16,
+  addrs: HostRecords,
+  txts: Option<Arc<[Arc<[u8]>]>>,
+  /// The expiry of the first of the SRV and TXT records to expire.
+  expires: Option<Instant>,
+  source: Option<SocketAddr>,
+  ipv4_interface: Option<Ipv4Addr>,
+  ipv6_interface: Option<u32>,
+  /// The entry last reported to the querier.
+  reported: Option<ServiceEntry>,
   queried: bool,
+  /// Whether a followed `PTR` record points to the instance.
+  pointed: bool,
 }
 
 impl Default for ServiceEntryBuilder {
@@ -107,19 +353,47 @@ impl Default for ServiceEntryBuilder {
       name: SmolStr::default(),
       host: SmolStr::default(),
       port: 0,
-      ipv4: None,
-      ipv6: None,
-      zone: None,
+      priority: 0,
+      weight: 0,
+      addrs: HostRecords::default(),
       txts: None,
-      sent: false,
+      expires: None,
+      source: None,
+      ipv4_interface: None,
+      ipv6_interface: None,
+      reported: None,
       queried: false,
+      pointed: false,
     }
   }
 }
 
 impl ServiceEntryBuilder {
-  fn complete(&self) -> bool {
-    (self.ipv4.is_some() || self.ipv6.is_some()) && self.port != 0 && self.txts.is_some()
+  /// Returns `true` if the entry has the records required by `completeness`.
+  fn complete(&self, completeness: Completeness) -> bool {
+    match completeness {
+      Completeness::RequireTxt => self.addrs.has_addr() && self.port != 0 && self.txts.is_some(),
+      Completeness::SrvAndAddress => self.addrs.has_addr() && self.port != 0,
+      Completeness::Partial => true,
+    }
+  }
+
+  /// Returns the names and types of the records missing from the entry, the `SRV` and
+  /// `TXT` records of the instance, or the `A` and `AAAA` records of the target host of
+  /// the `SRV` record.
+  fn missing(&self) -> SmallVec<(SmolStr, ResourceType)> {
+    let mut missing = SmallVec::new();
+    if self.port == 0 {
+      missing.push((self.name.clone(), ResourceType::Srv));
+    }
+    if self.txts.is_none() {
+      missing.push((self.name.clone(), ResourceType::Txt));
+    }
+    if !self.addrs.has_addr() && !self.host.is_empty() {
+      missing.push((self.host.clone(), ResourceType::A));
+      missing.push((self.host.clone(), ResourceType::AAAA));
+    }
+    missing
   }
 
   #[inline]
@@ -128,20 +402,68 @@ impl ServiceEntryBuilder {
     self
   }
 
+  /// Records that one of the SRV and TXT records of the entry expires at `at`.
+  #[inline]
+  fn expire(&mut self, at: Instant) {
+    self.expires = Some(earliest(self.expires, at));
+  }
+
+  /// Records that the SRV record of the entry was sent by `source`, and received on `link`.
+  fn received(&mut self, source: Option<SocketAddr>, link: &LinkInterface) {
+    self.source = source;
+    self.ipv4_interface = link.ipv4;
+    // without an interface, the one of a link-local address is its scope id
+    self.ipv6_interface = match source {
+      Some(SocketAddr::V6(addr)) => link
+        .ipv6
+        .or((addr.scope_id() != 0).then_some(addr.scope_id())),
+      _ => None,
+    };
+  }
+
   #[inline]
   fn finalize(&self) -> ServiceEntry {
+    let expires = match self.addrs.expires {
+      Some(at) => Some(earliest(self.expires, at)),
+      None => self.expires,
+    };
+
     ServiceEntry {
       name: self.name.clone(),
       host: self.host.clone(),
-      socket_v4: self.ipv4.map(|ip| SocketAddrV4::new(ip, self.port)),
-      socket_v6: self
-        .ipv6
-        .map(|ip| SocketAddrV6::new(ip, self.port, 0, self.zone.unwrap_or(0))),
-      txt: self.txts.as_ref().unwrap().clone(),
+      port: self.port,
+      priority: self.priority,
+      weight: self.weight,
+      socket_v4s: self
+        .addrs
+        .ipv4s
+        .iter()
+        .map(|ip| SocketAddrV4::new(*ip, self.port))
+        .collect(),
+      socket_v6s: self
+        .addrs
+        .ipv6s
+        .iter()
+        .map(|(ip, zone)| SocketAddrV6::new(*ip, self.port, 0, zone.unwrap_or(0)))
+        .collect(),
+      txt: self
+        .txts
+        .clone()
+        .unwrap_or_else(|| core::iter::empty().collect()),
+      expires,
+      source: self.source,
+      ipv4_interface: self.ipv4_interface,
+      ipv6_interface: self.ipv6_interface,
     }
   }
 }
 
+/// Returns the earlier of `current`, if any, and `at`.
+#[inline]
+fn earliest(current: Option<Instant>, at: Instant) -> Instant {
+  current.map_or(at, |current| current.min(at))
+}
+
 /// A handle to cancel a lookup.
 #[derive(Debug, Clone)]
 pub struct Canceller(Sender<()>);
@@ -195,11 +517,68 @@ impl Stream for Lookup {
   }
 }
 
+/// Events emitted by a [`Browser`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BrowseEvent {
+  /// A service was discovered.
+  Added(ServiceEntry),
+  /// The addresses, port, SRV priority or weight, or TXT record of a discovered service
+  /// changed.
+  Updated(ServiceEntry),
+  /// A discovered service said goodbye, or its records expired, the entry is the last
+  /// known state of the service.
+  Removed(ServiceEntry),
+}
+
+pin_project_lite::pin_project! {
+  /// A stream of the changes of the services found by a continuous query.
+  pub struct Browser {
+    shutdown_tx: Sender<()>,
+    has_err: bool,
+    #[pin]
+    event_rx: Receiver<io::Result<BrowseEvent>>,
+  }
+}
+
+impl Browser {
+  /// Returns a handle to cancel the browsing.
+  #[inline]
+  pub fn canceller(&self) -> Canceller {
+    Canceller(self.shutdown_tx.clone())
+  }
+}
+
+impl Stream for Browser {
+  type Item = io::Result<BrowseEvent>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    let this = self.project();
+
+    if *this.has_err {
+      return Poll::Ready(None);
+    }
+
+    this.event_rx.poll_next(cx).map(|res| match res {
+      Some(Ok(event)) => Some(Ok(event)),
+      Some(Err(e)) => {
+        *this.has_err = true;
+        Some(Err(e))
+      }
+      None => None,
+    })
+  }
+}
+
 /// Looks up a given service, in a domain, waiting at most
 /// for a timeout before finishing the query. The results are streamed
 /// to a channel. Sends will not block, so clients should make sure to
 /// either read or buffer. This method will attempt to stop the query
 /// on cancellation.
+///
+/// A service is sent once it has the records required by
+/// [`QueryParam::completeness`], and sent again every time it changes if partial
+/// entries are asked for.
 pub async fn query<N>(params: QueryParam<'_>, entry_tx: Sender<ServiceEntry>) -> io::Result<()>
 where
   N: Net,
@@ -215,7 +594,7 @@ where
   )
   .await?;
 
-  let name = format_smolstr!("{}.{}", params.service, params.domain);
+  let name = params.name();
   tracing::info!("mdns client: starting query for {}", name);
   <N::Runtime as RuntimeLite>::spawn_detach(async move {
     match client
@@ -223,6 +602,7 @@ where
         name,
         params.want_unicast_response,
         params.timeout,
+        params.completeness,
         entry_tx,
         shutdown_rx,
         params.max_payload_size,
@@ -242,36 +622,715 @@ where
     }
   });
 
-  Ok(())
-}
+  Ok(())
+}
+
+/// Similar to [`query`], however it uses all the default parameters
+pub async fn lookup<N>(service: Label<'_>, tx: Sender<ServiceEntry>) -> io::Result<()>
+where
+  N: Net,
+{
+  query::<N>(QueryParam::new(service), tx).await
+}
+
+/// Sends the questions of `params` in one packet, waiting at most for a timeout before
+/// finishing the query. The records answering the questions are streamed to a channel,
+/// each record once, as typed [`RecordEntry`] values. Sends will not block, so clients
+/// should make sure to either read or buffer.
+pub async fn query_records<N>(
+  params: RecordQueryParam<'_>,
+  tx: Sender<RecordEntry>,
+) -> io::Result<()>
+where
+  N: Net,
+{
+  if params.questions.is_empty() {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "at least one question must be asked",
+    ));
+  }
+
+  let (shutdown_tx, shutdown_rx) = async_channel::bounded::<()>(1);
+
+  // create a new client
+  let client = Clients::<N>::new(
+    !params.disable_ipv4 && ipv4(),
+    !params.disable_ipv6 && ipv6(),
+    params.ipv4_interface,
+    params.ipv6_interface,
+  )
+  .await?;
+
+  let questions = params
+    .questions
+    .iter()
+    .map(|q| (q.name().to_smolstr(), q.ty(), q.class()))
+    .collect::<SmallVec<_>>();
+  tracing::info!(
+    "mdns client: starting query for {} questions",
+    questions.len()
+  );
+  <N::Runtime as RuntimeLite>::spawn_detach(async move {
+    match client
+      .query_records_in(
+        questions,
+        params.timeout,
+        tx,
+        shutdown_rx,
+        params.max_payload_size,
+      )
+      .await
+    {
+      Ok(_) => {
+        if shutdown_tx.close() {
+          tracing::info!("mdns client: closing");
+        }
+      }
+      Err(e) => {
+        if shutdown_tx.close() {
+          tracing::error!(err=%e, "mdns client: closing");
+        }
+      }
+    }
+  });
+
+  Ok(())
+}
+
+/// Browses a given service, in a domain, until cancelled.
+///
+/// Unlike [`query`], the query is repeated, one second after the first one, then with
+/// an interval doubling every time, up to one hour, see RFC 6762, section 5.2. The
+/// returned stream tells when a service is added, updated or removed. The timeout of
+/// `params` is ignored, a service is added once it has the records required by
+/// [`QueryParam::completeness`].
+pub async fn browse<N>(params: QueryParam<'_>) -> io::Result<Browser>
+where
+  N: Net,
+{
+  let (shutdown_tx, shutdown_rx) = async_channel::bounded::<()>(1);
+  let (event_tx, event_rx) = match params.cap {
+    Some(cap) => async_channel::bounded(cap),
+    None => async_channel::unbounded(),
+  };
+
+  // create a new client
+  let client = Clients::<N>::new(
+    !params.disable_ipv4 && ipv4(),
+    !params.disable_ipv6 && ipv6(),
+    params.ipv4_interface,
+    params.ipv6_interface,
+  )
+  .await?;
+
+  let name = params.name();
+  tracing::info!("mdns client: starting to browse {}", name);
+  let closer = shutdown_tx.clone();
+  <N::Runtime as RuntimeLite>::spawn_detach(async move {
+    let res = client
+      .browse_in(
+        name,
+        params.want_unicast_response,
+        params.completeness,
+        event_tx.clone(),
+        shutdown_rx,
+        params.max_payload_size,
+      )
+      .await;
+
+    if let Err(e) = res {
+      tracing::error!(err=%e, "mdns client: failed to browse");
+      let _ = event_tx.send(Err(e)).await;
+    }
+
+    if closer.close() {
+      tracing::info!("mdns client: closing");
+    }
+  });
+
+  Ok(Browser {
+    shutdown_tx,
+    has_err: false,
+    event_rx,
+  })
+}
+
+/// Resolves a single service instance, e.g. `My Printer._ipp._tcp.local.`, waiting at
+/// most for a timeout.
+///
+/// The `SRV` and `TXT` records of the instance are asked for, then the `A` and `AAAA`
+/// records of the target host of the `SRV` record, if they are not in the Additional
+/// section of the response. Returns an error of kind [`io::ErrorKind::TimedOut`] if the
+/// instance is not resolved in time.
+pub async fn resolve<N>(params: ResolveParam<'_>) -> io::Result<ServiceEntry>
+where
+  N: Net,
+{
+  // the listeners stop once the sender is dropped
+  let (_shutdown_tx, shutdown_rx) = async_channel::bounded::<()>(1);
+
+  // create a new client
+  let client = Clients::<N>::new(
+    !params.disable_ipv4 && ipv4(),
+    !params.disable_ipv6 && ipv6(),
+    params.ipv4_interface,
+    params.ipv6_interface,
+  )
+  .await?;
+
+  let name = params.name.to_smolstr();
+  tracing::info!("mdns client: resolving {}", name);
+  <N::Runtime as RuntimeLite>::timeout(
+    params.timeout,
+    client.resolve_in(
+      name,
+      params.want_unicast_response,
+      shutdown_rx,
+      params.max_payload_size,
+    ),
+  )
+  .await
+  .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "mdns client: resolve timed out"))?
+}
+
+/// Resolves a `.local` hostname, e.g. `printer.local.`, to its addresses, waiting at most
+/// for a timeout.
+///
+/// The `A` and `AAAA` records of the host are asked for, from an ephemeral port, the
+/// responders then answer with a conventional unicast response, see RFC 6762,
+/// section 6.7. The query is sent again until a host answers, after 1 second, then at
+/// doubling intervals, see RFC 6762, section 5.2. The addresses of the responses naming
+/// the host are collected until no response is received for a short while, a link-local
+/// IPv6 address has the scope id of the interface it was received on.
+/// Returns an error of kind [`io::ErrorKind::TimedOut`] if no host answers in time.
+pub async fn resolve_host<N>(params: ResolveParam<'_>) -> io::Result<Vec<HostAddr>>
+where
+  N: Net,
+{
+  let host = params.name.to_smolstr();
+  let id = fastrand::u16(..);
+  let query = encode_host_query(id, &host)?;
+
+  let v4 = if !params.disable_ipv4 && ipv4() {
+    match unicast_udp4_socket(params.ipv4_interface)
+      .and_then(<N::UdpSocket as TryFrom<_>>::try_from)
+    {
+      Ok(conn) => Some(conn),
+      Err(e) => {
+        tracing::error!(err=%e, "mdns client: failed to bind to udp4 port");
+        None
+      }
+    }
+  } else {
+    None
+  };
+  let v6 = if !params.disable_ipv6 && ipv6() {
+    match unicast_udp6_socket(params.ipv6_interface)
+      .and_then(<N::UdpSocket as TryFrom<_>>::try_from)
+    {
+      Ok(conn) => Some(conn),
+      Err(e) => {
+        tracing::error!(err=%e, "mdns client: failed to bind to udp6 port");
+        None
+      }
+    }
+  } else {
+    None
+  };
+
+  if v4.is_none() && v6.is_none() {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "at least one of IPv4 and IPv6 must be enabled for querying",
+    ));
+  }
+
+  tracing::info!("mdns client: resolving host {}", host);
+  send_host_query::<N>(v4.as_ref(), v6.as_ref(), &query).await?;
+
+  let deadline = Instant::now() + params.timeout;
+  let mut interval = HOST_QUERY_INTERVAL;
+  let mut resend = Instant::now() + interval;
+  let mut buf4 = Buffer::zerod(params.max_payload_size);
+  let mut buf6 = Buffer::zerod(params.max_payload_size);
+  let mut addrs = Vec::new();
+  loop {
+    // once a host answered, the other responses are only waited for a short while, until
+    // then the query is sent again
+    let now = Instant::now();
+    let mut wait = deadline.saturating_duration_since(now);
+    if addrs.is_empty() {
+      wait = wait.min(resend.saturating_duration_since(now));
+    } else {
+      wait = wait.min(HOST_QUIET_PERIOD);
+    }
+
+    let recv = async {
+      futures::select! {
+        addrs = recv_host::<N>(v4.as_ref(), &mut buf4, id, &host).fuse() => addrs,
+        addrs = recv_host::<N>(v6.as_ref(), &mut buf6, id, &host).fuse() => addrs,
+      }
+    };
+    match <N::Runtime as RuntimeLite>::timeout(wait, recv).await {
+      Ok(received) => {
+        for addr in received {
+          if !addrs.contains(&addr) {
+            addrs.push(addr);
+          }
+        }
+      }
+      Err(_) if !addrs.is_empty() => return Ok(addrs),
+      Err(_) if Instant::now() >= deadline => {
+        return Err(io::Error::new(
+          io::ErrorKind::TimedOut,
+          "mdns client: resolve host timed out",
+        ));
+      }
+      Err(_) => {
+        tracing::debug!("mdns client: resending the query for host {}", host);
+        if let Err(e) = send_host_query::<N>(v4.as_ref(), v6.as_ref(), &query).await {
+          tracing::error!(err=%e, "mdns client: failed to resend query");
+        }
+        interval *= 2;
+        resend = Instant::now() + interval;
+      }
+    }
+  }
+}
+
+/// Sends the host `query` to the mDNS groups, on the sockets there are.
+///
+/// Returns the error of the last send if none of them succeeds.
+async fn send_host_query<N: Net>(
+  v4: Option<&N::UdpSocket>,
+  v6: Option<&N::UdpSocket>,
+  query: &[u8],
+) -> io::Result<()> {
+  let mut res = Ok(());
+  let mut sent = false;
+  for (conn, group) in [(v4, IpAddr::from(IPV4_MDNS)), (v6, IpAddr::from(IPV6_MDNS))] {
+    let Some(conn) = conn else {
+      continue;
+    };
+    match conn.send_to(query, (group, MDNS_PORT)).await {
+      Ok(_) => sent = true,
+      Err(e) => {
+        tracing::error!(err=%e, "mdns client: failed to send query");
+        res = Err(e);
+      }
+    }
+  }
+  if sent { Ok(()) } else { res }
+}
+
+/// Waits for a response to the query `id` naming `host` on `conn`, forever if there is
+/// no `conn`.
+async fn recv_host<N: Net>(
+  conn: Option<&N::UdpSocket>,
+  buf: &mut [u8],
+  id: u16,
+  host: &str,
+) -> SmallVec<HostAddr> {
+  let Some(conn) = conn else {
+    return futures::future::pending().await;
+  };
+
+  loop {
+    match conn.recv_from(buf).await {
+      Ok((size, src)) => {
+        tracing::trace!(from=%src, data=?&buf[..size], "mdns client: received packet");
+        let addrs = host_addrs(src, &buf[..size], id, host);
+        if !addrs.is_empty() {
+          return addrs;
+        }
+      }
+      Err(e) => tracing::error!(err=%e, "mdns client: failed to receive packet"),
+    }
+  }
+}
+
+/// A `.local` hostname and a port, resolved by [`resolve_host`] when passed to the
+/// `connect` or `bind` calls of [`agnostic_net`].
+pub struct LocalHost<N> {
+  name: SmolStr,
+  port: u16,
+  timeout: Duration,
+  ipv4_interface: Option<Ipv4Addr>,
+  ipv6_interface: Option<u32>,
+  want_unicast_response: bool,
+  disable_ipv4: bool,
+  disable_ipv6: bool,
+  max_payload_size: usize,
+  _net: PhantomData<fn() -> N>,
+}
+
+impl<N> Clone for LocalHost<N> {
+  fn clone(&self) -> Self {
+    Self {
+      name: self.name.clone(),
+      _net: PhantomData,
+      ..*self
+    }
+  }
+}
+
+impl<N> core::fmt::Debug for LocalHost<N> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("LocalHost")
+      .field("name", &self.name)
+      .field("port", &self.port)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<N> LocalHost<N> {
+  /// Creates a new `.local` hostname and port, the hostname is resolved as configured by
+  /// `params`.
+  pub fn new(params: ResolveParam<'_>, port: u16) -> Self {
+    Self {
+      name: params.name.to_smolstr(),
+      port,
+      timeout: params.timeout,
+      ipv4_interface: params.ipv4_interface,
+      ipv6_interface: params.ipv6_interface,
+      want_unicast_response: params.want_unicast_response,
+      disable_ipv4: params.disable_ipv4,
+      disable_ipv6: params.disable_ipv6,
+      max_payload_size: params.max_payload_size,
+      _net: PhantomData,
+    }
+  }
+
+  /// Returns the hostname.
+  #[inline]
+  pub fn name(&self) -> &SmolStr {
+    &self.name
+  }
+
+  /// Returns the port.
+  #[inline]
+  pub const fn port(&self) -> u16 {
+    self.port
+  }
+
+  fn params(&self) -> ResolveParam<'_> {
+    let mut params = ResolveParam::new(self.name.as_str().into())
+      .with_timeout(self.timeout)
+      .with_unicast_response(self.want_unicast_response)
+      .with_disable_ipv4(self.disable_ipv4)
+      .with_disable_ipv6(self.disable_ipv6)
+      .with_max_payload_size(self.max_payload_size);
+    params.ipv4_interface = self.ipv4_interface;
+    params.ipv6_interface = self.ipv6_interface;
+    params
+  }
+}
+
+impl<N: Net> ToSocketAddrs<N::Runtime> for LocalHost<N> {
+  type Iter = std::vec::IntoIter<SocketAddr>;
+  type Future = Pin<Box<dyn Future<Output = io::Result<Self::Iter>> + Send>>;
+
+  fn to_socket_addrs(&self) -> Self::Future {
+    let this = self.clone();
+    Box::pin(async move {
+      let addrs = resolve_host::<N>(this.params()).await?;
+      Ok(
+        addrs
+          .into_iter()
+          .map(|addr| addr.to_socket_addr(this.port))
+          .collect::<Vec<_>>()
+          .into_iter(),
+      )
+    })
+  }
+}
+
+/// Provides a query interface that can be used to
+/// search for service providers using mDNS
+struct Clients<N: Net> {
+  v4: Option<Client<N>>,
+  v6: Option<Client<N>>,
+}
+
+impl<N: Net> Clients<N> {
+  #[allow(clippy::too_many_arguments)]
+  async fn query_in(
+    self,
+    service: SmolStr,
+    want_unicast_response: bool,
+    timeout: Duration,
+    completeness: Completeness,
+    tx: Sender<ServiceEntry>,
+    shutdown_rx: Receiver<()>,
+    max_payload_size: usize,
+  ) -> io::Result<()> {
+    // Start listening for response packets
+    let (msg_tx, msg_rx) = async_channel::bounded::<Incoming>(32);
+
+    let q = Endpoint::prepare_question(Label::from(service.as_str()), want_unicast_response);
+
+    let mut qs = [q];
+    let msg = Message::new(0, Flags::new(), &mut qs, &mut [], &mut [], &mut []);
+    let space_needed = msg.space_needed();
+    let mut buf = Buffer::zerod(space_needed);
+    let len = msg
+      .write(&mut buf)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    // Map the in-progress responses
+    let inprogress = Arc::new(Mutex::new(InprogressCache::new(
+      Some(service.clone()),
+      completeness,
+    )));
+
+    if let Some(ref client) = self.v4 {
+      let tx = msg_tx.clone();
+      let shutdown_rx = shutdown_rx.clone();
+      let buf = buf.clone();
+      client.query(
+        inprogress.clone(),
+        tx,
+        shutdown_rx,
+        max_payload_size,
+        buf,
+        len,
+      );
+    }
+
+    if let Some(ref client) = self.v6 {
+      let tx = msg_tx.clone();
+      let shutdown_rx = shutdown_rx.clone();
+      client.query(
+        inprogress.clone(),
+        tx,
+        shutdown_rx,
+        max_payload_size,
+        buf,
+        len,
+      );
+    }
+
+    // Listen until we reach the timeout
+    let finish = <N::Runtime as RuntimeLite>::sleep(timeout);
+    futures::pin_mut!(finish);
+
+    // the incomplete entries, with the instant of their next follow-up and the interval
+    // to the one after
+    let mut follow_ups = HashMap::<SmolStr, (Instant, Duration)>::new();
+    loop {
+      let deadline = self
+        .v4
+        .iter()
+        .chain(self.v6.iter())
+        .filter_map(Client::poll_refresh)
+        .chain(follow_ups.values().map(|(at, _)| *at))
+        .min();
+      let timer = async {
+        match deadline {
+          Some(deadline) => {
+            <N::Runtime as RuntimeLite>::sleep(deadline.saturating_duration_since(Instant::now()))
+              .await
+          }
+          None => futures::future::pending().await,
+        }
+      };
+
+      futures::select! {
+        _ = (&mut finish).fuse() => {
+          break Ok(());
+        },
+        _ = timer.fuse() => {
+          for client in self.v4.iter().chain(self.v6.iter()) {
+            client.refresh().await;
+          }
+
+          let now = Instant::now();
+          let due = follow_ups
+            .iter()
+            .filter(|(_, (at, _))| *at <= now)
+            .map(|(name, _)| name.clone())
+            .collect::<SmallVec<_>>();
+          for name in due {
+            if self.follow_up(&inprogress, &name).await {
+              if let Some((at, interval)) = follow_ups.get_mut(&name) {
+                *at = now + *interval;
+                *interval *= 2;
+              }
+            } else {
+              follow_ups.remove(&name);
+            }
+          }
+        },
+        res = msg_rx.recv().fuse() => {
+          match res {
+            Ok(entry) => {
+              match entry {
+                Incoming::Entry(entry) => {
+                  if let Err(e) = tx.send(entry).await {
+                    tracing::error!(err=%e, "mdns client: failed to send service entry");
+                  }
+                },
+                // the first follow-up is sent right away
+                Incoming::Incomplete(name) => {
+                  follow_ups.insert(name, (Instant::now(), FOLLOW_UP_INTERVAL));
+                }
+                Incoming::Changed => {}
+              }
+            }
+            Err(e) => {
+              tracing::error!(err=%e, "mdns client: failed to receive packet");
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Asks for the records missing from the incomplete entry `name`, see
+  /// [`ServiceEntryBuilder::missing`].
+  ///
+  /// Returns `false` if no record is missing any more.
+  async fn follow_up(&self, inprogress: &Mutex<InprogressCache>, name: &str) -> bool {
+    let missing = match inprogress.lock().entries.get(name) {
+      Some(builder) => builder.missing(),
+      None => return false,
+    };
+    if missing.is_empty() {
+      return false;
+    }
+
+    let questions = missing
+      .iter()
+      .map(|(name, ty)| {
+        tracing::trace!(name=%name, ty=?ty, "mdns client: asking for a missing record");
+        Question::new(name.as_str(), *ty, 1)
+      })
+      .collect::<SmallVec<_>>();
+    for client in self.v4.iter().chain(self.v6.iter()) {
+      client.send(&questions).await;
+    }
+    true
+  }
+
+  async fn query_records_in(
+    self,
+    questions: SmallVec<(SmolStr, ResourceType, u16)>,
+    timeout: Duration,
+    tx: Sender<RecordEntry>,
+    shutdown_rx: Receiver<()>,
+    max_payload_size: usize,
+  ) -> io::Result<()> {
+    // Start listening for response packets
+    let (msg_tx, msg_rx) = async_channel::bounded::<Incoming>(32);
+
+    let mut qs = questions
+      .iter()
+      .map(|(name, ty, class)| Question::new(name.as_str(), *ty, *class))
+      .collect::<SmallVec<_>>();
+    let msg = Message::new(0, Flags::new(), &mut qs, &mut [], &mut [], &mut []);
+    let space_needed = msg.space_needed();
+    let mut buf = Buffer::zerod(space_needed);
+    let len = msg
+      .write(&mut buf)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let inprogress = Arc::new(Mutex::new(InprogressCache::new(
+      None,
+      Completeness::RequireTxt,
+    )));
+    for client in self.v4.iter().chain(self.v6.iter()) {
+      client.query(
+        inprogress.clone(),
+        msg_tx.clone(),
+        shutdown_rx.clone(),
+        max_payload_size,
+        buf.clone(),
+        len,
+      );
+    }
+
+    // Listen until we reach the timeout
+    let finish = <N::Runtime as RuntimeLite>::sleep(timeout);
+    futures::pin_mut!(finish);
+
+    let mut reported = HashSet::<(SmolStr, ResourceType, Arc<[u8]>)>::new();
+    loop {
+      futures::select! {
+        _ = (&mut finish).fuse() => {
+          break Ok(());
+        },
+        res = msg_rx.recv().fuse() => {
+          match res {
+            // only the records are reported, not the assembled service entries
+            Ok(Incoming::Changed) => {
+              for record in self.answers(&questions, &mut reported) {
+                if tx.send(record).await.is_err() {
+                  return Ok(());
+                }
+              }
+            }
+            Ok(_) => {}
+            Err(e) => {
+              tracing::error!(err=%e, "mdns client: failed to receive packet");
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Returns the records cached by the clients which answer `questions`, leaving out the
+  /// records already `reported`.
+  fn answers(
+    &self,
+    questions: &[(SmolStr, ResourceType, u16)],
+    reported: &mut HashSet<(SmolStr, ResourceType, Arc<[u8]>)>,
+  ) -> SmallVec<RecordEntry> {
+    let mut records = SmallVec::new();
+    for (from, link) in self.links().iter() {
+      for entry in link.records.iter() {
+        let name = entry.name().to_smolstr();
+        let answers = questions.iter().any(|(qname, ty, class)| {
+          (*ty == ResourceType::Wildcard || *ty == entry.ty())
+            && class & !(1 << 15) == entry.class()
+            && qname
+              .trim_end_matches('.')
+              .eq_ignore_ascii_case(name.trim_end_matches('.'))
+        });
+        if !answers {
+          continue;
+        }
 
-/// Similar to [`query`], however it uses all the default parameters
-pub async fn lookup<N>(service: Label<'_>, tx: Sender<ServiceEntry>) -> io::Result<()>
-where
-  N: Net,
-{
-  query::<N>(QueryParam::new(service), tx).await
-}
+        let key = (
+          name.to_ascii_lowercase().into(),
+          entry.ty(),
+          Arc::from(entry.data()),
+        );
+        if reported.contains(&key) {
+          continue;
+        }
 
-/// Provides a query interface that can be used to
-/// search for service providers using mDNS
-struct Clients<N: Net> {
-  v4: Option<Client<N>>,
-  v6: Option<Client<N>>,
-}
+        if let Some(record) = RecordEntry::new(*from, &entry.record()) {
+          reported.insert(key);
+          records.push(record);
+        }
+      }
+    }
+    records
+  }
 
-impl<N: Net> Clients<N> {
-  async fn query_in(
+  async fn browse_in(
     self,
     service: SmolStr,
     want_unicast_response: bool,
-    timeout: Duration,
-    tx: Sender<ServiceEntry>,
+    completeness: Completeness,
+    tx: Sender<io::Result<BrowseEvent>>,
     shutdown_rx: Receiver<()>,
     max_payload_size: usize,
   ) -> io::Result<()> {
     // Start listening for response packets
-    let (msg_tx, msg_rx) = async_channel::bounded::<Either<ServiceEntry, SmolStr>>(32);
+    let (msg_tx, msg_rx) = async_channel::bounded::<Incoming>(32);
 
     let q = Endpoint::prepare_question(Label::from(service.as_str()), want_unicast_response);
 
@@ -284,88 +1343,205 @@ impl<N: Net> Clients<N> {
       .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
     // Map the in-progress responses
-    let inprogress = Arc::new(Mutex::new(InprogressCache::new()));
-
-    if let Some(ref client) = self.v4 {
-      let tx = msg_tx.clone();
-      let shutdown_rx = shutdown_rx.clone();
-      let buf = buf.clone();
-      client.query(
-        inprogress.clone(),
-        tx,
-        shutdown_rx,
-        max_payload_size,
-        buf,
-        len,
-      );
-    }
-
-    if let Some(ref client) = self.v6 {
-      let tx = msg_tx.clone();
-      let shutdown_rx = shutdown_rx.clone();
+    let inprogress = Arc::new(Mutex::new(InprogressCache::new(
+      Some(service.clone()),
+      completeness,
+    )));
+
+    // the first query is sent right away, along with the start of the listeners
+    let mut schedule = QuerySchedule::new(Instant::now());
+    schedule.handle_timeout(Instant::now());
+    for client in self.v4.iter().chain(self.v6.iter()) {
       client.query(
         inprogress.clone(),
-        tx,
-        shutdown_rx,
+        msg_tx.clone(),
+        shutdown_rx.clone(),
         max_payload_size,
-        buf,
+        buf.clone(),
         len,
       );
     }
 
-    // Listen until we reach the timeout
-    let finish = <N::Runtime as RuntimeLite>::sleep(timeout);
-    futures::pin_mut!(finish);
-
+    let mut known = HashMap::<SmolStr, ServiceEntry>::new();
     loop {
+      let deadline = self
+        .v4
+        .iter()
+        .chain(self.v6.iter())
+        .filter_map(Client::poll_refresh)
+        .fold(schedule.poll_timeout(), Instant::min);
+      let timeout =
+        <N::Runtime as RuntimeLite>::sleep(deadline.saturating_duration_since(Instant::now()));
+
       futures::select! {
-        _ = (&mut finish).fuse() => {
-          break Ok(());
+        _ = shutdown_rx.recv().fuse() => return Ok(()),
+        _ = timeout.fuse() => {
+          if schedule.handle_timeout(Instant::now()) {
+            for client in self.v4.iter().chain(self.v6.iter()) {
+              client.requery(&q).await;
+            }
+          }
+
+          for client in self.v4.iter().chain(self.v6.iter()) {
+            client.refresh().await;
+          }
         },
         res = msg_rx.recv().fuse() => {
           match res {
-            Ok(entry) => {
-              match entry {
-                Either::Left(entry) => {
-                  if let Err(e) = tx.send(entry).await {
-                    tracing::error!(err=%e, "mdns client: failed to send service entry");
-                  }
-                },
-                Either::Right(name) => {
-                  let q = Endpoint::prepare_question(Label::from(name.as_str()), false);
-                  let mut qs = [q];
-                  let msg = Message::new(0, Flags::new(), &mut qs, &mut [], &mut [], &mut []);
-                  let space_needed = msg.space_needed();
-                  let mut buf = Buffer::zerod(space_needed);
-                  let len = match msg.write(&mut buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)) {
-                    Ok(len) => len,
-                    Err(e) => {
-                      tracing::error!(err=%e, "mdns client: failed to encode message");
-
-                      continue;
-                    }
-                  };
-
-                  if let Some(ref client) = self.v4 {
-                    if let Some((_, ref conn)) = client.unicast_conn {
-                      conn.send_to(&buf[..len], (IPV4_MDNS, MDNS_PORT)).await?;
-                    }
-                  }
-
-                  if let Some(ref client) = self.v6 {
-                    if let Some((_, ref conn)) = client.unicast_conn {
-                      conn.send_to(&buf[..len], (IPV6_MDNS, MDNS_PORT)).await?;
-                    }
-                  }
-                }
-              }
+            Ok(Incoming::Incomplete(name)) => {
+              self.follow_up(&inprogress, &name).await;
             }
+            Ok(Incoming::Entry(_)) | Ok(Incoming::Changed) => {}
             Err(e) => {
               tracing::error!(err=%e, "mdns client: failed to receive packet");
             }
           }
         }
       }
+
+      let mut current = self.cached_entries(&service, completeness);
+      let mut events = SmallVec::<BrowseEvent>::new();
+      known.retain(|name, entry| match current.remove(name) {
+        // a refresh of the records, only the expiry or the responder changed
+        Some(new) if !new.changed(entry) => {
+          *entry = new;
+          true
+        }
+        Some(new) => {
+          *entry = new.clone();
+          events.push(BrowseEvent::Updated(new));
+          true
+        }
+        None => {
+          events.push(BrowseEvent::Removed(entry.clone()));
+          false
+        }
+      });
+      for (name, entry) in current {
+        events.push(BrowseEvent::Added(entry.clone()));
+        known.insert(name, entry);
+      }
+
+      for event in events {
+        // the browser is dropped
+        if tx.send(Ok(event)).await.is_err() {
+          return Ok(());
+        }
+      }
+    }
+  }
+
+  /// Locks the records cached by the clients, along with the local address of each
+  /// client.
+  fn links(&self) -> SmallVec<(SocketAddr, MutexGuard<'_, Link>)> {
+    self
+      .v4
+      .iter()
+      .chain(self.v6.iter())
+      .filter_map(|client| {
+        let (from, _) = client.unicast_conn.as_ref()?;
+        Some((*from, client.link.lock()))
+      })
+      .collect()
+  }
+
+  /// Returns the entries of the instances of `service` which have the records required by
+  /// `completeness`, assembled from the records cached by the clients.
+  fn cached_entries(
+    &self,
+    service: &str,
+    completeness: Completeness,
+  ) -> HashMap<SmolStr, ServiceEntry> {
+    let links = self.links();
+    let mut entries = HashMap::new();
+    for (from, link) in links.iter() {
+      for ptr in link.records.get(Label::from(service), ResourceType::Ptr, 1) {
+        let Ok(Response::Ptr(instance)) = Endpoint::parse(*from, &[], &ptr.record()) else {
+          continue;
+        };
+
+        let name = instance.to_smolstr();
+        if entries.contains_key(&name) {
+          continue;
+        }
+
+        let builder = assemble(&links, name.clone());
+        if builder.complete(completeness) {
+          entries.insert(name, builder.finalize());
+        }
+      }
+    }
+    entries
+  }
+
+  async fn resolve_in(
+    &self,
+    instance: SmolStr,
+    want_unicast_response: bool,
+    shutdown_rx: Receiver<()>,
+    max_payload_size: usize,
+  ) -> io::Result<ServiceEntry> {
+    // Start listening for response packets
+    let (msg_tx, msg_rx) = async_channel::bounded::<Incoming>(32);
+
+    let class = if want_unicast_response {
+      1 | (1 << 15)
+    } else {
+      1
+    };
+    let mut qs = [
+      Question::new(instance.as_str(), ResourceType::Srv, class),
+      Question::new(instance.as_str(), ResourceType::Txt, class),
+    ];
+    let msg = Message::new(0, Flags::new(), &mut qs, &mut [], &mut [], &mut []);
+    let space_needed = msg.space_needed();
+    let mut buf = Buffer::zerod(space_needed);
+    let len = msg
+      .write(&mut buf)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    // Map the in-progress responses
+    let inprogress = Arc::new(Mutex::new(InprogressCache::new(
+      None,
+      Completeness::RequireTxt,
+    )));
+    for client in self.v4.iter().chain(self.v6.iter()) {
+      client.query(
+        inprogress.clone(),
+        msg_tx.clone(),
+        shutdown_rx.clone(),
+        max_payload_size,
+        buf.clone(),
+        len,
+      );
+    }
+
+    let mut asked = SmolStr::default();
+    loop {
+      let host = {
+        let builder = assemble(&self.links(), instance.clone());
+        if builder.complete(Completeness::RequireTxt) {
+          return Ok(builder.finalize());
+        }
+
+        builder.host
+      };
+
+      // the addresses of the target host were not in the Additional section
+      if !host.is_empty() && host != asked {
+        let questions = [
+          Question::new(host.as_str(), ResourceType::A, class),
+          Question::new(host.as_str(), ResourceType::AAAA, class),
+        ];
+        for client in self.v4.iter().chain(self.v6.iter()) {
+          client.send(&questions).await;
+        }
+        asked = host;
+      }
+
+      if let Err(e) = msg_rx.recv().await {
+        tracing::error!(err=%e, "mdns client: failed to receive packet");
+      }
     }
   }
 
@@ -479,6 +1655,13 @@ impl<N: Net> Clients<N> {
       Some(Client {
         unicast_conn: uconn4,
         multicast_conn: mconn4,
+        link: Arc::new(Mutex::new(Link {
+          interface: LinkInterface {
+            ipv4: ipv4_interface,
+            ipv6: None,
+          },
+          ..Default::default()
+        })),
       })
     } else {
       None
@@ -488,6 +1671,13 @@ impl<N: Net> Clients<N> {
       Some(Client {
         unicast_conn: uconn6,
         multicast_conn: mconn6,
+        link: Arc::new(Mutex::new(Link {
+          interface: LinkInterface {
+            ipv4: None,
+            ipv6: ipv6_interface,
+          },
+          ..Default::default()
+        })),
       })
     } else {
       None
@@ -503,13 +1693,203 @@ impl<N: Net> Clients<N> {
 struct Client<N: Net> {
   unicast_conn: Option<(SocketAddr, Arc<N::UdpSocket>)>,
   multicast_conn: Option<(SocketAddr, Arc<N::UdpSocket>)>,
+  link: Arc<Mutex<Link>>,
+}
+
+/// What a client learns from the packets received on the link.
+#[derive(Default)]
+struct Link {
+  /// The interface the sockets of the client are bound to.
+  interface: LinkInterface,
+  /// The questions and answers of the other hosts, see RFC 6762, sections 7.3 and 7.4.
+  suppression: SlabSuppression<Instant>,
+  /// The records received, see RFC 6762, section 5.2.
+  records: SlabCache<Instant>,
+  /// The responder which last sent records of each name, keyed by the lowercase name,
+  /// as long as records of the name are cached.
+  sources: HashMap<SmolStr, SocketAddr>,
+}
+
+/// The interface the sockets of a client are bound to, if any, see
+/// [`QueryParam::with_ipv4_interface`] and [`QueryParam::with_ipv6_interface`].
+#[derive(Debug, Default, Clone, Copy)]
+struct LinkInterface {
+  /// The address of the interface of an IPv4 client.
+  ipv4: Option<Ipv4Addr>,
+  /// The index of the interface of an IPv6 client.
+  ipv6: Option<u32>,
+}
+
+impl Link {
+  /// Returns the responder which last sent records of `name`.
+  fn source(&self, name: &str) -> Option<SocketAddr> {
+    self
+      .sources
+      .get(name.to_ascii_lowercase().as_str())
+      .copied()
+  }
 }
 
 impl<N: Net> Client<N> {
+  /// Returns `true` if another host has just asked `question`, our own query is then
+  /// treated as having been sent, see RFC 6762, section 7.3.
+  fn is_suppressed(&self, question: &Question<'_>) -> bool {
+    let suppressed = self
+      .link
+      .lock()
+      .suppression
+      .is_question_suppressed(question, Instant::now());
+    if suppressed {
+      tracing::debug!(name=%question.name(), "mdns client: another host has just asked the question");
+    }
+    suppressed
+  }
+
+  /// Returns the instant at which [`Client::refresh`] should be called next.
+  fn poll_refresh(&self) -> Option<Instant> {
+    self.link.lock().records.poll_timeout()
+  }
+
+  /// Forgets the expired records, and asks again for the records about to expire, see
+  /// RFC 6762, section 5.2.
+  async fn refresh(&self) {
+    let questions = {
+      let mut link = self.link.lock();
+      let mut questions = SmallVec::<(SmolStr, ResourceType, u16)>::new();
+      while let Some(event) = link.records.handle_timeout(Instant::now()) {
+        match event {
+          CacheEvent::Refresh(entry) => {
+            let question = (entry.name().to_smolstr(), entry.ty(), entry.class());
+            if !questions.contains(&question) {
+              questions.push(question);
+            }
+          }
+          CacheEvent::Expired(entry) => {
+            tracing::debug!(name=%entry.name(), ty=?entry.ty(), "mdns client: record expired");
+            let name = entry.name().to_smolstr().to_ascii_lowercase();
+            let cached = link
+              .records
+              .iter()
+              .any(|entry| entry.name().to_smolstr().eq_ignore_ascii_case(&name));
+            if !cached {
+              link.sources.remove(name.as_str());
+            }
+          }
+        }
+      }
+      questions
+    };
+
+    let questions = questions
+      .iter()
+      .map(|(name, ty, class)| {
+        tracing::trace!(name=%name, ty=?ty, "mdns client: refreshing record");
+        Question::new(name.as_str(), *ty, *class)
+      })
+      .collect::<SmallVec<_>>();
+    self.send(&questions).await;
+  }
+
+  /// Sends `questions` to the multicast group, leaving out the questions another host has
+  /// just asked, see RFC 6762, section 7.3.
+  async fn send(&self, questions: &[Question<'_>]) {
+    let Some((addr, conn)) = &self.unicast_conn else {
+      return;
+    };
+    let mut questions = questions
+      .iter()
+      .filter(|question| !self.is_suppressed(question))
+      .copied()
+      .collect::<SmallVec<_>>();
+    if questions.is_empty() {
+      return;
+    }
+
+    let msg = Message::new(0, Flags::new(), &mut questions, &mut [], &mut [], &mut []);
+    let mut buf = vec![0; msg.space_needed()];
+    let res = msg
+      .write(&mut buf)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    match res {
+      Ok(len) => {
+        let target: IpAddr = match addr.ip().is_ipv4() {
+          true => IPV4_MDNS.into(),
+          false => IPV6_MDNS.into(),
+        };
+        if let Err(e) = conn.send_to(&buf[..len], (target, MDNS_PORT)).await {
+          tracing::error!(err=%e, "mdns client: failed to send query");
+        }
+      }
+      Err(e) => tracing::error!(err=%e, "mdns client: failed to encode message"),
+    }
+  }
+
+  /// Asks `question` again, with the records cached by the client in the Answer section,
+  /// see RFC 6762, section 7.1.
+  async fn requery(&self, question: &Question<'_>) {
+    let Some((addr, conn)) = &self.unicast_conn else {
+      return;
+    };
+    if self.is_suppressed(question) {
+      return;
+    }
+
+    let res = {
+      let link = self.link.lock();
+      let now = Instant::now();
+      // a known answer is only included if more than half of its TTL remains
+      let mut known = link
+        .records
+        .get(
+          question.name(),
+          question.ty(),
+          question.class() & !(1 << 15),
+        )
+        .filter_map(|entry| {
+          let remaining = entry.expires().saturating_duration_since(now);
+          (remaining.as_secs() * 2 >= entry.ttl() as u64).then(|| {
+            let record = entry.record();
+            ResourceRecord::new(
+              record.name(),
+              record.ty(),
+              record.class(),
+              remaining.as_secs() as u32,
+              record.data(),
+            )
+          })
+        })
+        .collect::<SmallVec<_>>();
+      let mut qs = [*question];
+      let msg = Message::new(0, Flags::new(), &mut qs, &mut known, &mut [], &mut []);
+      let mut buf = vec![0; msg.space_needed()];
+      msg
+        .write(&mut buf)
+        .map(|len| {
+          buf.truncate(len);
+          buf
+        })
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    };
+
+    match res {
+      Ok(buf) => {
+        let target: IpAddr = match addr.ip().is_ipv4() {
+          true => IPV4_MDNS.into(),
+          false => IPV6_MDNS.into(),
+        };
+        tracing::trace!(name=%question.name(), "mdns client: querying again");
+        if let Err(e) = conn.send_to(&buf, (target, MDNS_PORT)).await {
+          tracing::error!(err=%e, "mdns client: failed to send query");
+        }
+      }
+      Err(e) => tracing::error!(err=%e, "mdns client: failed to encode message"),
+    }
+  }
+
   fn query(
     &self,
     cache: Arc<Mutex<InprogressCache>>,
-    tx: Sender<Either<ServiceEntry, SmolStr>>,
+    tx: Sender<Incoming>,
     shutdown_rx: Receiver<()>,
     max_payload_size: usize,
     buf: Buffer,
@@ -519,6 +1899,7 @@ impl<N: Net> Client<N> {
       N::Runtime::spawn_detach(Self::listen(
         *addr,
         conn.clone(),
+        self.link.clone(),
         cache.clone(),
         tx.clone(),
         shutdown_rx.clone(),
@@ -531,6 +1912,7 @@ impl<N: Net> Client<N> {
       let addr = *addr;
       let tx = tx.clone();
       let shutdown_rx = shutdown_rx.clone();
+      let link = self.link.clone();
 
       N::Runtime::spawn_detach(async move {
         tracing::trace!(from=%addr, data=?&buf[..len], "mdns client: sending query by unicast");
@@ -543,7 +1925,16 @@ impl<N: Net> Client<N> {
           tracing::error!(err=%e, "mdns client: failed to send query by unicast");
         }
 
-        Self::listen(addr, conn.clone(), cache, tx, shutdown_rx, max_payload_size).await
+        Self::listen(
+          addr,
+          conn.clone(),
+          link,
+          cache,
+          tx,
+          shutdown_rx,
+          max_payload_size,
+        )
+        .await
       });
     }
   }
@@ -551,8 +1942,9 @@ impl<N: Net> Client<N> {
   async fn listen(
     local_addr: SocketAddr,
     conn: Arc<N::UdpSocket>,
+    link: Arc<Mutex<Link>>,
     cache: Arc<Mutex<InprogressCache>>,
-    tx: Sender<Either<ServiceEntry, SmolStr>>,
+    tx: Sender<Incoming>,
     shutdown_rx: Receiver<()>,
     max_payload_size: usize,
   ) {
@@ -564,6 +1956,7 @@ impl<N: Net> Client<N> {
       tracing::debug!(local_addr=%local_addr, "mdns client: stopping to listen response");
     });
 
+    let interface = link.lock().interface;
     loop {
       futures::select! {
         _ = shutdown_rx.recv().fuse() => return,
@@ -621,38 +2014,67 @@ impl<N: Net> Client<N> {
             }
           };
 
-          for record in Endpoint::recv(src, &msg) {
+          let mut changed = false;
+          {
+            let mut link = link.lock();
+            let now = Instant::now();
+            // a query from another port than 5353 is answered by unicast only
+            if src.port() == MDNS_PORT {
+              if let Err(e) = link.suppression.observe(data, &msg, now) {
+                tracing::error!(err=%e, "mdns client: failed to observe message");
+              }
+            }
+
+            if msg.flags().qr() == MessageType::Reply {
+              for record in msg.answers().iter().chain(msg.additional()) {
+                match link.records.insert(data, record, now) {
+                  Ok(added) => changed |= added,
+                  Err(e) => tracing::error!(err=%e, "mdns client: failed to cache record"),
+                }
+                link.sources.insert(record.name().to_smolstr().to_ascii_lowercase().into(), src);
+              }
+            }
+          }
+
+          if changed && tx.send(Incoming::Changed).await.is_err() {
+            return;
+          }
+
+          for record in Endpoint::recv(src, data, &msg) {
             match record {
               Err(e) => {
                 tracing::error!(err=%e, "mdns client: failed to parse record");
               }
+              // a goodbye, the record is no longer valid, see RFC 6762, section 10.1
+              Ok(record) if record.ttl() == 0 => {}
               Ok(record) => {
-                match record {
+                let expires = Instant::now() + Duration::from_secs(record.ttl() as u64);
+                let owner = record.name();
+                match record.into_response() {
                   Response::A { name, addr } => {
                     let name = name.to_smolstr();
-                    cache.lock().entry(name, |entry| {
-                      entry.ipv4 = Some(addr);
-                    });
+                    cache.lock().host(name, |host| host.add_ipv4(addr, expires));
                   },
                   Response::AAAA { name, addr, zone } => {
                     let name = name.to_smolstr();
-                    cache.lock().entry(name, |entry| {
-                      entry.ipv6 = Some(addr);
-                      entry.zone = zone;
-                    });
+                    cache.lock().host(name, |host| host.add_ipv6(addr, zone, expires));
                   },
                   Response::Ptr(name) => {
-                    cache.lock().entry(name.to_smolstr(), |_| {});
+                    let mut cache = cache.lock();
+                    if cache.follows(&owner) {
+                      cache.entry(name.to_smolstr(), |entry| entry.pointed = true);
+                    }
                   },
                   Response::Txt { name, txt } => {
                     let name = name.to_smolstr();
                     match txt.strings().map(|res| {
-                      res.map(|s| s.to_smolstr())
+                      res.map(|s| Arc::from(s.as_bytes()))
                     }).collect::<Result<Arc<[_]>, _>>()
                     {
                       Ok(txt) => {
                         cache.lock().entry(name, |entry| {
                           entry.txts = Some(txt);
+                          entry.expire(expires);
                         });
                       },
                       Err(e) => {
@@ -661,20 +2083,18 @@ impl<N: Net> Client<N> {
                     }
                   },
                   Response::Srv { name, srv } => {
-                    let target = srv.target();
-                    let mut cache = cache.lock();
-                    let (name, target) = if target != name {
-                      cache.create_alias(&name, &target)
-                    } else {
-                      (name.to_smolstr(), target.to_smolstr())
-                    };
-
-                    // Update the entry
-                    cache.entry(name, |entry| {
-                      entry.host = target;
+                    let name = name.to_smolstr();
+                    cache.lock().entry(name, |entry| {
+                      entry.host = srv.target().to_smolstr();
                       entry.port = srv.port();
+                      entry.priority = srv.priority();
+                      entry.weight = srv.weight();
+                      entry.received(Some(src), &interface);
+                      entry.expire(expires);
                     });
                   },
+                  // not part of a service entry, only reported by `query_records`
+                  Response::Other { .. } => {},
                 }
               }
             }
@@ -682,29 +2102,49 @@ impl<N: Net> Client<N> {
 
           let entries = {
             let mut cache = cache.lock();
-            cache.entries.iter_mut().filter_map(|(name, ent)| {
-              // Check if this entry is complete
-              if ent.complete() {
+            let InprogressCache { entries, hosts, service, completeness } = &mut *cache;
+            let mut incoming = SmallVec::new();
+            for (name, ent) in entries.iter_mut() {
+              // the instances of other services, e.g. heard in their announcements
+              if service.is_some() && !ent.pointed {
+                continue;
+              }
+
+              // the addresses are the ones of the target host of the SRV record
+              if let Some(host) = hosts.get(&InprogressCache::key(&ent.host)) {
+                ent.addrs = host.clone();
+              }
+
+              // the missing records are asked for once, the querier then follows up
+              if !ent.queried {
                 ent.queried = true;
-                if ent.sent {
-                  return None;
-                }
-                ent.sent = true;
-                Some(Either::Left(ent.finalize()))
-              } else {
-                if ent.queried {
-                  return None;
+                if !ent.missing().is_empty() {
+                  incoming.push(Incoming::Incomplete(name.clone()));
                 }
+              }
 
-                ent.queried = true;
-                Some(Either::Right(name.clone()))
+              if !ent.complete(*completeness) {
+                continue;
+              }
+
+              // a partial entry is reported again every time it changes
+              let entry = ent.finalize();
+              let report = match ent.reported {
+                None => true,
+                Some(ref last) => *completeness == Completeness::Partial && entry.changed(last),
+              };
+              if report {
+                ent.reported = Some(entry.clone());
+                incoming.push(Incoming::Entry(entry));
               }
-            }).collect::<SmallVec<_>>()
+            }
+            incoming
           };
 
           for ent in entries {
-            if let Err(e) = tx.send(ent).await {
-              tracing::error!(err=%e, "mdns client: failed to send service entry");
+            // the querier is done
+            if tx.send(ent).await.is_err() {
+              return;
             }
           }
         }
@@ -713,19 +2153,141 @@ impl<N: Net> Client<N> {
   }
 }
 
+/// Returns the data of the records with the given name and type, among the records
+/// cached by the clients, along with their expiry, the responder which sent them and the
+/// interface they were received on, the most recently received first.
+fn cached<'a>(
+  links: &'a [(SocketAddr, MutexGuard<'_, Link>)],
+  name: &str,
+  ty: ResourceType,
+) -> SmallVec<(Response<'a>, Instant, Option<SocketAddr>, LinkInterface)> {
+  let mut entries = links
+    .iter()
+    .flat_map(|(local, link)| {
+      let source = link.source(name);
+      let interface = link.interface;
+      link
+        .records
+        .get(Label::from(name), ty, 1)
+        .map(move |entry| (*local, source, interface, entry))
+    })
+    .collect::<SmallVec<_>>();
+  entries.sort_by_key(|(_, _, _, entry)| core::cmp::Reverse(entry.received()));
+
+  entries
+    .into_iter()
+    .filter_map(|(local, source, interface, entry)| {
+      // the scope id of a link-local address is the one of the responder
+      match Endpoint::parse(source.unwrap_or(local), &[], &entry.record()) {
+        Ok(response) => Some((response, entry.expires(), source, interface)),
+        Err(e) => {
+          tracing::error!(err=%e, "mdns client: failed to parse record");
+          None
+        }
+      }
+    })
+    .collect()
+}
+
+/// Assembles the entry of `instance` from the records cached by the clients.
+fn assemble(
+  links: &[(SocketAddr, MutexGuard<'_, Link>)],
+  instance: SmolStr,
+) -> ServiceEntryBuilder {
+  let mut builder = ServiceEntryBuilder::default().with_name(instance);
+  let srv = cached(links, &builder.name, ResourceType::Srv)
+    .into_iter()
+    .next();
+  if let Some((Response::Srv { srv, .. }, expires, source, interface)) = srv {
+    builder.host = srv.target().to_smolstr();
+    builder.port = srv.port();
+    builder.priority = srv.priority();
+    builder.weight = srv.weight();
+    builder.received(source, &interface);
+    builder.expire(expires);
+  }
+  let txt = cached(links, &builder.name, ResourceType::Txt)
+    .into_iter()
+    .next();
+  if let Some((Response::Txt { txt, .. }, expires, ..)) = txt {
+    match txt
+      .strings()
+      .map(|res| res.map(|s| Arc::from(s.as_bytes())))
+      .collect::<Result<Arc<[_]>, _>>()
+    {
+      Ok(txt) => {
+        builder.txts = Some(txt);
+        builder.expire(expires);
+      }
+      Err(e) => tracing::error!(err=%e, "mdns client: failed to parse txt record"),
+    }
+  }
+  if builder.host.is_empty() {
+    return builder;
+  }
+
+  for (response, expires, ..) in cached(links, &builder.host, ResourceType::A) {
+    if let Response::A { addr, .. } = response {
+      builder.addrs.add_ipv4(addr, expires);
+    }
+  }
+  for (response, expires, ..) in cached(links, &builder.host, ResourceType::AAAA) {
+    if let Response::AAAA { addr, zone, .. } = response {
+      builder.addrs.add_ipv6(addr, zone, expires);
+    }
+  }
+  builder
+}
+
+/// What the listeners of a client tell the querier.
+enum Incoming {
+  /// An entry is complete.
+  Entry(ServiceEntry),
+  /// An entry is incomplete, its missing records should be asked for.
+  Incomplete(SmolStr),
+  /// New records were cached.
+  Changed,
+}
+
 struct InprogressCache {
+  /// The entries, by the lowercase names of the instances, see [`InprogressCache::key`].
   entries: HashMap<SmolStr, ServiceEntryBuilder>,
-  aliases: HashMap<SmolStr, SmolStr>,
+  /// The addresses of the hosts, an address record is owned by a host, which may be the
+  /// target of the SRV records of several instances. By the lowercase names of the hosts.
+  hosts: HashMap<SmolStr, HostRecords>,
+  /// The name whose `PTR` records are followed, all of them are if it is `None`.
+  service: Option<SmolStr>,
+  /// The records an entry must have before it is reported.
+  completeness: Completeness,
 }
 
 impl InprogressCache {
-  fn new() -> Self {
+  fn new(service: Option<SmolStr>, completeness: Completeness) -> Self {
     Self {
       entries: HashMap::new(),
-      aliases: HashMap::new(),
+      hosts: HashMap::new(),
+      service,
+      completeness,
     }
   }
 
+  /// Returns `true` if the instances pointed to by the `PTR` records of `name` are
+  /// followed, e.g. the `PTR` records of the service type are not when querying one of
+  /// its subtypes.
+  fn follows(&self, name: &Label<'_>) -> bool {
+    self
+      .service
+      .as_ref()
+      .is_none_or(|service| label_eq(&Label::from(service.as_str()), name))
+  }
+
+  /// Returns the key of `name` in the maps of the cache, as DNS names are case
+  /// insensitive, e.g. the target `Host.local` of a SRV record owns the address records
+  /// of `host.local`.
+  fn key(name: &str) -> SmolStr {
+    name.to_ascii_lowercase().into()
+  }
+
   /// Get a mutable reference to an entry, creating it if it doesn't exist.
   ///
   /// The entry is then passed to the closure for modification.
@@ -733,30 +2295,56 @@ impl InprogressCache {
   where
     F: FnOnce(&mut ServiceEntryBuilder),
   {
-    let name = match self.aliases.get(&name) {
-      Some(target) => target,
-      None => &name,
-    };
-
-    match self.entries.entry(name.clone()) {
+    match self.entries.entry(Self::key(&name)) {
       Entry::Occupied(occupied_entry) => {
         op(occupied_entry.into_mut());
       }
       Entry::Vacant(vacant_entry) => {
-        let mut builder = ServiceEntryBuilder::default().with_name(vacant_entry.key().clone());
+        let mut builder = ServiceEntryBuilder::default().with_name(name);
         op(&mut builder);
         vacant_entry.insert(builder);
       }
     }
   }
 
-  // Create an alias from one name to another
-  fn create_alias(&mut self, from: &Label<'_>, to: &Label<'_>) -> (SmolStr, SmolStr) {
-    let to = to.to_smolstr();
-    let from = from.to_smolstr();
+  /// Get a mutable reference to the addresses of a host, creating them if they don't
+  /// exist.
+  fn host<F>(&mut self, name: SmolStr, op: F)
+  where
+    F: FnOnce(&mut HostRecords),
+  {
+    op(self.hosts.entry(Self::key(&name)).or_default());
+  }
+}
+
+/// The addresses of a host.
+#[derive(Debug, Default, Clone)]
+struct HostRecords {
+  ipv4s: SmallVec<Ipv4Addr>,
+  ipv6s: SmallVec<(Ipv6Addr, Option<u32>)>,
+  /// The expiry of the first of the address records to expire.
+  expires: Option<Instant>,
+}
+
+impl HostRecords {
+  #[inline]
+  fn has_addr(&self) -> bool {
+    !self.ipv4s.is_empty() || !self.ipv6s.is_empty()
+  }
+
+  /// Adds an IPv4 address, from a record expiring at `expires`.
+  fn add_ipv4(&mut self, addr: Ipv4Addr, expires: Instant) {
+    if !self.ipv4s.contains(&addr) {
+      self.ipv4s.push(addr);
+    }
+    self.expires = Some(earliest(self.expires, expires));
+  }
 
-    // Create the alias
-    self.aliases.insert(to.clone(), from.clone());
-    (from, to)
+  /// Adds an IPv6 address, from a record expiring at `expires`.
+  fn add_ipv6(&mut self, addr: Ipv6Addr, zone: Option<u32>, expires: Instant) {
+    if !self.ipv6s.contains(&(addr, zone)) {
+      self.ipv6s.push((addr, zone));
+    }
+    self.expires = Some(earliest(self.expires, expires));
   }
 }
@@ -1,5 +1,5 @@
 use core::net::SocketAddr;
-use std::{io, ops::ControlFlow};
+use std::{io, time::Instant};
 
 use agnostic_net::{
   Net, UdpSocket,
@@ -11,15 +11,23 @@ use futures::{FutureExt, StreamExt as _, stream::FuturesUnordered};
 use iprobe::{ipv4, ipv6};
 use mdns_proto::{
   error::{BufferType, ProtoError},
-  proto::{Message, Question, ResourceRecord},
+  prober::ProbeEvent,
+  proto::{Message, MessageType, Question, ResourceRecord},
   server::SlabEndpoint,
+  suppression::SlabSuppression,
+  utils::record_eq,
 };
+use parking_lot::{Mutex, RwLock};
 use smallvec_wrapper::SmallVec;
+use smol_str::ToSmolStr;
 use triomphe::Arc;
 
 use crate::{
-  Buffer, MDNS_PORT, ServerOptions,
-  utils::{multicast_udp4_socket, multicast_udp6_socket},
+  Buffer, MDNS_PORT, ServerEvent, ServerOptions,
+  utils::{
+    Announce, Claim, Delayed, Multicasted, Response, Truncated, multicast_addr,
+    multicast_udp4_socket, multicast_udp6_socket,
+  },
 };
 
 use super::Zone;
@@ -30,12 +38,13 @@ where
   N: Net,
   Z: Zone,
 {
-  zone: Arc<Z>,
+  zone: Arc<SharedZone<Z>>,
   opts: ServerOptions,
   handles: AtomicRefCell<
     FuturesUnordered<<<N::Runtime as RuntimeLite>::Spawner as AsyncSpawner>::JoinHandle<()>>,
   >,
   shutdown_tx: Sender<()>,
+  events_rx: Receiver<ServerEvent>,
   _m: std::marker::PhantomData<N>,
 }
 
@@ -55,10 +64,19 @@ where
   Z: Zone,
 {
   /// Creates a new mDNS server.
+  ///
+  /// Waits until the unique records of the zone are claimed, see RFC 6762, section 8.1.
+  /// The zone is renamed if some of its names are owned by another host, see
+  /// [`Zone::rename`], and this fails if it cannot be renamed.
+  ///
+  /// Once claimed, all the records of the zone are announced in the background, see
+  /// RFC 6762, section 8.3.
   pub async fn new(zone: Z, opts: ServerOptions) -> io::Result<Self> {
     let (shutdown_tx, shutdown_rx) = async_channel::bounded(1);
+    let (claimed_tx, claimed_rx) = async_channel::bounded(2);
+    let (events_tx, events_rx) = async_channel::unbounded();
 
-    let zone = Arc::new(zone);
+    let zone = Arc::new(SharedZone::new(zone));
     let handles = FuturesUnordered::new();
 
     let v4 = if ipv4() {
@@ -71,6 +89,8 @@ where
           opts.log_empty_responses,
           opts.max_payload_size,
           shutdown_rx.clone(),
+          claimed_tx.clone(),
+          events_tx.clone(),
         )?),
         Err(e) => {
           tracing::error!(err=%e, "mdns server: failed to bind to IPv4");
@@ -91,6 +111,8 @@ where
           opts.log_empty_responses,
           opts.max_payload_size,
           shutdown_rx.clone(),
+          claimed_tx.clone(),
+          events_tx.clone(),
         )?),
         Err(e) => {
           tracing::error!(err=%e, "mdns server: failed to bind to IPv6");
@@ -101,6 +123,7 @@ where
       None
     };
 
+    drop(claimed_tx);
     match (v4, v6) {
       (Some(v4), Some(v6)) => {
         handles.push(<N::Runtime as RuntimeLite>::Spawner::spawn(v4.process()));
@@ -120,19 +143,45 @@ where
       }
     }
 
-    Ok(Self {
+    let this = Self {
       zone,
       opts,
       handles: AtomicRefCell::new(handles),
       shutdown_tx,
+      events_rx,
       _m: std::marker::PhantomData,
-    })
+    };
+
+    // Do not return before the unique records of the zone are claimed, see RFC 6762, section 8.1.
+    while let Ok(claimed) = claimed_rx.recv().await {
+      if !claimed {
+        this.shutdown().await;
+        return Err(io::Error::new(
+          io::ErrorKind::AddrInUse,
+          "the unique records of the zone are already owned by another host",
+        ));
+      }
+    }
+
+    Ok(this)
   }
 
   /// Returns the zone of the server.
+  ///
+  /// The zone may be replaced while the server is running, e.g. when it is renamed,
+  /// see [`Server::events`], so a snapshot of the current zone is returned.
   #[inline]
-  pub fn zone(&self) -> &Z {
-    &self.zone
+  pub fn zone(&self) -> Arc<Z> {
+    self.zone.load().1
+  }
+
+  /// Replaces the zone of the server, e.g. when its records changed.
+  ///
+  /// A goodbye is sent for the records removed from the zone, the unique records of the
+  /// new zone are probed for if their names changed, and all its records are announced
+  /// again, see RFC 6762, section 8.4 and 10.1.
+  pub fn update_zone(&self, zone: Z) {
+    self.zone.store(zone);
   }
 
   /// Returns the options of the server.
@@ -141,26 +190,106 @@ where
     &self.opts
   }
 
+  /// Returns a receiver of the events of the server, e.g. the zone was renamed
+  /// because one of its names is owned by another host.
+  ///
+  /// Events happened while creating the server are also received.
+  #[inline]
+  pub fn events(&self) -> Receiver<ServerEvent> {
+    self.events_rx.clone()
+  }
+
   /// Shuts down the mDNS server.
   ///
+  /// A goodbye for the announced records is sent before the packet processors stop,
+  /// see RFC 6762, section 10.1.
+  ///
+  /// The server is also shut down when a name of the zone is owned by another host and
+  /// the zone cannot be renamed, see [`ServerEvent::Lost`].
+  ///
   /// This method is concurrent safe and can be called multiple times, but only the first call
   /// will have an effect.
   pub async fn shutdown(&self) {
-    if !self.shutdown_tx.close() {
-      return;
-    }
-
+    self.shutdown_tx.close();
     let mut handles = core::mem::take(&mut *self.handles.borrow_mut());
     while handles.next().await.is_some() {}
   }
 }
 
+/// The zone shared by a [`Server`] and its packet processors.
+struct SharedZone<Z> {
+  /// The generation of the zone, incremented every time it is replaced, and the zone.
+  zone: RwLock<(u64, Arc<Z>)>,
+  /// Wakes up the packet processors when the zone is replaced.
+  watchers: Mutex<SmallVec<Sender<()>>>,
+}
+
+impl<Z> SharedZone<Z> {
+  fn new(zone: Z) -> Self {
+    Self {
+      zone: RwLock::new((0, Arc::new(zone))),
+      watchers: Mutex::new(SmallVec::new()),
+    }
+  }
+
+  fn load(&self) -> (u64, Arc<Z>) {
+    self.zone.read().clone()
+  }
+
+  /// Returns a receiver notified every time the zone is replaced.
+  fn watch(&self) -> Receiver<()> {
+    let (tx, rx) = async_channel::bounded(1);
+    self.watchers.lock().push(tx);
+    rx
+  }
+
+  /// Replaces the zone, if it is still at `generation`.
+  ///
+  /// Returns `false` if the zone has already been replaced.
+  fn replace(&self, generation: u64, zone: Z) -> bool {
+    {
+      let mut current = self.zone.write();
+      if current.0 != generation {
+        return false;
+      }
+      *current = (generation + 1, Arc::new(zone));
+    }
+
+    self.notify();
+    true
+  }
+
+  /// Replaces the zone, whatever its generation.
+  fn store(&self, zone: Z) {
+    {
+      let mut current = self.zone.write();
+      *current = (current.0 + 1, Arc::new(zone));
+    }
+
+    self.notify();
+  }
+
+  fn notify(&self) {
+    self.watchers.lock().iter().for_each(|tx| {
+      let _ = tx.try_send(());
+    });
+  }
+}
+
+/// Why a packet processor woke up.
+enum Wake {
+  Shutdown,
+  Replaced,
+  Packet(io::Result<(usize, SocketAddr)>),
+  Timeout,
+}
+
 struct Processor<N, Z>
 where
   N: Net,
   Z: Zone,
 {
-  zone: Arc<Z>,
+  zone: Arc<SharedZone<Z>>,
   conn: N::UdpSocket,
   #[allow(dead_code)]
   local_addr: SocketAddr,
@@ -170,6 +299,10 @@ where
   max_payload_size: usize,
   endpoint: SlabEndpoint,
   shutdown_rx: Receiver<()>,
+  replaced_rx: Receiver<()>,
+  /// Reports whether the unique records of the zone were claimed.
+  claimed_tx: Option<Sender<bool>>,
+  events_tx: Sender<ServerEvent>,
 }
 
 impl<N, Z> Processor<N, Z>
@@ -179,19 +312,24 @@ where
 {
   fn new(
     conn: N::UdpSocket,
-    zone: Arc<Z>,
+    zone: Arc<SharedZone<Z>>,
     log_empty_responses: bool,
     max_payload_size: usize,
     shutdown_rx: Receiver<()>,
+    claimed_tx: Sender<bool>,
+    events_tx: Sender<ServerEvent>,
   ) -> io::Result<Self> {
     conn.local_addr().map(|local_addr| Self {
       conn,
+      replaced_rx: zone.watch(),
       zone,
       local_addr,
       log_empty_responses,
       max_payload_size,
       endpoint: SlabEndpoint::new(),
       shutdown_rx,
+      claimed_tx: Some(claimed_tx),
+      events_tx,
     })
   }
 
@@ -200,66 +338,309 @@ where
       conn,
       zone,
       shutdown_rx,
+      replaced_rx,
       mut endpoint,
       local_addr,
       log_empty_responses,
       max_payload_size,
+      mut claimed_tx,
+      events_tx,
     } = self;
 
     let mut buf = Buffer::zerod(max_payload_size);
+    let mut owned = None;
+    let mut truncated = Truncated::default();
+    let mut multicasted = Multicasted::default();
+    let mut suppression = SlabSuppression::new();
+
+    'zone: loop {
+      while replaced_rx.try_recv().is_ok() {}
+      let (generation, current) = zone.load();
+      let mut claim = Claim::with_owned(current.unique_records(), owned.take());
+      let mut announce = None;
+      // the delayed responses of a replaced zone are dropped, as their records may be gone
+      let mut delayed = Delayed::default();
+      if claim.is_claimed() {
+        claimed_tx.take();
+        announce = Some(Announce::new(
+          current.shared_records(),
+          current.unique_records(),
+        ));
+      }
 
-    tracing::info!(local=%local_addr, service=?zone, "mdns server: listening mDNS packets");
-    loop {
-      let shutdown_fut = shutdown_rx.recv().fuse();
-      let recv_fut = async {
-        match conn.recv_from(&mut buf).await {
-          Err(_err) => {
-            #[cfg(target_os = "linux")]
-            tracing::error!(err=%_err, local=%local_addr, "mdns server: failed to receive data from UDP socket");
-            return ControlFlow::<(), bool>::Continue(true);
+      tracing::info!(local=%local_addr, service=?current, "mdns server: listening mDNS packets");
+      loop {
+        let deadline = claim
+          .poll_timeout()
+          .into_iter()
+          .chain(announce.as_ref().and_then(Announce::poll_timeout))
+          .chain(truncated.poll_timeout())
+          .chain(delayed.poll_timeout())
+          .min();
+        let wake = {
+          let shutdown_fut = shutdown_rx.recv().fuse();
+          let replaced_fut = replaced_rx.recv().fuse();
+          let recv_fut = conn.recv_from(&mut buf).fuse();
+          let timeout_fut = async {
+            match deadline {
+              Some(deadline) => {
+                <N::Runtime as RuntimeLite>::sleep(
+                  deadline.saturating_duration_since(Instant::now()),
+                )
+                .await;
+              }
+              None => futures::future::pending().await,
+            }
+          }
+          .fuse();
+          futures::pin_mut!(shutdown_fut, replaced_fut, recv_fut, timeout_fut);
+
+          // the pending packets are handled with the current zone before it is replaced,
+          // as some of them may be our own responses
+          futures::select_biased! {
+            _ = shutdown_fut => Wake::Shutdown,
+            res = recv_fut => Wake::Packet(res),
+            _ = replaced_fut => Wake::Replaced,
+            _ = timeout_fut => Wake::Timeout,
+          }
+        };
+
+        let event = match wake {
+          Wake::Shutdown => {
+            // the announced records are no longer valid, see RFC 6762, section 10.1
+            if let Some(goodbye) = announce.as_ref().and_then(|a| a.encode_goodbye(&[])) {
+              Self::multicast(&conn, &local_addr, goodbye, "goodbye").await;
+            }
+            tracing::info!("mdns server: shutting down server packet processor");
+            return;
+          }
+          Wake::Replaced => {
+            // say goodbye to the records removed from the zone
+            let (_, next) = zone.load();
+            let kept = next
+              .shared_records()
+              .chain(next.unique_records())
+              .collect::<SmallVec<_>>();
+            if let Some(goodbye) = announce.as_ref().and_then(|a| a.encode_goodbye(&kept)) {
+              Self::multicast(&conn, &local_addr, goodbye, "goodbye").await;
+            }
+            owned = claim.owned_names();
+            continue 'zone;
           }
-          Ok((len, addr)) => {
-            if len == 0 {
-              return ControlFlow::Continue(false);
+          Wake::Timeout => {
+            let now = Instant::now();
+            if let Some(announcement) = announce
+              .as_mut()
+              .and_then(|a| a.handle_timeout(now, &mut multicasted))
+            {
+              Self::multicast(&conn, &local_addr, announcement, "announcement").await;
             }
 
+            for pending in delayed.handle_timeout(now) {
+              Self::respond(
+                &conn,
+                pending.addr(),
+                pending.response(),
+                &mut multicasted,
+                Some(&suppression),
+                max_payload_size,
+              )
+              .await;
+            }
+
+            let mut event = claim.handle_timeout(now);
+            for (addr, query) in truncated.handle_timeout(now) {
+              let data = match query {
+                Ok(data) => data,
+                Err(e) => {
+                  tracing::error!(from=%addr, err=%e, "mdns server: fail to merge truncated query");
+                  continue;
+                }
+              };
+              let held = Self::handle_query(
+                &mut endpoint,
+                &conn,
+                addr,
+                &data,
+                &current,
+                &mut claim,
+                &mut multicasted,
+                &mut suppression,
+                &mut delayed,
+                log_empty_responses,
+                max_payload_size,
+              )
+              .await;
+              event = event.or(held);
+            }
+            event
+          }
+          Wake::Packet(Err(_err)) => {
+            #[cfg(target_os = "linux")]
+            tracing::error!(err=%_err, local=%local_addr, "mdns server: failed to receive data from UDP socket");
+            <N::Runtime as RuntimeLite>::yield_now().await;
+            continue;
+          }
+          Wake::Packet(Ok((0, _))) => continue,
+          Wake::Packet(Ok((len, addr))) => {
             let data = &buf[..len];
             tracing::trace!(from=%addr, data=?data, "mdns server: received packet");
+            if truncated.hold(addr, data) {
+              continue;
+            }
+
+            Self::handle_query(
+              &mut endpoint,
+              &conn,
+              addr,
+              data,
+              &current,
+              &mut claim,
+              &mut multicasted,
+              &mut suppression,
+              &mut delayed,
+              log_empty_responses,
+              max_payload_size,
+            )
+            .await
+          }
+        };
 
-            Self::handle_query(&mut endpoint, &conn, addr, data, &zone, log_empty_responses).await;
-            ControlFlow::Continue(false)
+        match event {
+          Some(ProbeEvent::Probe { unicast_response }) => {
+            let probe = claim.encode_probe(unicast_response);
+            Self::multicast(&conn, &local_addr, probe, "probe").await;
+          }
+          Some(ProbeEvent::Won) => {
+            tracing::info!(local=%local_addr, "mdns server: claimed the unique records of the zone");
+            if let Some(tx) = claimed_tx.take() {
+              let _ = tx.try_send(true);
+            }
+            announce = Some(Announce::new(
+              current.shared_records(),
+              current.unique_records(),
+            ));
+          }
+          Some(ProbeEvent::Deferred) => {
+            tracing::info!(
+              local=%local_addr,
+              "mdns server: lost a simultaneous probe tiebreak, probing again"
+            );
+          }
+          Some(ProbeEvent::Lost { index }) => {
+            let name = claim.name(index);
+            let Some((renamed, to)) = current.rename(name) else {
+              tracing::error!(local=%local_addr, name=%name, "mdns server: name already owned by another host");
+              // `Server::new` fails if the zone was never claimed
+              if let Some(tx) = claimed_tx.take() {
+                let _ = tx.try_send(false);
+                return;
+              }
+
+              // the other packet processors stop too, the zone is no longer served
+              if shutdown_rx.close() {
+                let name = name.to_smolstr();
+                let _ = events_tx.try_send(ServerEvent::Lost { name });
+              }
+              continue;
+            };
+
+            // the other packet processor may have renamed the zone already
+            if zone.replace(generation, renamed) {
+              let from = name.to_smolstr();
+              tracing::warn!(from=%from, to=%to, "mdns server: name already owned by another host, renamed");
+              let _ = events_tx.try_send(ServerEvent::Renamed { from, to });
+            }
+            continue 'zone;
           }
+          Some(ProbeEvent::Conflict) => {
+            tracing::warn!(local=%local_addr, "mdns server: conflicting records detected, probing again");
+            claim.restart();
+            announce = None;
+          }
+          None => {}
         }
-      };
-      futures::pin_mut!(shutdown_fut);
-      futures::pin_mut!(recv_fut);
+      }
+    }
+  }
 
-      match futures::future::select(shutdown_fut, recv_fut).await {
-        futures::future::Either::Left(_) => {
-          tracing::info!("mdns server: shutting down server packet processor");
-          return;
+  /// Sends `data` to the mDNS multicast group.
+  async fn multicast(
+    conn: &N::UdpSocket,
+    local_addr: &SocketAddr,
+    data: io::Result<Vec<u8>>,
+    what: &str,
+  ) {
+    let to = multicast_addr(local_addr);
+    match data {
+      Ok(data) => {
+        tracing::trace!(to=%to, data=?data, "mdns server: sending {what}");
+        if let Err(e) = conn.send_to(&data, to).await {
+          tracing::error!(to=%to, err=%e, "mdns server: fail to send {what}");
         }
-        futures::future::Either::Right((res, _)) => {
-          if let ControlFlow::Continue(true) = res {
-            <N::Runtime as RuntimeLite>::yield_now().await;
+      }
+      Err(e) => tracing::error!(err=%e, "mdns server: fail to serialize {what}"),
+    }
+  }
+
+  /// Sends `response` to the question of `addr`, the records which have not been multicast
+  /// recently are multicast, unless another host has just multicast them, see
+  /// `suppression`.
+  async fn respond(
+    conn: &N::UdpSocket,
+    addr: SocketAddr,
+    mut response: Response<'_>,
+    multicasted: &mut Multicasted,
+    suppression: Option<&SlabSuppression<Instant>>,
+    max_payload_size: usize,
+  ) {
+    let now = Instant::now();
+    response.route(multicasted, now);
+    if let Some(suppression) = suppression {
+      response.suppress(|record| suppression.is_answer_suppressed(record, now));
+    }
+    let group = conn.local_addr().map(|local| multicast_addr(&local));
+    match response.encode(max_payload_size) {
+      Err(e) => {
+        tracing::error!(from=%addr, err=%e, "mdns server: fail to serialize response message");
+      }
+      Ok(packets) => {
+        for (unicast, packet) in packets {
+          let to = match (unicast, &group) {
+            (true, _) => addr,
+            (false, Ok(group)) => *group,
+            (false, Err(e)) => {
+              tracing::error!(err=%e, "mdns server: fail to get local address");
+              continue;
+            }
+          };
+          tracing::trace!(from=%addr, to=%to, data=?packet, "mdns server: sending response message");
+          if let Err(e) = conn.send_to(&packet, to).await {
+            tracing::error!(to=%to, err=%e, "mdns server: fail to send response message");
           }
         }
       }
     }
   }
 
+  #[allow(clippy::too_many_arguments)]
   async fn handle_query(
     endpoint: &mut SlabEndpoint,
     conn: &N::UdpSocket,
     addr: SocketAddr,
     data: &[u8],
     zone: &Z,
+    claim: &mut Claim<'_>,
+    multicasted: &mut Multicasted,
+    suppression: &mut SlabSuppression<Instant>,
+    delayed: &mut Delayed,
     log_empty_responses: bool,
-  ) {
+    max_payload_size: usize,
+  ) -> Option<ProbeEvent> {
     let ch = match endpoint.accept() {
       Err(e) => {
         tracing::error!(from=%addr, err=%e, "mdns server: fail to accept connection");
-        return;
+        return None;
       }
       Ok(ch) => ch,
     };
@@ -303,26 +684,55 @@ where
               if let Err(e) = endpoint.drain_connection(ch) {
                 tracing::error!(from=%addr, err=%e, "mdns server: fail to drain connection");
               }
-              return;
+              return None;
             }
           },
         }
       }
     };
 
-    let q = match endpoint.recv(ch, req) {
+    let event = claim.recv(data, &req);
+
+    // the questions and answers of the other hosts suppress ours, see RFC 6762, sections
+    // 7.3 and 7.4, a query from another port than 5353 is answered by unicast only
+    if addr.port() == MDNS_PORT {
+      if let Err(e) = suppression.observe(data, &req, Instant::now()) {
+        tracing::error!(from=%addr, err=%e, "mdns server: fail to observe message");
+      }
+    }
+
+    // Responses are only used to detect conflicts, and no query is answered before
+    // the unique records are claimed, see RFC 6762, section 8.1.
+    if req.flags().qr() == MessageType::Reply || !claim.is_claimed() {
+      if let Err(e) = endpoint.drain_connection(ch) {
+        tracing::error!(from=%addr, err=%e, "mdns server: fail to drain connection");
+      }
+      return event;
+    }
+
+    let q = match endpoint.recv(ch, data, req) {
       Err(e) => {
         tracing::error!(from=%addr, err=%e, "mdns server: fail to handle event");
         if let Err(e) = endpoint.drain_connection(ch) {
           tracing::error!(from=%addr, err=%e, "mdns server: fail to drain connection");
         }
-        return;
+        return event;
       }
       Ok(q) => q,
     };
 
+    let shared = zone.shared_records().collect::<SmallVec<_>>();
+    let mut response = Response::default();
+    let mut delayed_response = Response::default();
     for question in q.questions() {
-      match endpoint.response(q.query_handle(), *question) {
+      // RFC 6762, section 6.7: a query from a source port other than 5353 is sent by a
+      // simple resolver, which expects a conventional unicast response
+      let outgoing = if addr.port() == MDNS_PORT {
+        endpoint.response(q.query_handle(), *question)
+      } else {
+        endpoint.legacy_response(q.query_handle(), *question)
+      };
+      match outgoing {
         Err(e) => {
           tracing::error!(from=%addr, err=%e, "mdns server: fail to handle question");
         }
@@ -334,14 +744,14 @@ where
             name=%question.name(),
             "mdns server: handling question",
           );
-          let mut answers = match zone.answers(question.name(), question.ty()).await {
+          let answers = match zone.answers(question.name(), question.ty()).await {
             Err(e) => {
               tracing::error!(from=%addr, err=%e, "mdns server: fail to get answers from zone");
               continue;
             }
             Ok(records) => records.collect::<SmallVec<_>>(),
           };
-          let mut additionals = match zone.additionals(question.name(), question.ty()).await {
+          let additionals = match zone.additionals(question.name(), question.ty()).await {
             Err(e) => {
               tracing::error!(from=%addr, err=%e, "mdns server: fail to get additionals from zone");
               continue;
@@ -349,44 +759,60 @@ where
             Ok(records) => records.collect::<SmallVec<_>>(),
           };
 
-          if log_empty_responses && (answers.is_empty() && additionals.is_empty()) {
+          if answers.is_empty() && additionals.is_empty() && log_empty_responses {
             tracing::info!(
               class=%question.class(),
               ty=?question.ty(),
               name=%question.name(),
               "mdns server: no responses for question",
             );
-            continue;
           }
-
-          let msg = Message::new(
-            outgoing.id(),
-            outgoing.flags(),
-            &mut [],
-            &mut answers,
-            &mut [],
-            &mut additionals,
-          );
-          let encoded_len = msg.space_needed();
-
-          let mut buf = Buffer::zerod(encoded_len);
-
-          let len = match msg.write(&mut buf) {
-            Ok(len) => len,
-            Err(e) => {
-              tracing::error!(from=%addr, err=%e, "mdns server: fail to serialize response message");
-              continue;
-            }
-          };
-          tracing::trace!(from=%addr, data=?&buf[..len], "mdns server: sending response message");
-          if let Err(e) = conn.send_to(&buf[..len], addr).await {
-            tracing::error!(from=%addr, err=%e, "mdns server: fail to send response message");
-            continue;
+          // RFC 6762, section 6: the other hosts may answer with the same shared records,
+          // the response is delayed to avoid collisions, unique records are answered at once
+          let is_shared =
+            |record: &ResourceRecord<'_>| shared.iter().any(|s| record_eq(&[], s, record));
+          if !outgoing.is_legacy() && answers.iter().any(is_shared) {
+            delayed_response.push(&outgoing, *question, answers, additionals);
+          } else {
+            response.push(&outgoing, *question, answers, additionals);
           }
         }
       };
     }
 
+    // Known-answer suppression, see RFC 6762, section 7.1
+    let empty = response.is_empty() && delayed_response.is_empty();
+    response.retain(|record| !q.is_known_answer(record));
+    delayed_response.retain(|record| !q.is_known_answer(record));
+    if response.is_empty() && delayed_response.is_empty() {
+      if !empty {
+        tracing::debug!(
+          from=%addr,
+          "mdns server: all responses are already known by the querier",
+        );
+      }
+    } else {
+      // probes are answered immediately, see RFC 6762, section 6
+      let probe = !q.authorities().is_empty();
+      if probe {
+        response.extend(delayed_response);
+      } else if !delayed_response.is_empty() {
+        delayed.schedule(addr, &delayed_response);
+      }
+
+      if !response.is_empty() {
+        Self::respond(
+          conn,
+          addr,
+          response,
+          multicasted,
+          (!probe).then_some(&*suppression),
+          max_payload_size,
+        )
+        .await;
+      }
+    }
+
     if let Err(e) = endpoint.drain_query(q.query_handle()) {
       tracing::error!(from=%addr, err=%e, "mdns server: fail to drain query");
     }
@@ -394,5 +820,7 @@ where
     if let Err(e) = endpoint.drain_connection(ch) {
       tracing::error!(from=%addr, err=%e, "mdns server: fail to drain connection");
     }
+
+    event
   }
 }
@@ -0,0 +1,968 @@
+use core::{
+  convert::Infallible,
+  net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+  pin::Pin,
+  time::Duration,
+};
+use std::{io, time::Instant};
+
+use agnostic_net::{Net, ToSocketAddrs, UdpSocket, runtime::RuntimeLite};
+use futures::StreamExt;
+use mdns_proto::proto::ResourceRecord;
+use smol_str::format_smolstr;
+
+use crate::{
+  Completeness, HostAddr, IPV4_MDNS, Label, MDNS_PORT, QueryParam, RecordQueryParam, ResolveParam,
+  ResourceType, ServerOptions, TxtRecord,
+  service::{Service, ServiceBuilder},
+  sync,
+  tests::make_service_with_service_name,
+  utils::unicast_udp4_socket,
+  worksteal::{
+    BrowseEvent, Browser, LocalHost, RecordData, Server, ServiceEntry, Zone,
+    client::{browse, query, query_records, resolve, resolve_host},
+  },
+};
+
+macro_rules! test_suites {
+  ($runtime:ident {
+    $($name:ident),+$(,)?
+  }) => {
+    $(
+      paste::paste! {
+        #[test]
+        fn [< $runtime _ $name >]() {
+          $crate::worksteal::tests::[< $runtime _run >]($name::<agnostic_net::[< $runtime >]::Net>());
+        }
+      }
+    )*
+  }
+}
+
+/// Waits for the next event of `browser`.
+async fn next_event<N: Net>(browser: &mut Pin<Box<Browser>>) -> BrowseEvent {
+  <N::Runtime as RuntimeLite>::timeout(Duration::from_secs(5), browser.next())
+    .await
+    .expect("no event received")
+    .expect("the browser stopped")
+    .unwrap()
+}
+
+async fn client_browse<N: Net>() {
+  // a service name of its own, so the other tests are not disturbed
+  let name = format_smolstr!("_browse{}._tcp", fastrand::u16(..));
+  let instance = format_smolstr!("hostname.{name}.local");
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  let params = QueryParam::new(name.as_str().into()).with_disable_ipv6(true);
+  let mut browser = Box::pin(browse::<N>(params).await.unwrap());
+
+  match next_event::<N>(&mut browser).await {
+    BrowseEvent::Added(ent) => {
+      assert_eq!(ent.name(), &instance);
+      assert_eq!(ent.port(), 80);
+      assert_eq!(ent.txt().next().unwrap(), b"Local web server");
+    }
+    ev => panic!("unexpected event: {ev:?}"),
+  }
+
+  // the port changed
+  serv.update_zone(
+    ServiceBuilder::new("hostname".into(), name.as_str().into())
+      .with_domain("local.".into())
+      .with_hostname("testhost.".into())
+      .with_port(8080)
+      .with_ip("192.168.0.42".parse().unwrap())
+      .with_ip("2620:0:1000:1900:b0c2:d0b2:c411:18bc".parse().unwrap())
+      .with_txt_record("Local web server")
+      .finalize()
+      .unwrap(),
+  );
+  match next_event::<N>(&mut browser).await {
+    BrowseEvent::Updated(ent) => {
+      assert_eq!(ent.name(), &instance);
+      assert_eq!(ent.port(), 8080);
+    }
+    ev => panic!("unexpected event: {ev:?}"),
+  }
+
+  // the goodbye of the server
+  serv.shutdown().await;
+  match next_event::<N>(&mut browser).await {
+    BrowseEvent::Removed(ent) => assert_eq!(ent.name(), &instance),
+    ev => panic!("unexpected event: {ev:?}"),
+  }
+
+  assert!(browser.canceller().cancel());
+  assert!(!browser.canceller().cancel());
+}
+
+async fn client_resolve<N: Net>() {
+  let name = format_smolstr!("_resolve{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  let instance = format_smolstr!("hostname.{name}.local.");
+  let params = ResolveParam::new(instance.as_str().into())
+    .with_timeout(Duration::from_secs(3))
+    .with_disable_ipv6(true);
+  let ent = resolve::<N>(params).await.unwrap();
+  assert_eq!(
+    ent.name().as_str(),
+    format_smolstr!("hostname.{name}.local")
+  );
+  assert_eq!(ent.host().as_str(), "testhost");
+  assert_eq!(ent.port(), 80);
+  assert_eq!(
+    ent.ipv4_addr().unwrap(),
+    &"192.168.0.42".parse::<Ipv4Addr>().unwrap()
+  );
+  assert_eq!(
+    ent.ipv6_addr().unwrap(),
+    &"2620:0:1000:1900:b0c2:d0b2:c411:18bc"
+      .parse::<Ipv6Addr>()
+      .unwrap()
+  );
+  assert_eq!(ent.txt().next().unwrap(), b"Local web server");
+
+  // an instance nobody answers for
+  let nobody = format_smolstr!("nobody.{name}.local.");
+  let params = ResolveParam::new(nobody.as_str().into())
+    .with_timeout(Duration::from_millis(200))
+    .with_disable_ipv6(true);
+  let err = resolve::<N>(params).await.unwrap_err();
+  assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+  serv.shutdown().await;
+}
+
+async fn client_resolve_host<N: Net>() {
+  // a hostname of its own, so the other tests are not disturbed
+  let host = format_smolstr!("host{}.local.", fastrand::u16(..));
+  let service = ServiceBuilder::new("hostname".into(), "_resolvehost._tcp".into())
+    .with_domain("local.".into())
+    .with_hostname(host.as_str().into())
+    .with_port(80)
+    .with_ip("192.168.0.42".parse().unwrap())
+    .with_ip("2620:0:1000:1900:b0c2:d0b2:c411:18bc".parse().unwrap())
+    .finalize()
+    .unwrap();
+  let serv = Server::<N, Service>::new(service, ServerOptions::default())
+    .await
+    .unwrap();
+
+  let expected = [
+    HostAddr::new("192.168.0.42".parse().unwrap(), None),
+    HostAddr::new(
+      "2620:0:1000:1900:b0c2:d0b2:c411:18bc".parse().unwrap(),
+      None,
+    ),
+  ];
+  let params = ResolveParam::new(host.as_str().into())
+    .with_timeout(Duration::from_secs(3))
+    .with_disable_ipv6(true);
+  let addrs = resolve_host::<N>(params.clone()).await.unwrap();
+  assert_eq!(addrs, expected);
+
+  // the same, resolved by a blocking thread
+  let name = host.clone();
+  let addrs = <N::Runtime as RuntimeLite>::spawn_blocking(move || {
+    let params = ResolveParam::new(name.as_str().into())
+      .with_timeout(Duration::from_secs(3))
+      .with_disable_ipv6(true);
+    sync::resolve_host(params)
+  })
+  .await
+  .unwrap()
+  .unwrap();
+  assert_eq!(addrs, expected);
+
+  // as an address resolver of agnostic_net
+  let addrs = ToSocketAddrs::<N::Runtime>::to_socket_addrs(&LocalHost::<N>::new(params, 8080))
+    .await
+    .unwrap()
+    .collect::<Vec<_>>();
+  assert_eq!(
+    addrs,
+    expected
+      .iter()
+      .map(|addr| addr.to_socket_addr(8080))
+      .collect::<Vec<SocketAddr>>()
+  );
+
+  // a host nobody answers for
+  let nobody = format_smolstr!("nobody{}.local.", fastrand::u16(..));
+  let params = ResolveParam::new(nobody.as_str().into())
+    .with_timeout(Duration::from_millis(200))
+    .with_disable_ipv6(true);
+  let err = resolve_host::<N>(params).await.unwrap_err();
+  assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+  serv.shutdown().await;
+}
+
+async fn client_resolve_host_resend<N: Net>() {
+  let service = |host: &str| {
+    ServiceBuilder::new("hostname".into(), "_resolvehost._tcp".into())
+      .with_domain("local.".into())
+      .with_hostname(host.into())
+      .with_port(80)
+      .with_ip("192.168.0.42".parse().unwrap())
+      .finalize()
+      .unwrap()
+  };
+  let expected = [HostAddr::new("192.168.0.42".parse().unwrap(), None)];
+
+  // the host is not up yet when the query is first sent, it answers the next one
+  let host = format_smolstr!("host{}.local.", fastrand::u16(..));
+  let params = ResolveParam::new(host.as_str().into())
+    .with_timeout(Duration::from_secs(3))
+    .with_disable_ipv6(true);
+  let (addrs, serv) = futures::join!(resolve_host::<N>(params), async {
+    <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(200)).await;
+    Server::<N, Terse>::new(Terse(service(&host)), ServerOptions::default())
+      .await
+      .unwrap()
+  });
+  assert_eq!(addrs.unwrap(), expected);
+  serv.shutdown().await;
+
+  // the same, resolved by a blocking thread
+  let host = format_smolstr!("host{}.local.", fastrand::u16(..));
+  let name = host.clone();
+  let (addrs, serv) = futures::join!(
+    <N::Runtime as RuntimeLite>::spawn_blocking(move || {
+      let params = ResolveParam::new(name.as_str().into())
+        .with_timeout(Duration::from_secs(3))
+        .with_disable_ipv6(true);
+      sync::resolve_host(params)
+    }),
+    async {
+      <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(200)).await;
+      Server::<N, Terse>::new(Terse(service(&host)), ServerOptions::default())
+        .await
+        .unwrap()
+    }
+  );
+  assert_eq!(addrs.unwrap().unwrap(), expected);
+  serv.shutdown().await;
+}
+
+async fn client_resolve_host_responders<N: Net>() {
+  let host = format_smolstr!("host{}.local.", fastrand::u16(..));
+  let service = |instance: &str, ip: &str| {
+    ServiceBuilder::new(instance.into(), "_resolvehost._tcp".into())
+      .with_domain("local.".into())
+      .with_hostname(host.as_str().into())
+      .with_port(80)
+      .with_ip(ip.parse().unwrap())
+      .finalize()
+      .unwrap()
+  };
+  // both answer for the host, as the zones claim no unique records to probe for
+  let web = Server::<N, Terse>::new(
+    Terse(service("web", "192.168.0.42")),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+  let api = Server::<N, Terse>::new(
+    Terse(service("api", "192.168.0.43")),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  // the addresses of every responder, not only the first one to answer
+  let expected = [
+    HostAddr::new("192.168.0.42".parse().unwrap(), None),
+    HostAddr::new("192.168.0.43".parse().unwrap(), None),
+  ];
+  let params = ResolveParam::new(host.as_str().into())
+    .with_timeout(Duration::from_secs(3))
+    .with_disable_ipv6(true);
+  let mut addrs = resolve_host::<N>(params).await.unwrap();
+  addrs.sort_by_key(|addr| addr.to_socket_addr(0));
+  assert_eq!(addrs, expected);
+
+  let name = host.clone();
+  let mut addrs = <N::Runtime as RuntimeLite>::spawn_blocking(move || {
+    let params = ResolveParam::new(name.as_str().into())
+      .with_timeout(Duration::from_secs(3))
+      .with_disable_ipv6(true);
+    sync::resolve_host(params)
+  })
+  .await
+  .unwrap()
+  .unwrap();
+  addrs.sort_by_key(|addr| addr.to_socket_addr(0));
+  assert_eq!(addrs, expected);
+
+  web.shutdown().await;
+  api.shutdown().await;
+}
+
+async fn client_query_records<N: Net>() {
+  let name = format_smolstr!("_records{}._tcp", fastrand::u16(..));
+  let host = format_smolstr!("host{}.local.", fastrand::u16(..));
+  let service = ServiceBuilder::new("hostname".into(), name.as_str().into())
+    .with_domain("local.".into())
+    .with_hostname(host.as_str().into())
+    .with_port(80)
+    .with_ip("192.168.0.42".parse().unwrap())
+    .with_ip("2620:0:1000:1900:b0c2:d0b2:c411:18bc".parse().unwrap())
+    .with_txt_record("Local web server")
+    .finalize()
+    .unwrap();
+  let serv = Server::<N, Service>::new(service, ServerOptions::default())
+    .await
+    .unwrap();
+
+  // the PTR records of the service and the addresses of the host, in one packet
+  let service = format_smolstr!("{name}.local.");
+  let params = RecordQueryParam::new()
+    .with_question(service.as_str().into(), ResourceType::Ptr, false)
+    .with_question(host.as_str().into(), ResourceType::A, false)
+    .with_question(host.as_str().into(), ResourceType::AAAA, false)
+    .with_timeout(Duration::from_millis(1500))
+    .with_disable_ipv6(true);
+  let (tx, rx) = async_channel::unbounded();
+  query_records::<N>(params, tx).await.unwrap();
+
+  let mut records = Vec::new();
+  while let Ok(record) = rx.recv().await {
+    records.push(record);
+  }
+
+  // the SRV and TXT records of the Additional section were not asked for
+  assert_eq!(records.len(), 3, "{records:?}");
+  assert!(records.iter().any(|r| {
+    r.ty() == ResourceType::Ptr
+      && r.data() == &RecordData::Ptr(format_smolstr!("hostname.{name}.local"))
+  }));
+  assert!(records.iter().any(|r| {
+    r.data() == &RecordData::A("192.168.0.42".parse().unwrap())
+      && r.name().trim_end_matches('.') == host.trim_end_matches('.')
+  }));
+  assert!(records.iter().any(|r| {
+    r.data()
+      == &RecordData::AAAA {
+        addr: "2620:0:1000:1900:b0c2:d0b2:c411:18bc".parse().unwrap(),
+        zone: None,
+      }
+  }));
+
+  // at least one question must be asked
+  let (tx, _rx) = async_channel::unbounded();
+  let err = query_records::<N>(RecordQueryParam::new(), tx)
+    .await
+    .unwrap_err();
+  assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+  serv.shutdown().await;
+}
+
+/// A zone answering a question with the records of the type asked for only, like some
+/// embedded responders, the other records of an instance must be asked for.
+#[derive(Debug)]
+struct Terse(Service);
+
+impl Zone for Terse {
+  type Error = Infallible;
+
+  async fn answers<'a>(
+    &'a self,
+    name: Label<'a>,
+    rt: ResourceType,
+  ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
+    Ok(
+      self
+        .0
+        .fetch_answers(name, rt)
+        .filter(move |record| record.ty() == rt),
+    )
+  }
+
+  async fn additionals<'a>(
+    &'a self,
+    _: Label<'a>,
+    _: ResourceType,
+  ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
+    Ok(core::iter::empty())
+  }
+}
+
+async fn client_follow_up<N: Net>() {
+  let name = format_smolstr!("_terse{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Terse>::new(
+    Terse(make_service_with_service_name(&name)),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  // the PTR record only is received, then the SRV and TXT records of the instance, then
+  // the addresses of its target host are asked for
+  let (tx, rx) = async_channel::unbounded();
+  let params = QueryParam::new(name.as_str().into())
+    .with_timeout(Duration::from_secs(3))
+    .with_disable_ipv6(true);
+  query::<N>(params, tx).await.unwrap();
+
+  let ent = <N::Runtime as RuntimeLite>::timeout(Duration::from_secs(3), rx.recv())
+    .await
+    .expect("the entry is not complete")
+    .unwrap();
+  assert_eq!(
+    ent.name().as_str(),
+    format_smolstr!("hostname.{name}.local")
+  );
+  assert_eq!(ent.port(), 80);
+  assert_eq!(
+    ent.ipv4_addr().unwrap(),
+    &"192.168.0.42".parse::<Ipv4Addr>().unwrap()
+  );
+  assert_eq!(ent.txt().next().unwrap(), b"Local web server");
+
+  serv.shutdown().await;
+}
+
+async fn client_shared_host<N: Net>() {
+  let name = format_smolstr!("_shared{}._tcp", fastrand::u16(..));
+  let host = format_smolstr!("box{}.local.", fastrand::u16(..));
+  let service = |instance: &str| {
+    ServiceBuilder::new(instance.into(), name.as_str().into())
+      .with_domain("local.".into())
+      .with_hostname(host.as_str().into())
+      .with_port(80)
+      .with_ip("192.168.0.42".parse().unwrap())
+      .with_txt_record("Local web server")
+      .finalize()
+      .unwrap()
+  };
+
+  // the addresses of the host are received after the SRV records of both instances
+  let web = Server::<N, Terse>::new(Terse(service("web")), ServerOptions::default())
+    .await
+    .unwrap();
+  let api = Server::<N, Terse>::new(Terse(service("api")), ServerOptions::default())
+    .await
+    .unwrap();
+
+  let (tx, rx) = async_channel::unbounded();
+  let params = QueryParam::new(name.as_str().into())
+    .with_timeout(Duration::from_secs(3))
+    .with_disable_ipv6(true);
+  query::<N>(params, tx).await.unwrap();
+
+  let mut names = Vec::new();
+  while let Ok(ent) = rx.recv().await {
+    assert_eq!(ent.host().trim_end_matches('.'), host.trim_end_matches('.'));
+    assert_eq!(
+      ent.ipv4_addr().unwrap(),
+      &"192.168.0.42".parse::<Ipv4Addr>().unwrap()
+    );
+    names.push(ent.name().clone());
+  }
+  names.sort();
+  assert_eq!(
+    names,
+    [
+      format_smolstr!("api.{name}.local"),
+      format_smolstr!("web.{name}.local"),
+    ]
+  );
+
+  web.shutdown().await;
+  api.shutdown().await;
+}
+
+async fn client_multi_homed<N: Net>() {
+  let name = format_smolstr!("_multi{}._tcp", fastrand::u16(..));
+  let ipv4s: [Ipv4Addr; 3] = [
+    "192.168.0.42".parse().unwrap(),
+    "10.0.0.42".parse().unwrap(),
+    "172.16.0.42".parse().unwrap(),
+  ];
+  let ipv6s: [Ipv6Addr; 2] = [
+    "2620:0:1000:1900:b0c2:d0b2:c411:18bc".parse().unwrap(),
+    "fd00::42".parse().unwrap(),
+  ];
+  let service = ipv4s
+    .iter()
+    .map(|ip| IpAddr::V4(*ip))
+    .chain(ipv6s.iter().map(|ip| IpAddr::V6(*ip)))
+    .fold(
+      ServiceBuilder::new("hostname".into(), name.as_str().into())
+        .with_domain("local.".into())
+        .with_hostname("testhost.".into())
+        .with_port(80)
+        .with_srv_priority(5)
+        .with_srv_weight(7)
+        .with_txt_record("Local web server"),
+      ServiceBuilder::with_ip,
+    )
+    .finalize()
+    .unwrap();
+  let serv = Server::<N, Service>::new(service, ServerOptions::default())
+    .await
+    .unwrap();
+
+  let check = |ent: &ServiceEntry, interface: Option<&Ipv4Addr>| {
+    let mut v4 = ent.ipv4_addrs().copied().collect::<Vec<_>>();
+    v4.sort();
+    let mut expected = ipv4s.to_vec();
+    expected.sort();
+    assert_eq!(v4, expected);
+
+    let mut v6 = ent.ipv6_addrs().copied().collect::<Vec<_>>();
+    v6.sort();
+    let mut expected = ipv6s.to_vec();
+    expected.sort();
+    assert_eq!(v6, expected);
+
+    assert_eq!(ent.socket_addrs().count(), 5);
+    assert!(ent.socket_addrs().all(|addr| addr.port() == 80));
+    assert_eq!(ent.priority(), 5);
+    assert_eq!(ent.weight(), 7);
+    assert!(ent.expires().unwrap() > Instant::now());
+    assert!(ent.source().is_some());
+    assert_eq!(ent.ipv4_interface(), interface);
+    assert_eq!(ent.ipv6_interface(), None);
+  };
+
+  // the records of the in-progress query
+  let (tx, rx) = async_channel::unbounded();
+  let params = QueryParam::new(name.as_str().into())
+    .with_timeout(Duration::from_secs(2))
+    .with_disable_ipv6(true);
+  query::<N>(params, tx).await.unwrap();
+  let ent = rx.recv().await.unwrap();
+  check(&ent, None);
+
+  // the records cached by the clients, received on the interface the lookup is bound to
+  let instance = format_smolstr!("hostname.{name}.local.");
+  let params = ResolveParam::new(instance.as_str().into())
+    .with_timeout(Duration::from_secs(3))
+    .with_disable_ipv6(true)
+    .with_ipv4_interface(Ipv4Addr::UNSPECIFIED);
+  let ent = resolve::<N>(params).await.unwrap();
+  check(&ent, Some(&Ipv4Addr::UNSPECIFIED));
+
+  serv.shutdown().await;
+}
+
+/// A zone without a TXT record, like some embedded responders.
+#[derive(Debug)]
+struct NoTxt(Service);
+
+impl Zone for NoTxt {
+  type Error = Infallible;
+
+  async fn answers<'a>(
+    &'a self,
+    name: Label<'a>,
+    rt: ResourceType,
+  ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
+    Ok(
+      self
+        .0
+        .fetch_answers(name, rt)
+        .filter(|record| record.ty() != ResourceType::Txt),
+    )
+  }
+
+  async fn additionals<'a>(
+    &'a self,
+    _: Label<'a>,
+    _: ResourceType,
+  ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
+    Ok(core::iter::empty())
+  }
+}
+
+async fn client_completeness<N: Net>() {
+  let name = format_smolstr!("_notxt{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, NoTxt>::new(
+    NoTxt(make_service_with_service_name(&name)),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  let entries = |completeness| {
+    let name = name.clone();
+    async move {
+      let (tx, rx) = async_channel::unbounded();
+      let params = QueryParam::new(name.as_str().into())
+        .with_timeout(Duration::from_secs(1))
+        .with_disable_ipv6(true)
+        .with_completeness(completeness);
+      query::<N>(params, tx).await.unwrap();
+
+      let mut entries = Vec::new();
+      while let Ok(ent) = rx.recv().await {
+        entries.push(ent);
+      }
+      entries
+    }
+  };
+
+  // the service is never complete without its TXT record
+  assert!(entries(Completeness::RequireTxt).await.is_empty());
+
+  let found = entries(Completeness::SrvAndAddress).await;
+  assert_eq!(found.len(), 1);
+  assert_eq!(found[0].port(), 80);
+  assert!(found[0].ipv4_addr().is_some());
+  assert!(found[0].txt().next().is_none());
+  serv.shutdown().await;
+
+  // the records of the instance arrive one by one
+  let name = format_smolstr!("_partial{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Terse>::new(
+    Terse(make_service_with_service_name(&name)),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  let (tx, rx) = async_channel::unbounded();
+  let params = QueryParam::new(name.as_str().into())
+    .with_timeout(Duration::from_secs(2))
+    .with_disable_ipv6(true)
+    .with_completeness(Completeness::Partial);
+  query::<N>(params, tx).await.unwrap();
+
+  let mut found = Vec::new();
+  while let Ok(ent) = rx.recv().await {
+    found.push(ent);
+  }
+  assert!(found.len() > 1);
+  assert_eq!(found[0].port(), 0);
+  assert!(found[0].ipv4_addr().is_none());
+  let last = found.last().unwrap();
+  assert_eq!(last.port(), 80);
+  assert!(last.ipv4_addr().is_some());
+  assert_eq!(last.txt().next().unwrap(), b"Local web server");
+
+  serv.shutdown().await;
+}
+
+/// A zone whose SRV record names its target host in uppercase, unlike the address
+/// records of the host.
+#[derive(Debug)]
+struct ShoutedTarget {
+  service: Service,
+  srv: Vec<u8>,
+}
+
+impl ShoutedTarget {
+  fn new(service: Service) -> Self {
+    let mut srv = [0, 0, 0, 0].to_vec();
+    srv.extend_from_slice(&service.port().to_be_bytes());
+    for label in service
+      .hostname()
+      .split('.')
+      .filter(|label| !label.is_empty())
+    {
+      srv.push(label.len() as u8);
+      srv.extend_from_slice(label.to_ascii_uppercase().as_bytes());
+    }
+    srv.push(0);
+    Self { service, srv }
+  }
+}
+
+impl Zone for ShoutedTarget {
+  type Error = Infallible;
+
+  async fn answers<'a>(
+    &'a self,
+    name: Label<'a>,
+    rt: ResourceType,
+  ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
+    Ok(self.service.fetch_answers(name, rt).map(|record| {
+      if record.ty() != ResourceType::Srv {
+        return record;
+      }
+      ResourceRecord::new(
+        record.name(),
+        record.ty(),
+        record.class(),
+        record.ttl(),
+        &self.srv,
+      )
+    }))
+  }
+
+  async fn additionals<'a>(
+    &'a self,
+    _: Label<'a>,
+    _: ResourceType,
+  ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
+    Ok(core::iter::empty())
+  }
+}
+
+async fn client_host_case<N: Net>() {
+  let name = format_smolstr!("_shouted{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, ShoutedTarget>::new(
+    ShoutedTarget::new(make_service_with_service_name(&name)),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  // the target `TESTHOST` owns the address records of `testhost`
+  let (tx, rx) = async_channel::unbounded();
+  let params = QueryParam::new(name.as_str().into())
+    .with_timeout(Duration::from_secs(1))
+    .with_disable_ipv6(true)
+    .with_completeness(Completeness::SrvAndAddress);
+  query::<N>(params, tx).await.unwrap();
+
+  let ent = rx.recv().await.unwrap();
+  assert_eq!(ent.host().trim_end_matches('.'), "TESTHOST");
+  assert_eq!(
+    ent.ipv4_addr().unwrap(),
+    &"192.168.0.42".parse::<Ipv4Addr>().unwrap()
+  );
+
+  serv.shutdown().await;
+}
+
+async fn client_txt_record<N: Net>() {
+  let name = format_smolstr!("_txt{}._tcp", fastrand::u16(..));
+  let txt = TxtRecord::new()
+    .with("txtvers", "1")
+    .unwrap()
+    .with_flag("paper")
+    .unwrap()
+    .with("note", "")
+    .unwrap()
+    .with("id", [0xff, 0x00, b'\\', b'"'])
+    .unwrap();
+  let service = ServiceBuilder::new("hostname".into(), name.as_str().into())
+    .with_domain("local.".into())
+    .with_hostname("testhost.".into())
+    .with_port(80)
+    .with_ip("192.168.0.42".parse().unwrap())
+    .with_txt_records(txt.strings())
+    .finalize()
+    .unwrap();
+  let serv = Server::<N, Service>::new(service, ServerOptions::default())
+    .await
+    .unwrap();
+
+  let instance = format_smolstr!("hostname.{name}.local.");
+  let params = ResolveParam::new(instance.as_str().into())
+    .with_timeout(Duration::from_secs(3))
+    .with_disable_ipv6(true);
+  let ent = resolve::<N>(params).await.unwrap();
+  // the bytes are received as they were published
+  assert!(ent.txt().any(|s| s == b"id=\xff\x00\\\""));
+  assert_eq!(ent.txt_strs().filter(Result::is_err).count(), 1);
+  let received = ent.txt_record();
+  assert_eq!(received, txt);
+  assert_eq!(received.get_str("TXTVERS"), Some("1"));
+  assert_eq!(received.get("paper"), Some(None));
+  assert_eq!(received.get("note"), Some(Some(&[][..])));
+  assert_eq!(
+    received.get("id"),
+    Some(Some(&[0xff, 0x00, b'\\', b'"'][..]))
+  );
+
+  serv.shutdown().await;
+}
+
+/// Encodes a response announcing the instance `hostname` of `service`, with the names
+/// compressed as by Bonjour and Avahi: the owner of the `PTR` record is a pointer to the
+/// question, and the owner of the `SRV` record a pointer to the data of the `PTR` record.
+fn compressed_announcement(service: &str) -> Vec<u8> {
+  let mut packet = vec![0, 0, 0x84, 0, 0, 1, 0, 2, 0, 0, 0, 0];
+  for label in service.split('.').filter(|label| !label.is_empty()) {
+    packet.push(label.len() as u8);
+    packet.extend_from_slice(label.as_bytes());
+  }
+  packet.push(0);
+  packet.extend_from_slice(&[0, 12, 0, 1]);
+
+  // PTR, `hostname` and a pointer to the service
+  packet.extend_from_slice(&[0xc0, 12, 0, 12, 0, 1, 0, 0, 0, 120, 0, 11]);
+  let instance = packet.len() as u8;
+  packet.push(8);
+  packet.extend_from_slice(b"hostname");
+  packet.extend_from_slice(&[0xc0, 12]);
+
+  // SRV, of priority 0, weight 0 and port 80, targeting `testhost.local`
+  packet.extend_from_slice(&[0xc0, instance, 0, 33, 0x80, 1, 0, 0, 0, 120, 0, 22]);
+  packet.extend_from_slice(&[0, 0, 0, 0, 0, 80, 8]);
+  packet.extend_from_slice(b"testhost");
+  packet.push(5);
+  packet.extend_from_slice(b"local");
+  packet.push(0);
+  packet
+}
+
+async fn client_compressed_ptr<N: Net>() {
+  let name = format_smolstr!("_compressed{}._tcp", fastrand::u16(..));
+  let service = format_smolstr!("{name}.local.");
+  let conn = unicast_udp4_socket(None)
+    .and_then(<N::UdpSocket as TryFrom<_>>::try_from)
+    .unwrap();
+  let announcement = compressed_announcement(&service);
+
+  let (tx, rx) = async_channel::unbounded();
+  let params = QueryParam::new(name.as_str().into())
+    .with_timeout(Duration::from_secs(1))
+    .with_disable_ipv6(true)
+    .with_completeness(Completeness::Partial);
+  let announce = async {
+    for _ in 0..5 {
+      conn
+        .send_to(&announcement, (IPV4_MDNS, MDNS_PORT))
+        .await
+        .unwrap();
+      <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(150)).await;
+    }
+  };
+  let (res, _) = futures::join!(query::<N>(params, tx), announce);
+  res.unwrap();
+
+  let mut found = Vec::new();
+  while let Ok(ent) = rx.recv().await {
+    found.push(ent);
+  }
+  assert!(!found.is_empty());
+  assert!(
+    found
+      .iter()
+      .all(|ent| ent.name().as_str() == format_smolstr!("hostname.{name}.local"))
+  );
+  assert_eq!(found.last().unwrap().port(), 80);
+}
+
+async fn client_subtype<N: Net>() {
+  let name = format_smolstr!("_sub{}._tcp", fastrand::u16(..));
+  let service = |instance: &str, hostname: &str, ip: &str, subtypes: &[&str]| {
+    ServiceBuilder::new(instance.into(), name.as_str().into())
+      .with_domain("local.".into())
+      .with_hostname(hostname.into())
+      .with_port(80)
+      .with_ip(ip.parse().unwrap())
+      .with_txt_record("Local web server")
+      .with_subtypes(subtypes.iter().copied())
+      .finalize()
+      .unwrap()
+  };
+  let printer = Server::<N, Service>::new(
+    service("printer", "printerhost.", "192.168.0.42", &["_printer"]),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+  let scanner = Server::<N, Service>::new(
+    service("scanner", "scannerhost.", "192.168.0.43", &[]),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  // only the instance registered with the subtype answers
+  let (tx, rx) = async_channel::unbounded();
+  let params = QueryParam::new(name.as_str().into())
+    .with_subtype("_printer".into())
+    .with_timeout(Duration::from_secs(1))
+    .with_disable_ipv6(true);
+  query::<N>(params, tx).await.unwrap();
+
+  let mut found = Vec::new();
+  while let Ok(ent) = rx.recv().await {
+    found.push(ent);
+  }
+  assert_eq!(found.len(), 1);
+  assert_eq!(
+    found[0].name().as_str(),
+    format_smolstr!("printer.{name}.local")
+  );
+  assert_eq!(found[0].port(), 80);
+
+  let params = QueryParam::new(name.as_str().into())
+    .with_subtype("_printer".into())
+    .with_disable_ipv6(true);
+  let mut browser = Box::pin(browse::<N>(params).await.unwrap());
+  match next_event::<N>(&mut browser).await {
+    BrowseEvent::Added(ent) => {
+      assert_eq!(ent.name().as_str(), format_smolstr!("printer.{name}.local"));
+    }
+    ev => panic!("unexpected event: {ev:?}"),
+  }
+  assert!(browser.canceller().cancel());
+
+  printer.shutdown().await;
+  scanner.shutdown().await;
+}
+
+#[cfg(feature = "tokio")]
+test_suites!(tokio {
+  client_browse,
+  client_resolve,
+  client_resolve_host,
+  client_resolve_host_resend,
+  client_resolve_host_responders,
+  client_query_records,
+  client_follow_up,
+  client_shared_host,
+  client_multi_homed,
+  client_completeness,
+  client_host_case,
+  client_txt_record,
+  client_subtype,
+  client_compressed_ptr,
+});
+
+#[cfg(feature = "smol")]
+test_suites!(smol {
+  client_browse,
+  client_resolve,
+  client_resolve_host,
+  client_resolve_host_resend,
+  client_resolve_host_responders,
+  client_query_records,
+  client_follow_up,
+  client_shared_host,
+  client_multi_homed,
+  client_completeness,
+  client_host_case,
+  client_txt_record,
+  client_subtype,
+  client_compressed_ptr,
+});
+
+#[cfg(feature = "async-std")]
+test_suites!(async_std {
+  client_browse,
+  client_resolve,
+  client_resolve_host,
+  client_resolve_host_resend,
+  client_resolve_host_responders,
+  client_query_records,
+  client_follow_up,
+  client_shared_host,
+  client_multi_homed,
+  client_completeness,
+  client_host_case,
+  client_txt_record,
+  client_subtype,
+  client_compressed_ptr,
+});
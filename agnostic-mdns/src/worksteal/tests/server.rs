@@ -1,15 +1,24 @@
-use core::time::Duration;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use core::{convert::Infallible, time::Duration};
+use std::{
+  net::{Ipv4Addr, Ipv6Addr},
+  time::Instant,
+};
 
-use agnostic_net::Net;
-use mdns_proto::proto::Label;
+use agnostic_net::{Net, UdpSocket, runtime::RuntimeLite};
+use mdns_proto::{
+  proto::{Flags, Label, Message, MessageType, Question, ResourceRecord, ResourceType},
+  server::{LEGACY_UNICAST_TTL, SHARED_RESPONSE_DELAY_MIN, TRUNCATED_QUERY_DELAY_MIN},
+};
+use smol_str::{SmolStr, format_smolstr};
+use socket2::{Domain, Protocol, Socket, Type};
 
 use crate::{
-  QueryParam, ServerOptions,
-  service::Service,
+  IPV4_MDNS, MDNS_PORT, QueryParam, ServerEvent, ServerOptions,
+  service::{Service, ServiceBuilder},
   sync::Server as SyncServer,
-  tests::{make_service, make_service_with_service_name},
-  worksteal::{Server, client::query},
+  tests::{make_conflicting_service, make_service, make_service_with_service_name},
+  utils::{multicast_udp4_socket, unicast_udp4_socket},
+  worksteal::{Server, Zone, client::query},
 };
 
 macro_rules! test_suites {
@@ -45,7 +54,7 @@ async fn server_start_stop<N: Net>() {
       .unwrap(),]
   );
   assert_eq!(s.port(), 80);
-  assert_eq!(s.txt_records(), &["Local web server"]);
+  assert_eq!(s.txt_records().collect::<Vec<_>>(), [b"Local web server"]);
 
   let _ = serv.options();
 
@@ -87,7 +96,7 @@ async fn server_lookup<N: Net>() {
             .parse::<Ipv6Addr>()
             .unwrap()
         );
-        assert_eq!(ent.txt()[0].as_str(), "Local web server");
+        assert_eq!(ent.txt().next().unwrap(), b"Local web server");
         got_response = true;
       }
 
@@ -102,6 +111,929 @@ async fn server_lookup<N: Net>() {
   assert!(got_response, "No response from the server");
 }
 
+async fn server_name_conflict<N: Net>() {
+  // a service name of its own, so the other tests are not disturbed
+  let name = format_smolstr!("_conflict{}._tcp", fastrand::u16(..));
+  let s = make_service_with_service_name(&name);
+  let serv = Server::<N, Service>::new(s, ServerOptions::default())
+    .await
+    .unwrap();
+
+  let renamed =
+    Server::<N, Service>::new(make_conflicting_service(&name), ServerOptions::default())
+      .await
+      .unwrap();
+  assert_eq!(renamed.zone().instance().as_str(), "hostname (2)");
+  assert_eq!(renamed.zone().port(), 8080);
+
+  assert_eq!(
+    renamed.events().try_recv().unwrap(),
+    ServerEvent::Renamed {
+      from: format_smolstr!("hostname.{name}.local"),
+      to: format_smolstr!("hostname (2).{name}.local"),
+    }
+  );
+
+  renamed.shutdown().await;
+  serv.shutdown().await;
+}
+
+/// A zone which cannot be renamed, see [`Zone::rename`].
+#[derive(Debug)]
+struct Unrenamable(Service);
+
+impl Zone for Unrenamable {
+  type Error = Infallible;
+
+  async fn answers<'a>(
+    &'a self,
+    name: Label<'a>,
+    rt: ResourceType,
+  ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
+    self.0.answers(name, rt).await
+  }
+
+  async fn additionals<'a>(
+    &'a self,
+    name: Label<'a>,
+    rt: ResourceType,
+  ) -> Result<impl Iterator<Item = ResourceRecord<'a>> + 'a, Self::Error> {
+    self.0.additionals(name, rt).await
+  }
+
+  fn shared_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    self.0.shared_records()
+  }
+
+  fn unique_records(&self) -> impl Iterator<Item = ResourceRecord<'_>> + '_ {
+    self.0.unique_records()
+  }
+}
+
+async fn server_name_lost<N: Net>() {
+  let name = format_smolstr!("_lost{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  let other = format_smolstr!("_lost{}._tcp", fastrand::u16(..));
+  let lost = Server::<N, Unrenamable>::new(
+    Unrenamable(make_service_with_service_name(&other)),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  // the new name is owned by `serv`, and the zone cannot be renamed
+  lost.update_zone(Unrenamable(make_conflicting_service(&name)));
+  let event = <N::Runtime as RuntimeLite>::timeout(Duration::from_secs(5), lost.events().recv())
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(
+    event,
+    ServerEvent::Lost {
+      name: format_smolstr!("hostname.{name}.local"),
+    }
+  );
+
+  // all the packet processors stopped
+  <N::Runtime as RuntimeLite>::timeout(Duration::from_secs(1), lost.shutdown())
+    .await
+    .unwrap();
+  serv.shutdown().await;
+}
+
+/// Waits for an unsolicited response announcing the `SRV` record of `instance`
+/// with the cache-flush bit set, and returns its port and TTL.
+async fn recv_srv<N: Net>(conn: &N::UdpSocket, instance: &str) -> (u16, u32) {
+  let mut buf = vec![0; 9000];
+  let fut = async {
+    loop {
+      let (len, _) = conn.recv_from(&mut buf).await.unwrap();
+      let mut answers = [ResourceRecord::default(); 16];
+      let Ok(msg) = Message::read(&buf[..len], &mut [], &mut answers, &mut [], &mut []) else {
+        continue;
+      };
+      if msg.flags().qr() != MessageType::Reply || msg.id() != 0 {
+        continue;
+      }
+
+      let srv = msg
+        .answers()
+        .iter()
+        .find(|r| r.ty() == ResourceType::Srv && format_smolstr!("{}", r.name()) == instance);
+      if let Some(srv) = srv {
+        assert_ne!(srv.class() & (1 << 15), 0, "cache-flush bit is not set");
+        return (
+          u16::from_be_bytes([srv.data()[4], srv.data()[5]]),
+          srv.ttl(),
+        );
+      }
+    }
+  };
+
+  <N::Runtime as RuntimeLite>::timeout(Duration::from_secs(5), fut)
+    .await
+    .expect("no announcement received")
+}
+
+fn announcement_listener<N: Net>() -> N::UdpSocket {
+  multicast_udp4_socket(None, MDNS_PORT)
+    .and_then(<N::UdpSocket as TryFrom<_>>::try_from)
+    .unwrap()
+}
+
+async fn server_announce<N: Net>() {
+  let name = format_smolstr!("_announce{}._tcp", fastrand::u16(..));
+  let instance: SmolStr = format_smolstr!("hostname.{name}.local");
+  let conn = announcement_listener::<N>();
+
+  let start = Instant::now();
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  // at least two announcements, one second apart
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (80, 120));
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (80, 120));
+  assert!(start.elapsed() >= Duration::from_secs(1));
+
+  // announces again once the records changed
+  serv.update_zone(make_conflicting_service(&name));
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (80, 0));
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (8080, 120));
+
+  serv.shutdown().await;
+}
+
+async fn server_goodbye<N: Net>() {
+  let name = format_smolstr!("_goodbye{}._tcp", fastrand::u16(..));
+  let instance: SmolStr = format_smolstr!("hostname.{name}.local");
+  let conn = announcement_listener::<N>();
+
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (80, 120));
+
+  serv.shutdown().await;
+  loop {
+    let (port, ttl) = recv_srv::<N>(&conn, &instance).await;
+    assert_eq!(port, 80);
+    if ttl == 0 {
+      break;
+    }
+  }
+}
+
+async fn sync_server_announce<N: Net>() {
+  let name = format_smolstr!("_announce{}._tcp", fastrand::u16(..));
+  let instance: SmolStr = format_smolstr!("hostname.{name}.local");
+  let conn = announcement_listener::<N>();
+
+  let start = Instant::now();
+  let (serv, closer) = SyncServer::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .unwrap();
+  let updater = serv.updater();
+  std::thread::spawn(move || serv.run());
+
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (80, 120));
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (80, 120));
+  assert!(start.elapsed() >= Duration::from_secs(1));
+
+  updater.try_send(make_conflicting_service(&name)).unwrap();
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (80, 0));
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (8080, 120));
+
+  closer.close();
+}
+
+async fn sync_server_goodbye<N: Net>() {
+  let name = format_smolstr!("_goodbye{}._tcp", fastrand::u16(..));
+  let instance: SmolStr = format_smolstr!("hostname.{name}.local");
+  let conn = announcement_listener::<N>();
+
+  let (serv, closer) = SyncServer::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .unwrap();
+  std::thread::spawn(move || serv.run());
+  assert_eq!(recv_srv::<N>(&conn, &instance).await, (80, 120));
+
+  closer.close();
+  loop {
+    let (port, ttl) = recv_srv::<N>(&conn, &instance).await;
+    assert_eq!(port, 80);
+    if ttl == 0 {
+      break;
+    }
+  }
+}
+
+/// Sends a query for the `SRV` record of `instance`, with `known` in the Answer section,
+/// and returns `true` if a unicast response is received.
+async fn query_srv<N: Net>(
+  conn: &N::UdpSocket,
+  instance: &str,
+  known: &[ResourceRecord<'_>],
+) -> bool {
+  let mut questions = [Question::new(instance, ResourceType::Srv, 1 | (1 << 15))];
+  send_query::<N>(conn, Flags::new(), &mut questions, known).await;
+  recv_within::<N>(conn, Duration::from_millis(500)).await
+}
+
+/// Sends a truncated query for the `SRV` record of `instance`, followed by a continuation
+/// packet with `known` in the Answer section, and returns `true` if a unicast response is
+/// received, no sooner than the responder is expected to wait for the continuation.
+async fn query_srv_truncated<N: Net>(
+  conn: &N::UdpSocket,
+  instance: &str,
+  known: &[ResourceRecord<'_>],
+) -> bool {
+  let mut flags = Flags::new();
+  flags.set_truncated(true);
+  let mut questions = [Question::new(instance, ResourceType::Srv, 1 | (1 << 15))];
+  let start = Instant::now();
+  send_query::<N>(conn, flags, &mut questions, &[]).await;
+  <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(100)).await;
+  send_query::<N>(conn, Flags::new(), &mut [], known).await;
+
+  let received = recv_within::<N>(conn, Duration::from_secs(1)).await;
+  assert!(!received || start.elapsed() >= TRUNCATED_QUERY_DELAY_MIN);
+  received
+}
+
+/// Like [`query_srv_truncated`], with the owner names of the continuation packet
+/// compressed, as by macOS, and an unanswered question before the one for `instance`, so
+/// that the pointers of the continuation do not refer to the same names in the truncated
+/// packet.
+async fn query_srv_truncated_compressed<N: Net>(
+  conn: &N::UdpSocket,
+  instance: &str,
+  known: &[ResourceRecord<'_>],
+) -> bool {
+  let nobody = format_smolstr!("nobody{}.local.", fastrand::u16(..));
+  let mut flags = Flags::new();
+  flags.set_truncated(true);
+  let mut questions = [
+    Question::new(nobody.as_str(), ResourceType::A, 1 | (1 << 15)),
+    Question::new(instance, ResourceType::Srv, 1 | (1 << 15)),
+  ];
+  let start = Instant::now();
+  send_query::<N>(conn, flags, &mut questions, &[]).await;
+  <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(100)).await;
+  conn
+    .send_to(&compressed_answers(known), (IPV4_MDNS, MDNS_PORT))
+    .await
+    .unwrap();
+
+  let received = recv_within::<N>(conn, Duration::from_secs(1)).await;
+  assert!(!received || start.elapsed() >= TRUNCATED_QUERY_DELAY_MIN);
+  received
+}
+
+/// Encodes a packet with `records` in the Answer section, each owner name already written
+/// in the packet replaced by a pointer to it.
+fn compressed_answers(records: &[ResourceRecord<'_>]) -> Vec<u8> {
+  let mut packet = vec![0; 12];
+  packet[6..8].copy_from_slice(&(records.len() as u16).to_be_bytes());
+
+  let mut names = Vec::<(String, u16)>::new();
+  for record in records {
+    let name = format_smolstr!("{}", record.name()).to_ascii_lowercase();
+    match names.iter().find(|(n, _)| *n == name) {
+      Some((_, at)) => packet.extend_from_slice(&(0xc000 | at).to_be_bytes()),
+      None => {
+        names.push((name.clone(), packet.len() as u16));
+        for label in name.split('.').filter(|label| !label.is_empty()) {
+          packet.push(label.len() as u8);
+          packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+      }
+    }
+    packet.extend_from_slice(&u16::from(record.ty()).to_be_bytes());
+    packet.extend_from_slice(&record.class().to_be_bytes());
+    packet.extend_from_slice(&record.ttl().to_be_bytes());
+    packet.extend_from_slice(&(record.data().len() as u16).to_be_bytes());
+    packet.extend_from_slice(record.data());
+  }
+  packet
+}
+
+async fn send_query<'a, N: Net>(
+  conn: &N::UdpSocket,
+  flags: Flags,
+  questions: &mut [Question<'a>],
+  known: &[ResourceRecord<'a>],
+) {
+  let mut answers = known.to_vec();
+  let msg = Message::new(0, flags, questions, &mut answers, &mut [], &mut []);
+  let mut buf = vec![0; msg.space_needed()];
+  let len = msg.write(&mut buf).unwrap();
+  conn
+    .send_to(&buf[..len], (IPV4_MDNS, MDNS_PORT))
+    .await
+    .unwrap();
+}
+
+async fn recv_within<N: Net>(conn: &N::UdpSocket, timeout: Duration) -> bool {
+  let mut buf = vec![0; 9000];
+  <N::Runtime as RuntimeLite>::timeout(timeout, conn.recv_from(&mut buf))
+    .await
+    .is_ok()
+}
+
+async fn known_answer_suppression<N: Net>(name: &str) {
+  let instance = format_smolstr!("hostname.{name}.local.");
+  let service = make_service_with_service_name(name);
+  let known = service.unique_records().collect::<Vec<_>>();
+  let conn = unicast_udp4_socket(None)
+    .and_then(<N::UdpSocket as TryFrom<_>>::try_from)
+    .unwrap();
+
+  assert!(query_srv::<N>(&conn, &instance, &[]).await);
+  assert!(!query_srv::<N>(&conn, &instance, &known).await);
+
+  // the known answers are about to expire
+  let expiring = known
+    .iter()
+    .map(|r| ResourceRecord::new(r.name(), r.ty(), r.class(), r.ttl() / 2 - 1, r.data()))
+    .collect::<Vec<_>>();
+  assert!(query_srv::<N>(&conn, &instance, &expiring).await);
+
+  // the known answers are in a continuation of a truncated query
+  assert!(!query_srv_truncated::<N>(&conn, &instance, &known).await);
+  assert!(query_srv_truncated::<N>(&conn, &instance, &expiring).await);
+
+  // the owner names of the continuation are compressed
+  let mut compressed = known.clone();
+  compressed.sort_by_key(|record| format_smolstr!("{}", record.name()));
+  assert!(compressed.windows(2).any(|w| w[0].name() == w[1].name()));
+  assert!(!query_srv_truncated_compressed::<N>(&conn, &instance, &compressed).await);
+  assert!(query_srv_truncated_compressed::<N>(&conn, &instance, &expiring).await);
+}
+
+async fn server_known_answer_suppression<N: Net>() {
+  let name = format_smolstr!("_known{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  known_answer_suppression::<N>(&name).await;
+  serv.shutdown().await;
+}
+
+async fn sync_server_known_answer_suppression<N: Net>() {
+  let name = format_smolstr!("_known{}._tcp", fastrand::u16(..));
+  let (serv, closer) = SyncServer::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .unwrap();
+  std::thread::spawn(move || serv.run());
+
+  known_answer_suppression::<N>(&name).await;
+  closer.close();
+}
+
+/// Queries the `SRV`, `TXT` and `ANY` records of the instance of the service named `name` in
+/// a single query, and checks every record is received once, in packets of at most
+/// `max_payload_size`.
+///
+/// Returns the number of response packets.
+async fn aggregate_responses<N: Net>(name: &str, max_payload_size: usize) -> usize {
+  let instance = format_smolstr!("hostname.{name}.local.");
+  let conn = unicast_udp4_socket(None)
+    .and_then(<N::UdpSocket as TryFrom<_>>::try_from)
+    .unwrap();
+
+  let mut questions = [
+    Question::new(instance.as_str(), ResourceType::Srv, 1 | (1 << 15)),
+    Question::new(instance.as_str(), ResourceType::Txt, 1 | (1 << 15)),
+    Question::new(instance.as_str(), ResourceType::Wildcard, 1 | (1 << 15)),
+  ];
+  send_query::<N>(&conn, Flags::new(), &mut questions, &[]).await;
+
+  let mut packets = 0;
+  let mut received = Vec::new();
+  let mut buf = vec![0; 9000];
+  while let Ok(Ok((len, _))) =
+    <N::Runtime as RuntimeLite>::timeout(Duration::from_millis(500), conn.recv_from(&mut buf)).await
+  {
+    assert!(len <= max_payload_size, "packet of {len} bytes");
+    packets += 1;
+
+    let mut questions = [Question::default(); 4];
+    let mut answers = [ResourceRecord::default(); 8];
+    let mut additionals = [ResourceRecord::default(); 8];
+    let msg = Message::read(
+      &buf[..len],
+      &mut questions,
+      &mut answers,
+      &mut [],
+      &mut additionals,
+    )
+    .unwrap();
+    received.extend(
+      msg
+        .answers()
+        .iter()
+        .chain(msg.additional())
+        .map(|r| (format_smolstr!("{}", r.name()), r.ty(), r.data().to_vec())),
+    );
+  }
+
+  let mut types = received.iter().map(|(_, ty, _)| *ty).collect::<Vec<_>>();
+  types.sort_by_key(|ty| u16::from(*ty));
+  let mut expected = vec![
+    ResourceType::Srv,
+    ResourceType::Txt,
+    ResourceType::A,
+    ResourceType::AAAA,
+  ];
+  expected.sort_by_key(|ty| u16::from(*ty));
+  assert_eq!(types, expected, "received: {received:?}");
+  packets
+}
+
+async fn server_aggregate_responses<N: Net>() {
+  let name = format_smolstr!("_aggr{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+  assert_eq!(aggregate_responses::<N>(&name, 1500).await, 1);
+  serv.shutdown().await;
+
+  // the response does not fit in a single packet
+  let name = format_smolstr!("_aggr{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default().with_max_payload_size(250),
+  )
+  .await
+  .unwrap();
+  assert!(aggregate_responses::<N>(&name, 250).await > 1);
+  serv.shutdown().await;
+}
+
+async fn sync_server_aggregate_responses<N: Net>() {
+  let name = format_smolstr!("_aggr{}._tcp", fastrand::u16(..));
+  let (serv, closer) = SyncServer::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .unwrap();
+  std::thread::spawn(move || serv.run());
+  assert_eq!(aggregate_responses::<N>(&name, 1500).await, 1);
+  closer.close();
+
+  let name = format_smolstr!("_aggr{}._tcp", fastrand::u16(..));
+  let (serv, closer) = SyncServer::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default().with_max_payload_size(250),
+  )
+  .unwrap();
+  std::thread::spawn(move || serv.run());
+  assert!(aggregate_responses::<N>(&name, 250).await > 1);
+  closer.close();
+}
+
+/// Returns a socket bound to the mDNS port on the address of the default multicast interface,
+/// which receives the unicast responses sent to the mDNS port, but not the multicast ones.
+fn unicast_listener<N: Net>() -> N::UdpSocket {
+  let route = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+  route.connect((IPV4_MDNS, MDNS_PORT)).unwrap();
+  let local = route.local_addr().unwrap().ip();
+
+  let sock = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+  sock.set_reuse_address(true).unwrap();
+  #[cfg(unix)]
+  sock.set_reuse_port(true).unwrap();
+  sock
+    .bind(&std::net::SocketAddr::new(local, MDNS_PORT).into())
+    .unwrap();
+  sock.set_nonblocking(true).unwrap();
+  <N::UdpSocket as TryFrom<_>>::try_from(std::net::UdpSocket::from(sock)).unwrap()
+}
+
+/// Returns the ID of the first response with the `SRV` record of `instance` received
+/// within `timeout`.
+async fn recv_srv_response<N: Net>(
+  conn: &N::UdpSocket,
+  instance: &str,
+  timeout: Duration,
+) -> Option<u16> {
+  let mut buf = vec![0; 9000];
+  let fut = async {
+    loop {
+      let (len, _) = conn.recv_from(&mut buf).await.unwrap();
+      let mut answers = [ResourceRecord::default(); 16];
+      let mut additionals = [ResourceRecord::default(); 16];
+      let Ok(msg) = Message::read(
+        &buf[..len],
+        &mut [],
+        &mut answers,
+        &mut [],
+        &mut additionals,
+      ) else {
+        continue;
+      };
+      if msg.flags().qr() != MessageType::Reply {
+        continue;
+      }
+
+      let mut records = msg.answers().iter().chain(msg.additional());
+      if records.any(|r| r.ty() == ResourceType::Srv && format_smolstr!("{}", r.name()) == instance)
+      {
+        return msg.id();
+      }
+    }
+  };
+
+  <N::Runtime as RuntimeLite>::timeout(timeout, fut)
+    .await
+    .ok()
+}
+
+async fn response_routing<N: Net>(name: &str) {
+  const ID: u16 = 0x1234;
+  let instance = format_smolstr!("hostname.{name}.local");
+  let group = announcement_listener::<N>();
+  let querier = unicast_listener::<N>();
+  let query = |unicast: bool| {
+    let mut class = 1;
+    if unicast {
+      class |= 1 << 15;
+    }
+    let mut questions = [Question::new(instance.as_str(), ResourceType::Srv, class)];
+    let msg = Message::new(ID, Flags::new(), &mut questions, &mut [], &mut [], &mut []);
+    let mut buf = vec![0; msg.space_needed()];
+    let len = msg.write(&mut buf).unwrap();
+    buf.truncate(len);
+    buf
+  };
+  let wait = Duration::from_millis(300);
+
+  // wait for the end of the announcements, and ignore them, a record is not multicast
+  // again within one second, see RFC 6762, section 6
+  <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(2200)).await;
+  while recv_srv_response::<N>(&group, &instance, Duration::from_millis(10))
+    .await
+    .is_some()
+  {}
+
+  // a multicast question is answered to the multicast group
+  querier
+    .send_to(&query(false), (IPV4_MDNS, MDNS_PORT))
+    .await
+    .unwrap();
+  assert_eq!(
+    recv_srv_response::<N>(&group, &instance, wait).await,
+    Some(0)
+  );
+  assert_eq!(
+    recv_srv_response::<N>(&querier, &instance, wait).await,
+    None
+  );
+
+  // the record has just been multicast, a unicast question is answered to the querier only
+  querier
+    .send_to(&query(true), (IPV4_MDNS, MDNS_PORT))
+    .await
+    .unwrap();
+  assert_eq!(
+    recv_srv_response::<N>(&querier, &instance, wait).await,
+    Some(ID)
+  );
+  assert_eq!(recv_srv_response::<N>(&group, &instance, wait).await, None);
+
+  // a quarter of the TTL has elapsed since the record was multicast
+  <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(1100)).await;
+  querier
+    .send_to(&query(true), (IPV4_MDNS, MDNS_PORT))
+    .await
+    .unwrap();
+  assert_eq!(
+    recv_srv_response::<N>(&group, &instance, wait).await,
+    Some(0)
+  );
+}
+
+/// A service whose records are multicast again after one second, a quarter of their TTL.
+fn make_short_lived_service(name: &str) -> Service {
+  ServiceBuilder::new("hostname".into(), name.into())
+    .with_domain("local.".into())
+    .with_hostname("testhost.".into())
+    .with_port(80)
+    .with_ttl(4)
+    .with_ip("192.168.0.42".parse().unwrap())
+    .finalize()
+    .unwrap()
+}
+
+async fn server_response_routing<N: Net>() {
+  let name = format_smolstr!("_route{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Service>::new(make_short_lived_service(&name), ServerOptions::default())
+    .await
+    .unwrap();
+
+  response_routing::<N>(&name).await;
+  serv.shutdown().await;
+}
+
+async fn sync_server_response_routing<N: Net>() {
+  let name = format_smolstr!("_route{}._tcp", fastrand::u16(..));
+  let (serv, closer) =
+    SyncServer::new(make_short_lived_service(&name), ServerOptions::default()).unwrap();
+  std::thread::spawn(move || serv.run());
+
+  response_routing::<N>(&name).await;
+  closer.close();
+}
+
+/// Multicasts the `SRV` record of the service from another host, and checks the server
+/// does not multicast its own copy, unless the other host gave a shorter TTL.
+async fn duplicate_answer_suppression<N: Net>(name: &str) {
+  let service = make_service_with_service_name(name);
+  let instance = format_smolstr!("hostname.{name}.local");
+  let srv = service
+    .unique_records()
+    .find(|r| r.ty() == ResourceType::Srv)
+    .unwrap();
+  let group = announcement_listener::<N>();
+  let other = unicast_listener::<N>();
+  let wait = Duration::from_millis(300);
+  let multicast = |msg: Message<'_, '_>| {
+    let mut buf = vec![0; msg.space_needed()];
+    let len = msg.write(&mut buf).unwrap();
+    buf.truncate(len);
+    buf
+  };
+  let mut flags = Flags::new();
+  flags.set_qr(MessageType::Reply).set_authoritative(true);
+
+  // wait for the end of the announcements, and ignore them
+  <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(2200)).await;
+  while recv_srv_response::<N>(&group, &instance, Duration::from_millis(10))
+    .await
+    .is_some()
+  {}
+
+  let mut questions = [Question::new(instance.as_str(), ResourceType::Srv, 1)];
+  let query = multicast(Message::new(
+    0,
+    Flags::new(),
+    &mut questions,
+    &mut [],
+    &mut [],
+    &mut [],
+  ));
+
+  for (ttl, suppressed) in [(srv.ttl() / 2, false), (srv.ttl(), true)] {
+    let mut answers = [ResourceRecord::new(
+      srv.name(),
+      srv.ty(),
+      srv.class(),
+      ttl,
+      srv.data(),
+    )];
+    let response = multicast(Message::new(
+      0,
+      flags,
+      &mut [],
+      &mut answers,
+      &mut [],
+      &mut [],
+    ));
+    other
+      .send_to(&response, (IPV4_MDNS, MDNS_PORT))
+      .await
+      .unwrap();
+    // the response of the other host
+    assert_eq!(
+      recv_srv_response::<N>(&group, &instance, wait).await,
+      Some(0)
+    );
+
+    other.send_to(&query, (IPV4_MDNS, MDNS_PORT)).await.unwrap();
+    assert_eq!(
+      recv_srv_response::<N>(&group, &instance, wait)
+        .await
+        .is_none(),
+      suppressed
+    );
+
+    // our own answer is not multicast again within one second
+    <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(1100)).await;
+  }
+}
+
+async fn server_duplicate_answer_suppression<N: Net>() {
+  let name = format_smolstr!("_dup{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  duplicate_answer_suppression::<N>(&name).await;
+  serv.shutdown().await;
+}
+
+async fn sync_server_duplicate_answer_suppression<N: Net>() {
+  let name = format_smolstr!("_dup{}._tcp", fastrand::u16(..));
+  let (serv, closer) = SyncServer::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .unwrap();
+  std::thread::spawn(move || serv.run());
+
+  duplicate_answer_suppression::<N>(&name).await;
+  closer.close();
+}
+
+/// Asks for the shared records of the service in two queries, and for its `SRV` record in a
+/// third one, and checks the shared records are answered later, in a single packet.
+async fn server_shared_response_delay<N: Net>() {
+  let name = format_smolstr!("_delay{}._tcp", fastrand::u16(..));
+  let service = make_service_with_service_name(&name);
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+  let instance = format_smolstr!("hostname.{name}.local");
+  let shared = service.shared_records().collect::<Vec<_>>();
+  let srv = service
+    .unique_records()
+    .find(|r| r.ty() == ResourceType::Srv)
+    .unwrap();
+  let group = announcement_listener::<N>();
+  let querier = unicast_listener::<N>();
+
+  // wait for the end of the announcements, and ignore them
+  <N::Runtime as RuntimeLite>::sleep(Duration::from_millis(2200)).await;
+  while recv_srv_response::<N>(&group, &instance, Duration::from_millis(10))
+    .await
+    .is_some()
+  {}
+
+  let start = Instant::now();
+  for record in shared.iter().chain([&srv]) {
+    send_query::<N>(
+      &querier,
+      Flags::new(),
+      &mut [Question::new(record.name(), record.ty(), 1)],
+      &[],
+    )
+    .await;
+  }
+
+  let same = |a: &ResourceRecord<'_>, b: &ResourceRecord<'_>| {
+    a.ty() == b.ty()
+      && a.data() == b.data()
+      && format_smolstr!("{}", a.name()).trim_end_matches('.')
+        == format_smolstr!("{}", b.name()).trim_end_matches('.')
+  };
+  let mut buf = vec![0; 9000];
+  let mut srv_received = false;
+  let fut = async {
+    loop {
+      let (len, _) = group.recv_from(&mut buf).await.unwrap();
+      let mut answers = [ResourceRecord::default(); 16];
+      let Ok(msg) = Message::read(&buf[..len], &mut [], &mut answers, &mut [], &mut []) else {
+        continue;
+      };
+      if msg.flags().qr() != MessageType::Reply {
+        continue;
+      }
+
+      let has = |record: &ResourceRecord<'_>| msg.answers().iter().any(|r| same(r, record));
+      srv_received |= has(&srv);
+      match shared.iter().filter(|record| has(record)).count() {
+        0 => continue,
+        n => return (n, srv_received, start.elapsed()),
+      }
+    }
+  };
+  let (answered, srv_first, elapsed) =
+    <N::Runtime as RuntimeLite>::timeout(Duration::from_secs(1), fut)
+      .await
+      .unwrap();
+
+  // the answers to both questions are aggregated in one packet
+  assert_eq!(answered, shared.len());
+  // the unique record is answered at once
+  assert!(srv_first);
+  assert!(elapsed >= SHARED_RESPONSE_DELAY_MIN, "{elapsed:?}");
+  serv.shutdown().await;
+}
+
+/// Sends a one-shot query for the `SRV` record of `instance` from an ephemeral port, and
+/// checks the response is a conventional unicast DNS response.
+async fn legacy_unicast<N: Net>(name: &str) {
+  const ID: u16 = 0x4242;
+  let instance = format_smolstr!("hostname.{name}.local");
+  let conn = unicast_udp4_socket(None)
+    .and_then(<N::UdpSocket as TryFrom<_>>::try_from)
+    .unwrap();
+
+  let mut questions = [Question::new(instance.as_str(), ResourceType::Srv, 1)];
+  let msg = Message::new(ID, Flags::new(), &mut questions, &mut [], &mut [], &mut []);
+  let mut buf = vec![0; msg.space_needed()];
+  let len = msg.write(&mut buf).unwrap();
+  conn
+    .send_to(&buf[..len], (IPV4_MDNS, MDNS_PORT))
+    .await
+    .unwrap();
+
+  let mut buf = vec![0; 9000];
+  let (len, _) =
+    <N::Runtime as RuntimeLite>::timeout(Duration::from_secs(1), conn.recv_from(&mut buf))
+      .await
+      .expect("no legacy unicast response")
+      .unwrap();
+
+  let mut questions = [Question::default(); 1];
+  let mut answers = [ResourceRecord::default(); 8];
+  let mut additionals = [ResourceRecord::default(); 8];
+  let msg = Message::read(
+    &buf[..len],
+    &mut questions,
+    &mut answers,
+    &mut [],
+    &mut additionals,
+  )
+  .unwrap();
+
+  assert_eq!(msg.id(), ID);
+  assert_eq!(msg.flags().qr(), MessageType::Reply);
+  assert_eq!(msg.questions().len(), 1);
+  assert_eq!(format_smolstr!("{}", msg.questions()[0].name()), instance);
+  assert_eq!(msg.questions()[0].ty(), ResourceType::Srv);
+
+  assert!(
+    msg
+      .answers()
+      .iter()
+      .any(|r| r.ty() == ResourceType::Srv && format_smolstr!("{}", r.name()) == instance)
+  );
+  for record in msg.answers().iter().chain(msg.additional()) {
+    assert!(record.ttl() <= LEGACY_UNICAST_TTL, "{record:?}");
+    assert_eq!(record.class() & (1 << 15), 0, "{record:?}");
+  }
+}
+
+async fn server_legacy_unicast<N: Net>() {
+  let name = format_smolstr!("_legacy{}._tcp", fastrand::u16(..));
+  let serv = Server::<N, Service>::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .await
+  .unwrap();
+
+  legacy_unicast::<N>(&name).await;
+  serv.shutdown().await;
+}
+
+async fn sync_server_legacy_unicast<N: Net>() {
+  let name = format_smolstr!("_legacy{}._tcp", fastrand::u16(..));
+  let (serv, closer) = SyncServer::new(
+    make_service_with_service_name(&name),
+    ServerOptions::default(),
+  )
+  .unwrap();
+  std::thread::spawn(move || serv.run());
+
+  legacy_unicast::<N>(&name).await;
+  closer.close();
+}
+
 #[allow(clippy::extra_unused_type_parameters)]
 async fn sync_server_start_stop<N: Net>() {
   let s = make_service();
@@ -119,7 +1051,7 @@ async fn sync_server_start_stop<N: Net>() {
       .unwrap(),]
   );
   assert_eq!(s.port(), 80);
-  assert_eq!(s.txt_records(), &["Local web server"]);
+  assert_eq!(s.txt_records().collect::<Vec<_>>(), [b"Local web server"]);
 
   closer.close();
 }
@@ -162,7 +1094,7 @@ async fn sync_server_lookup<N: Net>() {
             .parse::<Ipv6Addr>()
             .unwrap()
         );
-        assert_eq!(ent.txt()[0].as_str(), "Local web server");
+        assert_eq!(ent.txt().next().unwrap(), b"Local web server");
         got_response = true;
       }
 
@@ -181,7 +1113,24 @@ async fn sync_server_lookup<N: Net>() {
 test_suites!(tokio {
   server_start_stop,
   server_lookup,
+  server_name_conflict,
+  server_name_lost,
+  server_announce,
+  server_goodbye,
+  server_known_answer_suppression,
+  server_aggregate_responses,
+  server_response_routing,
+  server_legacy_unicast,
+  server_duplicate_answer_suppression,
+  server_shared_response_delay,
   sync_server_lookup,
+  sync_server_announce,
+  sync_server_goodbye,
+  sync_server_known_answer_suppression,
+  sync_server_aggregate_responses,
+  sync_server_response_routing,
+  sync_server_legacy_unicast,
+  sync_server_duplicate_answer_suppression,
   sync_server_start_stop,
 });
 
@@ -189,7 +1138,24 @@ test_suites!(tokio {
 test_suites!(smol {
   server_start_stop,
   server_lookup,
+  server_name_conflict,
+  server_name_lost,
+  server_announce,
+  server_goodbye,
+  server_known_answer_suppression,
+  server_aggregate_responses,
+  server_response_routing,
+  server_legacy_unicast,
+  server_duplicate_answer_suppression,
+  server_shared_response_delay,
   sync_server_lookup,
+  sync_server_announce,
+  sync_server_goodbye,
+  sync_server_known_answer_suppression,
+  sync_server_aggregate_responses,
+  sync_server_response_routing,
+  sync_server_legacy_unicast,
+  sync_server_duplicate_answer_suppression,
   sync_server_start_stop,
 });
 
@@ -197,6 +1163,23 @@ test_suites!(smol {
 test_suites!(async_std {
   server_start_stop,
   server_lookup,
+  server_name_conflict,
+  server_name_lost,
+  server_announce,
+  server_goodbye,
+  server_known_answer_suppression,
+  server_aggregate_responses,
+  server_response_routing,
+  server_legacy_unicast,
+  server_duplicate_answer_suppression,
+  server_shared_response_delay,
   sync_server_lookup,
+  sync_server_announce,
+  sync_server_goodbye,
+  sync_server_known_answer_suppression,
+  sync_server_aggregate_responses,
+  sync_server_response_routing,
+  sync_server_legacy_unicast,
+  sync_server_duplicate_answer_suppression,
   sync_server_start_stop,
 });
@@ -8,7 +8,7 @@ use mdns_proto::{
   proto::{Cursor, Deserialize, Label, ResourceType},
 };
 
-use crate::{tests::make_service, worksteal::Zone};
+use crate::{service::ServiceBuilder, tests::make_service, worksteal::Zone};
 
 macro_rules! test_suites {
   ($runtime:ident {
@@ -94,6 +94,12 @@ async fn instance_addr_srv() {
   matches!(recs[2].ty(), ResourceType::AAAA);
 
   assert_eq!(&recs[0].data()[4..6], s.port().to_be_bytes());
+
+  // the addresses are owned by the target host of the SRV record, not by the instance
+  assert_eq!(recs[1].ty(), ResourceType::A);
+  assert_eq!(recs[1].name(), Label::from("testhost."));
+  assert_eq!(recs[2].ty(), ResourceType::AAAA);
+  assert_eq!(recs[2].name(), Label::from("testhost."));
 }
 
 async fn instance_addr_a() {
@@ -156,11 +162,51 @@ async fn instance_addr_txt() {
   let txt = Txt::from_bytes(recs[0].data());
 
   assert_eq!(
-    txt.strings().next().unwrap().unwrap().to_string(),
-    s.txt_records()[0].as_str()
+    txt.strings().next().unwrap().unwrap().as_bytes(),
+    s.txt_records().next().unwrap()
   );
 }
 
+async fn instance_addr_empty_txt() {
+  let s = ServiceBuilder::new("hostname".into(), "_http._tcp".into())
+    .with_domain("local.".into())
+    .with_hostname("testhost.".into())
+    .with_port(80)
+    .with_ip("192.168.0.42".parse().unwrap())
+    .finalize()
+    .unwrap();
+
+  let recs = s
+    .answers("hostname._http._tcp.local.".into(), ResourceType::Txt)
+    .await
+    .unwrap()
+    .collect::<Vec<_>>();
+  assert_eq!(recs.len(), 1, "bad: {recs:?}");
+
+  // a single empty character-string
+  assert_eq!(recs[0].data(), [0]);
+}
+
+async fn subtype_ptr_case() {
+  let s = ServiceBuilder::new("hostname".into(), "_http._tcp".into())
+    .with_domain("local.".into())
+    .with_hostname("testhost.".into())
+    .with_port(80)
+    .with_ip("192.168.0.42".parse().unwrap())
+    .with_subtype("_printer")
+    .finalize()
+    .unwrap();
+
+  // names are case insensitive, see RFC 6762, section 16
+  let recs = s
+    .answers("_PRINTER._sub._HTTP._tcp.local.".into(), ResourceType::Ptr)
+    .await
+    .unwrap()
+    .collect::<Vec<_>>();
+  assert!(!recs.is_empty(), "bad: {recs:?}");
+  assert_eq!(recs[0].ty(), ResourceType::Ptr);
+}
+
 async fn service_enum_ptr() {
   let s = make_service();
 
@@ -188,6 +234,8 @@ test_suites!(tokio {
   instance_addr_a,
   instance_addr_aaaa,
   instance_addr_txt,
+  instance_addr_empty_txt,
+  subtype_ptr_case,
   service_enum_ptr,
 });
 
@@ -200,6 +248,8 @@ test_suites!(smol {
   instance_addr_a,
   instance_addr_aaaa,
   instance_addr_txt,
+  instance_addr_empty_txt,
+  subtype_ptr_case,
   service_enum_ptr,
 });
 
@@ -212,5 +262,7 @@ test_suites!(async_std {
   instance_addr_a,
   instance_addr_aaaa,
   instance_addr_txt,
+  instance_addr_empty_txt,
+  subtype_ptr_case,
   service_enum_ptr,
 });
@@ -0,0 +1,117 @@
+use core::{ops::Add, time::Duration};
+
+use dns_protocol::ResourceRecord;
+
+use super::utils::TOP_BIT;
+
+/// The interval between the first two announcements.
+///
+/// RFC 6762, section 8.3: the Multicast DNS responder MUST send at least two
+/// unsolicited responses, one second apart.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of unsolicited responses sent to announce the records.
+pub const ANNOUNCE_COUNT: u8 = 2;
+
+/// A sans-I/O announcer.
+///
+/// ```text
+/// RFC 6762, section 8.3.  Announcing
+///
+/// The second startup step is that the Multicast DNS responder MUST send
+/// an unsolicited Multicast DNS response containing, in the Answer
+/// Section, all of its newly registered resource records (both shared
+/// records, and unique records that have completed the probing step).
+/// If there are shared records, or unique records whose probing has
+/// completed, the Multicast DNS responder MUST send at least two
+/// unsolicited responses, one second apart.
+/// ```
+///
+/// The announcer only schedules the announcements, the response is built by the caller,
+/// with the cache-flush bit set on the unique records, see [`cache_flush`].
+///
+/// `I` is the instant type of the clock driving the announcer, e.g. `std::time::Instant`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Announcer<I> {
+  sent: u8,
+  deadline: Option<I>,
+}
+
+impl<I> Announcer<I>
+where
+  I: Copy + Ord + Add<Duration, Output = I>,
+{
+  /// Creates a new announcer, which sends its first announcement at `start`.
+  #[inline]
+  pub const fn new(start: I) -> Self {
+    Self {
+      sent: 0,
+      deadline: Some(start),
+    }
+  }
+
+  /// Restarts announcing from scratch, e.g. after the records changed, the first
+  /// announcement will be sent at `start`.
+  ///
+  /// RFC 6762, section 8.4: whenever a host has a resource record with new data, it
+  /// SHOULD announce it as described in section 8.3.
+  #[inline]
+  pub fn restart(&mut self, start: I) {
+    *self = Self::new(start);
+  }
+
+  /// Returns `true` if all the announcements have been sent.
+  #[inline]
+  pub const fn is_done(&self) -> bool {
+    self.deadline.is_none()
+  }
+
+  /// Returns the instant at which [`Announcer::handle_timeout`] should be called next.
+  #[inline]
+  pub const fn poll_timeout(&self) -> Option<I> {
+    self.deadline
+  }
+
+  /// Drives the timers of the announcer.
+  ///
+  /// Returns `true` if an announcement should be sent now. The interval between two
+  /// announcements starts at [`ANNOUNCE_INTERVAL`], and doubles after every announcement.
+  pub fn handle_timeout(&mut self, now: I) -> bool {
+    match self.deadline {
+      Some(deadline) if deadline <= now => {}
+      _ => return false,
+    }
+
+    self.sent += 1;
+    self.deadline =
+      (self.sent < ANNOUNCE_COUNT).then(|| now + ANNOUNCE_INTERVAL * (1 << (self.sent - 1)));
+    true
+  }
+}
+
+/// Returns `record` with the cache-flush bit set.
+///
+/// RFC 6762, section 10.2: the cache-flush bit is set on unique records in responses,
+/// to tell the neighboring hosts that this is not a shared record type, and older
+/// records with the same name, rrtype, and rrclass should be flushed.
+#[inline]
+pub fn cache_flush(record: ResourceRecord<'_>) -> ResourceRecord<'_> {
+  ResourceRecord::new(
+    record.name(),
+    record.ty(),
+    record.class() | TOP_BIT,
+    record.ttl(),
+    record.data(),
+  )
+}
+
+/// Returns `record` with a TTL of zero, to announce that it is no longer valid.
+///
+/// RFC 6762, section 10.1: in the case where a host knows that certain resource record
+/// data is about to become invalid (for example, when the host is undergoing a clean
+/// shutdown), the host should send an unsolicited Multicast DNS response packet, giving
+/// the same resource record name, rrtype, rrclass, and rdata, but an RR TTL of zero.
+#[inline]
+pub fn goodbye(record: ResourceRecord<'_>) -> ResourceRecord<'_> {
+  ResourceRecord::new(record.name(), record.ty(), record.class(), 0, record.data())
+}
@@ -0,0 +1,330 @@
+use core::{marker::PhantomData, ops::Add, time::Duration};
+use std::{
+  string::{String, ToString},
+  vec::Vec,
+};
+
+use dns_protocol::{Label, ResourceRecord, ResourceType};
+
+use super::{
+  Pool,
+  rdata::Rdata,
+  utils::{TOP_BIT, label_eq},
+};
+
+/// The fractions of the TTL, in percent, at which a record is refreshed.
+///
+/// RFC 6762, section 5.2: the querier should plan to retransmit its query to refresh the
+/// record at 80% of the record lifetime, and then if no answer is received, at 85%, 90%,
+/// and 95%.
+pub const REFRESH_PERCENTS: [u8; 4] = [80, 85, 90, 95];
+
+/// How long a record is kept after a goodbye, or after a newer record of the same set is
+/// received with the cache-flush bit set.
+///
+/// RFC 6762, sections 10.1 and 10.2: the record is not deleted at once, its TTL is set to
+/// one second.
+pub const FLUSH_DELAY: Duration = Duration::from_secs(1);
+
+/// A record cache using a slab for the entries.
+#[cfg(feature = "slab")]
+#[cfg_attr(docsrs, doc(cfg(feature = "slab")))]
+pub type SlabCache<I> = Cache<I, slab::Slab<Entry<I>>>;
+
+/// A cached record.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Entry<I> {
+  name: String,
+  ty: ResourceType,
+  class: u16,
+  data: Vec<u8>,
+  ttl: u32,
+  received: I,
+  refreshes: u8,
+}
+
+impl<I> Entry<I>
+where
+  I: Copy + Ord + Add<Duration, Output = I>,
+{
+  /// Returns the name of the record.
+  #[inline]
+  pub fn name(&self) -> Label<'_> {
+    Label::from(self.name.as_str())
+  }
+
+  /// Returns the type of the record.
+  #[inline]
+  pub const fn ty(&self) -> ResourceType {
+    self.ty
+  }
+
+  /// Returns the class of the record, without the cache-flush bit.
+  #[inline]
+  pub const fn class(&self) -> u16 {
+    self.class
+  }
+
+  /// Returns the data of the record, with its names uncompressed.
+  #[inline]
+  pub fn data(&self) -> &[u8] {
+    &self.data
+  }
+
+  /// Returns the TTL of the record, in seconds, from the instant it was received.
+  #[inline]
+  pub const fn ttl(&self) -> u32 {
+    self.ttl
+  }
+
+  /// Returns the instant at which the record was last received.
+  #[inline]
+  pub const fn received(&self) -> I {
+    self.received
+  }
+
+  /// Returns the instant at which the record expires.
+  #[inline]
+  pub fn expires(&self) -> I {
+    self.received + Duration::from_secs(self.ttl as u64)
+  }
+
+  /// Returns the record, with its original TTL.
+  #[inline]
+  pub fn record(&self) -> ResourceRecord<'_> {
+    ResourceRecord::new(self.name(), self.ty, self.class, self.ttl, &self.data)
+  }
+
+  /// Returns `true` if the record has the same name, type and class as `record`.
+  fn same_set(&self, record: &ResourceRecord<'_>) -> bool {
+    self.ty == record.ty()
+      && self.class == record.class() & !TOP_BIT
+      && label_eq(&self.name(), &record.name())
+  }
+
+  /// Returns `true` if the record has the same data as `data`.
+  fn same_data(&self, data: &Rdata<'_>) -> bool {
+    Rdata::new(&[], &self.record()) == *data
+  }
+
+  /// Returns the instant of the next refresh, or of the expiration.
+  fn deadline(&self) -> I {
+    let lifetime = Duration::from_secs(self.ttl as u64);
+    match REFRESH_PERCENTS.get(self.refreshes as usize) {
+      Some(percent) => self.received + lifetime * *percent as u32 / 100,
+      None => self.received + lifetime,
+    }
+  }
+
+  /// Expires the record in [`FLUSH_DELAY`], without refreshing it.
+  fn flush(&mut self, now: I) {
+    self.received = now;
+    self.ttl = FLUSH_DELAY.as_secs() as u32;
+    self.refreshes = REFRESH_PERCENTS.len() as u8;
+  }
+}
+
+/// Events emitted by a [`Cache`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, derive_more::IsVariant)]
+pub enum CacheEvent<'a, I> {
+  /// The record is about to expire, a query for its name, type and class should be sent
+  /// if the record is still of interest.
+  Refresh(&'a Entry<I>),
+  /// The record expired, and was removed from the cache.
+  Expired(Entry<I>),
+}
+
+/// A sans-I/O cache of the records received by a querier.
+///
+/// ```text
+/// RFC 6762, section 5.2.  Continuous Multicast DNS Querying
+///
+/// The querier should plan to retransmit its query at 80% of the record
+/// lifetime, and then if no answer is received, at 85%, 90%, and 95%.
+/// If an answer is received, then the remaining TTL of the record is
+/// reset, and the retransmission schedule is restarted.  If no answer
+/// is received by 100% of the record lifetime, the record is deleted
+/// from the cache.
+/// ```
+///
+/// The records are keyed by name, type and class, several records with different data
+/// may share the same key. The names are compared case-insensitively, and the
+/// cache-flush bit of the class is ignored.
+///
+/// `I` is the instant type of the clock driving the cache, e.g. `std::time::Instant`.
+pub struct Cache<I, P> {
+  entries: P,
+  _i: PhantomData<I>,
+}
+
+impl<I, P> Default for Cache<I, P>
+where
+  I: Copy + Ord + Add<Duration, Output = I>,
+  P: Pool<Entry<I>>,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<I, P> Cache<I, P>
+where
+  I: Copy + Ord + Add<Duration, Output = I>,
+  P: Pool<Entry<I>>,
+{
+  /// Creates a new, empty cache.
+  pub fn new() -> Self {
+    Self {
+      entries: P::new(),
+      _i: PhantomData,
+    }
+  }
+
+  /// Creates a new, empty cache with a specific capacity.
+  pub fn with_capacity(capacity: usize) -> Result<Self, P::Error> {
+    Ok(Self {
+      entries: P::with_capacity(capacity)?,
+      _i: PhantomData,
+    })
+  }
+
+  /// Returns the number of cached records.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  /// Returns `true` if the cache is empty.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Returns an iterator over the cached records.
+  pub fn iter(&self) -> impl Iterator<Item = &Entry<I>> {
+    self.entries.iter().map(|(_, entry)| entry)
+  }
+
+  /// Returns the cached records with the given name, type and class.
+  pub fn get<'a>(
+    &'a self,
+    name: Label<'_>,
+    ty: ResourceType,
+    class: u16,
+  ) -> impl Iterator<Item = &'a Entry<I>> {
+    let key = ResourceRecord::new(name, ty, class, 0, &[]);
+    self.iter().filter(move |entry| entry.same_set(&key))
+  }
+
+  /// Adds `record`, received at `now`, to the cache, or renews the TTL of the cached record
+  /// with the same name, type, class and data.
+  ///
+  /// A record with a TTL of zero is a goodbye, the cached record expires in
+  /// [`FLUSH_DELAY`], see RFC 6762, section 10.1. If the cache-flush bit of `record` is set,
+  /// the other cached records with the same name, type and class received more than
+  /// [`FLUSH_DELAY`] ago expire in [`FLUSH_DELAY`], see RFC 6762, section 10.2.
+  ///
+  /// `record` must be read from the raw `packet`, the names inside its data may be
+  /// compressed, see [`Rdata`]. The record is cached with its names uncompressed, so the
+  /// cached data can be read on its own.
+  ///
+  /// Returns `true` if the record was not cached. Returns an error if the cache cannot hold
+  /// any more records.
+  pub fn insert(
+    &mut self,
+    packet: &[u8],
+    record: &ResourceRecord<'_>,
+    now: I,
+  ) -> Result<bool, P::Error> {
+    let data = Rdata::new(packet, record);
+
+    if record.class() & TOP_BIT != 0 {
+      self
+        .entries
+        .iter()
+        .filter(|(_, entry)| {
+          entry.same_set(record) && !entry.same_data(&data) && entry.received + FLUSH_DELAY <= now
+        })
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .for_each(|key| {
+          if let Some(entry) = self.entries.get_mut(key) {
+            entry.flush(now);
+          }
+        });
+    }
+
+    let cached = self
+      .entries
+      .iter()
+      .find_map(|(key, entry)| (entry.same_set(record) && entry.same_data(&data)).then_some(key));
+
+    match cached.and_then(|key| self.entries.get_mut(key)) {
+      Some(entry) if record.ttl() == 0 => {
+        entry.flush(now);
+        Ok(false)
+      }
+      Some(entry) => {
+        entry.received = now;
+        entry.ttl = record.ttl();
+        entry.refreshes = 0;
+        Ok(false)
+      }
+      // a goodbye for a record we do not know
+      None if record.ttl() == 0 => Ok(false),
+      None => {
+        self.entries.insert(Entry {
+          name: record.name().to_string(),
+          ty: record.ty(),
+          class: record.class() & !TOP_BIT,
+          data: data.to_vec(),
+          ttl: record.ttl(),
+          received: now,
+          refreshes: 0,
+        })?;
+        Ok(true)
+      }
+    }
+  }
+
+  /// Removes all the cached records.
+  pub fn clear(&mut self) {
+    loop {
+      let key = self.entries.iter().map(|(key, _)| key).next();
+      match key {
+        Some(key) => {
+          self.entries.try_remove(key);
+        }
+        None => return,
+      }
+    }
+  }
+
+  /// Returns the instant at which [`Cache::handle_timeout`] should be called next.
+  pub fn poll_timeout(&self) -> Option<I> {
+    self.iter().map(Entry::deadline).min()
+  }
+
+  /// Drives the timers of the cache.
+  ///
+  /// Returns the next event due at `now`, this method should be called until it returns
+  /// `None`.
+  pub fn handle_timeout(&mut self, now: I) -> Option<CacheEvent<'_, I>> {
+    let key = self
+      .entries
+      .iter()
+      .filter(|(_, entry)| entry.deadline() <= now)
+      .min_by_key(|(_, entry)| entry.deadline())
+      .map(|(key, _)| key)?;
+
+    let entry = self.entries.get(key)?;
+    if entry.refreshes as usize >= REFRESH_PERCENTS.len() {
+      return self.entries.try_remove(key).map(CacheEvent::Expired);
+    }
+
+    let entry = self.entries.get_mut(key)?;
+    entry.refreshes += 1;
+    Some(CacheEvent::Refresh(entry))
+  }
+}
@@ -1,12 +1,173 @@
-use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::{
+  net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+  ops::Add,
+  time::Duration,
+};
 
-use dns_protocol::{Cursor, Deserialize, Label, Message, Question, ResourceType};
+use dns_protocol::{Label, Message, Question, ResourceRecord, ResourceType};
 
 use super::{
   Srv, Txt,
   error::{ProtoError, proto_error_parse},
+  rdata::Rdata,
+  utils::TOP_BIT,
 };
 
+/// The interval between the first two queries of a continuous query.
+///
+/// RFC 6762, section 5.2: the interval between the first two queries MUST be at least
+/// one second.
+pub const QUERY_INTERVAL_MIN: Duration = Duration::from_secs(1);
+
+/// The maximum interval between two queries of a continuous query.
+///
+/// RFC 6762, section 5.2: when the interval between queries reaches or exceeds 60
+/// minutes, a querier MAY cap the interval to a maximum of 60 minutes.
+pub const QUERY_INTERVAL_MAX: Duration = Duration::from_secs(60 * 60);
+
+/// A sans-I/O schedule of the queries of a continuous query.
+///
+/// ```text
+/// RFC 6762, section 5.2.  Continuous Multicast DNS Querying
+///
+/// When retransmitting Multicast DNS queries to implement this kind of
+/// continuous monitoring, the interval between the first two queries
+/// MUST be at least one second, the intervals between successive queries
+/// MUST increase by at least a factor of two, and the querier MUST
+/// implement Known-Answer Suppression, as described below in Section 7.1.
+/// ...
+/// When the interval between queries reaches or exceeds 60 minutes, a
+/// querier MAY cap the interval to a maximum of 60 minutes, and perform
+/// subsequent queries at a steady-state rate of one query per hour.
+/// ```
+///
+/// The schedule only tells when to query, the query is built and sent by the caller.
+///
+/// `I` is the instant type of the clock driving the schedule, e.g. `std::time::Instant`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuerySchedule<I> {
+  interval: Duration,
+  deadline: I,
+}
+
+impl<I> QuerySchedule<I>
+where
+  I: Copy + Ord + Add<Duration, Output = I>,
+{
+  /// Creates a new schedule, which sends its first query at `start`.
+  #[inline]
+  pub const fn new(start: I) -> Self {
+    Self {
+      interval: QUERY_INTERVAL_MIN,
+      deadline: start,
+    }
+  }
+
+  /// Restarts the schedule from scratch, e.g. after the network changed, the first
+  /// query will be sent at `start`.
+  #[inline]
+  pub fn restart(&mut self, start: I) {
+    *self = Self::new(start);
+  }
+
+  /// Returns the interval between the next query and the one after.
+  #[inline]
+  pub const fn interval(&self) -> Duration {
+    self.interval
+  }
+
+  /// Returns the instant at which [`QuerySchedule::handle_timeout`] should be called next.
+  #[inline]
+  pub const fn poll_timeout(&self) -> I {
+    self.deadline
+  }
+
+  /// Drives the timer of the schedule.
+  ///
+  /// Returns `true` if a query should be sent now. The interval between two queries
+  /// starts at [`QUERY_INTERVAL_MIN`], and doubles after every query, up to
+  /// [`QUERY_INTERVAL_MAX`].
+  pub fn handle_timeout(&mut self, now: I) -> bool {
+    if self.deadline > now {
+      return false;
+    }
+
+    self.deadline = now + self.interval;
+    self.interval = (self.interval * 2).min(QUERY_INTERVAL_MAX);
+    true
+  }
+}
+
+/// The section of a message a record was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, derive_more::IsVariant)]
+pub enum Section {
+  /// The Answer section.
+  Answer,
+  /// The Additional section.
+  Additional,
+}
+
+/// A record of an incoming response, along with its metadata.
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'a> {
+  name: Label<'a>,
+  ttl: u32,
+  class: u16,
+  section: Section,
+  response: Response<'a>,
+}
+
+impl<'a> Record<'a> {
+  /// Returns the name of the record.
+  #[inline]
+  pub const fn name(&self) -> Label<'a> {
+    self.name
+  }
+
+  /// Returns the TTL of the record, in seconds.
+  ///
+  /// RFC 6762, section 10.1: a TTL of zero means the record is no longer valid, and
+  /// should be deleted from the cache after one second.
+  #[inline]
+  pub const fn ttl(&self) -> u32 {
+    self.ttl
+  }
+
+  /// Returns the class of the record, without the cache-flush bit.
+  #[inline]
+  pub const fn class(&self) -> u16 {
+    self.class & !TOP_BIT
+  }
+
+  /// Returns `true` if the cache-flush bit of the class is set.
+  ///
+  /// RFC 6762, section 10.2: the record is a member of a unique record set, the cached
+  /// records with the same name, rrtype, and rrclass, received more than one second
+  /// ago, should be flushed.
+  #[inline]
+  pub const fn cache_flush(&self) -> bool {
+    self.class & TOP_BIT != 0
+  }
+
+  /// Returns the section of the message the record was read from.
+  #[inline]
+  pub const fn section(&self) -> Section {
+    self.section
+  }
+
+  /// Returns the data of the record.
+  #[inline]
+  pub const fn response(&self) -> &Response<'a> {
+    &self.response
+  }
+
+  /// Consumes the record, returning its data.
+  #[inline]
+  pub const fn into_response(self) -> Response<'a> {
+    self.response
+  }
+}
+
 /// Events reacted to incoming responses
 #[derive(Debug, Clone, Copy)]
 pub enum Response<'a> {
@@ -42,6 +203,15 @@ pub enum Response<'a> {
     /// The service record
     srv: Srv<'a>,
   },
+  /// A record of another type, its data is left undecoded
+  Other {
+    /// The name of the record
+    name: Label<'a>,
+    /// The type of the record
+    ty: ResourceType,
+    /// The data of the record
+    data: &'a [u8],
+  },
 }
 
 trait Ipv6AddrExt {
@@ -67,8 +237,18 @@ impl Ipv6AddrExt for Ipv6Addr {
 pub struct Endpoint;
 
 impl Endpoint {
-  /// Prepare a question.
+  /// Prepare a question asking for the `PTR` records of `name`.
   pub fn prepare_question(name: Label<'_>, unicast_response: bool) -> Question<'_> {
+    Self::prepare_typed_question(name, ResourceType::Ptr, unicast_response)
+  }
+
+  /// Prepare a question asking for the records of type `ty` of `name`, e.g. the `A` and
+  /// `AAAA` records of a host, or all its records with [`ResourceType::Wildcard`].
+  pub fn prepare_typed_question(
+    name: Label<'_>,
+    ty: ResourceType,
+    unicast_response: bool,
+  ) -> Question<'_> {
     // RFC 6762, section 18.12.  Repurposing of Top Bit of qclass in Query
     // Section
     //
@@ -82,91 +262,117 @@ impl Endpoint {
       1
     };
 
-    Question::new(name, ResourceType::Ptr, qclass)
+    Question::new(name, ty, qclass)
   }
 
-  /// Handle an incoming message
+  /// Handle an incoming message, returning the records of its Answer and Additional
+  /// sections, in that order.
+  ///
+  /// `msg` must be read from the raw `packet`, the names inside the data of the records
+  /// may be compressed, see [`Rdata`].
   pub fn recv<'innards>(
     from: SocketAddr,
+    packet: &'innards [u8],
     msg: &Message<'_, 'innards>,
-  ) -> impl Iterator<Item = Result<Response<'innards>, ProtoError>> {
+  ) -> impl Iterator<Item = Result<Record<'innards>, ProtoError>> {
     // TODO(reddaly): Check that response corresponds to service addr?
-    msg
-      .answers()
+    let answers = msg.answers().iter().map(|record| (Section::Answer, record));
+    let additionals = msg
+      .additional()
       .iter()
-      .chain(msg.additional().iter())
-      .filter_map(move |record| {
-        let record_name = record.name();
-        match record.ty() {
-          ResourceType::A => {
-            let src = record.data();
-            let res: Result<[u8; 4], _> = src.try_into();
-
-            match res {
-              Ok(ip) => Some(Ok(Response::A {
-                name: record_name,
-                addr: Ipv4Addr::from(ip),
-              })),
-              Err(_) => {
-                #[cfg(feature = "tracing")]
-                tracing::error!("mdns endpoint: invalid A record data");
-                Some(Err(proto_error_parse("A")))
-              }
-            }
+      .map(|record| (Section::Additional, record));
+    answers.chain(additionals).map(move |(section, record)| {
+      Self::parse(from, packet, record).map(|response| Record {
+        name: record.name(),
+        ttl: record.ttl(),
+        class: record.class(),
+        section,
+        response,
+      })
+    })
+  }
+
+  /// Parses the data of a record received from `from`, read from the raw `packet`.
+  ///
+  /// A record kept in a cache, with the names inside its data uncompressed, is parsed on
+  /// its own with an empty `packet`, see [`Rdata::new`].
+  ///
+  /// The data of a record of another type than `A`, `AAAA`, `PTR`, `SRV` and `TXT` is
+  /// returned undecoded, as [`Response::Other`].
+  pub fn parse<'innards>(
+    from: SocketAddr,
+    packet: &'innards [u8],
+    record: &ResourceRecord<'innards>,
+  ) -> Result<Response<'innards>, ProtoError> {
+    let record_name = record.name();
+    match record.ty() {
+      ResourceType::A => {
+        let src = record.data();
+        let res: Result<[u8; 4], _> = src.try_into();
+
+        match res {
+          Ok(ip) => Ok(Response::A {
+            name: record_name,
+            addr: Ipv4Addr::from(ip),
+          }),
+          Err(_) => {
+            #[cfg(feature = "tracing")]
+            tracing::error!("mdns endpoint: invalid A record data");
+            Err(proto_error_parse("A"))
           }
-          ResourceType::AAAA => {
-            let src = record.data();
-            let res: Result<[u8; 16], _> = src.try_into();
-
-            match res {
-              Ok(ip) => {
-                let ip = Ipv6Addr::from(ip);
-                let mut zone = None;
-                // link-local IPv6 addresses must be qualified with a zone (interface). Zone is
-                // specific to this machine/network-namespace and so won't be carried in the
-                // mDNS message itself. We borrow the zone from the source address of the UDP
-                // packet, as the link-local address should be valid on that interface.
-                if Ipv6AddrExt::is_unicast_link_local(&ip) || ip.is_multicast_link_local() {
-                  if let SocketAddr::V6(addr) = from {
-                    zone = Some(addr.scope_id());
-                  }
-                }
-
-                Some(Ok(Response::AAAA {
-                  name: record_name,
-                  addr: ip,
-                  zone,
-                }))
-              }
-              Err(_) => {
-                #[cfg(feature = "tracing")]
-                tracing::error!("mdns endpoint: invalid AAAA record data");
-                Some(Err(proto_error_parse("AAAA")))
+        }
+      }
+      ResourceType::AAAA => {
+        let src = record.data();
+        let res: Result<[u8; 16], _> = src.try_into();
+
+        match res {
+          Ok(ip) => {
+            let ip = Ipv6Addr::from(ip);
+            let mut zone = None;
+            // link-local IPv6 addresses must be qualified with a zone (interface). Zone is
+            // specific to this machine/network-namespace and so won't be carried in the
+            // mDNS message itself. We borrow the zone from the source address of the UDP
+            // packet, as the link-local address should be valid on that interface.
+            if Ipv6AddrExt::is_unicast_link_local(&ip) || ip.is_multicast_link_local() {
+              if let SocketAddr::V6(addr) = from {
+                zone = Some(addr.scope_id());
               }
             }
-          }
-          ResourceType::Ptr => {
-            let mut label = Label::default();
-            let cursor = Cursor::new(record.data());
-            Some(label.deserialize(cursor).map(|_| Response::Ptr(label)))
-          }
-          ResourceType::Srv => {
-            let data = record.data();
 
-            Some(Srv::from_bytes(data).map(|srv| Response::Srv {
+            Ok(Response::AAAA {
               name: record_name,
-              srv,
-            }))
+              addr: ip,
+              zone,
+            })
           }
-          ResourceType::Txt => {
-            let data = record.data();
-            Some(Ok(Response::Txt {
-              name: record_name,
-              txt: Txt::from_bytes(data),
-            }))
+          Err(_) => {
+            #[cfg(feature = "tracing")]
+            tracing::error!("mdns endpoint: invalid AAAA record data");
+            Err(proto_error_parse("AAAA"))
           }
-          _ => None,
         }
-      })
+      }
+      ResourceType::Ptr => Rdata::new(packet, record)
+        .name()
+        .map(Response::Ptr)
+        .ok_or_else(|| proto_error_parse("PTR")),
+      ResourceType::Srv => Srv::from_rdata(Rdata::new(packet, record)).map(|srv| Response::Srv {
+        name: record_name,
+        srv,
+      }),
+      ResourceType::Txt => {
+        let data = record.data();
+        Ok(Response::Txt {
+          name: record_name,
+          txt: Txt::from_bytes(data),
+        })
+      }
+      ty => Ok(Response::Other {
+        name: record_name,
+        ty,
+        data: record.data(),
+      }),
+    }
   }
 }
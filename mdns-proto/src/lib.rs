@@ -8,6 +8,9 @@
 #![allow(clippy::needless_return)]
 #![allow(unreachable_code)]
 
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+extern crate alloc as std;
+
 #[cfg(feature = "slab")]
 pub use slab;
 pub use srv::*;
@@ -22,8 +25,23 @@ pub mod server;
 /// The client endpoint
 pub mod client;
 
+/// The prober for unique records
+pub mod prober;
+
+/// The announcer of newly registered records
+pub mod announcer;
+
+/// The tracker of the questions and answers of other hosts
+pub mod suppression;
+
+/// The cache of the records received by a querier
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "std", feature = "alloc"))))]
+pub mod cache;
+
 /// An implementation of the mDNS protocol
 pub mod proto {
+  pub use super::rdata::Rdata;
   pub use super::srv::Srv;
   pub use super::txt::{Str, Strings, Txt};
   pub use dns_protocol::{
@@ -32,9 +50,16 @@ pub mod proto {
   };
 }
 
+mod rdata;
 mod srv;
 mod txt;
 
+/// The comparisons of names and records, as DNS names are case insensitive
+pub mod utils;
+
+#[cfg(test)]
+mod tests;
+
 /// Internal identifier for a `Connection` currently associated with an endpoint
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct ConnectionHandle(pub usize);
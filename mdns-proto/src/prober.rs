@@ -0,0 +1,360 @@
+use core::{cmp::Ordering, ops::Add, time::Duration};
+
+use dns_protocol::{Label, Message, MessageType, Question, ResourceRecord, ResourceType};
+
+use super::{
+  rdata::Rdata,
+  utils::{CLASS_IN, TOP_BIT, label_eq, record_eq},
+};
+
+/// The interval between two consecutive probes.
+///
+/// RFC 6762, section 8.1: 250 ms after the first query, the host should
+/// send a second; then, 250 ms after that, a third.
+pub const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The number of probes sent before the names are considered to be owned.
+pub const PROBE_COUNT: u8 = 3;
+
+/// How long a prober waits before probing again after losing a simultaneous probe tiebreak.
+///
+/// RFC 6762, section 8.2: the host that lost the tiebreak should defer for one second,
+/// and then begin probing for this record again.
+pub const PROBE_DEFER: Duration = Duration::from_secs(1);
+
+/// The state of a [`Prober`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, derive_more::IsVariant)]
+pub enum ProbeState {
+  /// The prober is still sending probes.
+  Probing,
+  /// No other host claimed any of the names, the records can be announced.
+  Won,
+  /// Another host already owns at least one of the names.
+  Lost,
+  /// Another host answered with a conflicting record after the names were claimed.
+  Conflict,
+}
+
+/// Events emitted by a [`Prober`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, derive_more::IsVariant)]
+pub enum ProbeEvent {
+  /// A probe query should be sent now.
+  ///
+  /// The probe is a query built from [`questions`], with the unique records
+  /// placed in the Authority section.
+  Probe {
+    /// Whether the unicast-response bit should be set in the questions.
+    unicast_response: bool,
+  },
+  /// All the probes have been sent and nobody claimed the names.
+  Won,
+  /// Another host is probing for the same names at the same time, and won the
+  /// tiebreak, probing restarts after [`PROBE_DEFER`].
+  Deferred,
+  /// Another host already owns at least one of the names.
+  Lost {
+    /// The index of the first record whose name is owned by another host, in the
+    /// records passed to [`Prober::recv`].
+    index: usize,
+  },
+  /// Another host answered with a conflicting record after the names were claimed.
+  Conflict,
+}
+
+/// A sans-I/O prober for unique records.
+///
+/// ```text
+/// RFC 6762, section 8.1.  Probing
+///
+/// When the host is ready to send its probe query, it sends a query for
+/// all of the names it intends to claim, with the rrtype ANY, and with
+/// the proposed answers in the Authority section. 250 ms after the first
+/// query, the host should send a second; then, 250 ms after that, a third.
+/// If, by 250 ms after the third probe, no conflicting Multicast DNS
+/// responses have been received, the host may move to the next step,
+/// announcing.
+/// ```
+///
+/// The prober does not own the records, the same set of unique records must be
+/// passed to every call of [`Prober::recv`], and used to build every probe.
+///
+/// `I` is the instant type of the clock driving the prober, e.g. `std::time::Instant`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Prober<I> {
+  state: ProbeState,
+  sent: u8,
+  deadline: Option<I>,
+}
+
+impl<I> Prober<I>
+where
+  I: Copy + Ord + Add<Duration, Output = I>,
+{
+  /// Creates a new prober, which sends its first probe at `start`.
+  ///
+  /// RFC 6762 recommends delaying the first probe by a random amount of time,
+  /// uniformly distributed in the range 0-250 ms, the caller is responsible for
+  /// choosing `start` accordingly.
+  #[inline]
+  pub const fn new(start: I) -> Self {
+    Self {
+      state: ProbeState::Probing,
+      sent: 0,
+      deadline: Some(start),
+    }
+  }
+
+  /// Creates a prober for names which are already owned, e.g. when only the data of the
+  /// records changed, no probe is sent.
+  ///
+  /// RFC 6762, section 8.4: at any time, if the rdata of any of a host's Multicast DNS
+  /// records changes, the host MUST repeat the Announcing step to update neighboring
+  /// caches.
+  #[inline]
+  pub const fn claimed() -> Self {
+    Self {
+      state: ProbeState::Won,
+      sent: PROBE_COUNT,
+      deadline: None,
+    }
+  }
+
+  /// Returns the current state of the prober.
+  #[inline]
+  pub const fn state(&self) -> ProbeState {
+    self.state
+  }
+
+  /// Restarts probing from scratch, the first probe will be sent at `start`.
+  #[inline]
+  pub fn restart(&mut self, start: I) {
+    *self = Self::new(start);
+  }
+
+  /// Returns the instant at which [`Prober::handle_timeout`] should be called next.
+  #[inline]
+  pub const fn poll_timeout(&self) -> Option<I> {
+    self.deadline
+  }
+
+  /// Drives the timers of the prober.
+  ///
+  /// Returns [`ProbeEvent::Probe`] when a probe should be sent, and [`ProbeEvent::Won`]
+  /// once the last probe has been unanswered for [`PROBE_INTERVAL`].
+  pub fn handle_timeout(&mut self, now: I) -> Option<ProbeEvent> {
+    let deadline = self.deadline?;
+    if now < deadline || !self.state.is_probing() {
+      return None;
+    }
+
+    if self.sent < PROBE_COUNT {
+      self.sent += 1;
+      self.deadline = Some(now + PROBE_INTERVAL);
+      return Some(ProbeEvent::Probe {
+        unicast_response: self.sent == 1,
+      });
+    }
+
+    self.deadline = None;
+    self.state = ProbeState::Won;
+    Some(ProbeEvent::Won)
+  }
+
+  /// Handles an incoming message, read from the raw message `packet`, `records` are the
+  /// unique records being probed.
+  ///
+  /// Returns [`ProbeEvent::Lost`] if a response claims one of the names while probing,
+  /// [`ProbeEvent::Deferred`] if another host probing for one of the names wins the
+  /// [`tiebreak`], or [`ProbeEvent::Conflict`] if a response contains a record conflicting
+  /// with one of `records` after the names were claimed.
+  pub fn recv(
+    &mut self,
+    now: I,
+    packet: &[u8],
+    msg: &Message<'_, '_>,
+    records: &[ResourceRecord<'_>],
+  ) -> Option<ProbeEvent> {
+    match (self.state, msg.flags().qr()) {
+      (ProbeState::Probing, MessageType::Query) => {
+        let theirs = msg.authorities();
+        let lost = distinct_names(records).any(|name| {
+          theirs.iter().any(|r| label_eq(&r.name(), &name))
+            && tiebreak_by(records, theirs, packet, |r| label_eq(&r.name(), &name))
+              == Ordering::Less
+        });
+
+        if lost {
+          self.sent = 0;
+          self.deadline = Some(now + PROBE_DEFER);
+          return Some(ProbeEvent::Deferred);
+        }
+        None
+      }
+      (ProbeState::Probing, MessageType::Reply) => {
+        // RFC 6762, section 8.1: if, during probing, the host receives a response
+        // containing a record with the same name as one of its proposed records,
+        // the name is already in use.
+        let mut incoming = msg.answers().iter().chain(msg.additional().iter());
+        let index = incoming.find_map(|record| claims(packet, record, records))?;
+        self.deadline = None;
+        self.state = ProbeState::Lost;
+        Some(ProbeEvent::Lost { index })
+      }
+      (ProbeState::Won, MessageType::Reply) => {
+        let mut incoming = msg.answers().iter().chain(msg.additional().iter());
+        if incoming.any(|record| conflicts(packet, record, records)) {
+          self.state = ProbeState::Conflict;
+          return Some(ProbeEvent::Conflict);
+        }
+        None
+      }
+      _ => None,
+    }
+  }
+}
+
+/// Compares two sets of records according to the simultaneous probe tiebreaking rules.
+///
+/// ```text
+/// RFC 6762, section 8.2.  Simultaneous Probe Tiebreaking
+///
+/// The determination of "lexicographically later" is performed by first
+/// comparing the record class (excluding the cache-flush bit described
+/// in Section 10.2), then the record type, then raw comparison of the
+/// binary content of the rdata without regard for meaning or structure.
+///
+/// ... each host first sorts its records into (lexicographical) order,
+/// and then compares them pairwise, in order, until a difference is
+/// found. If one host has records remaining when the other has run out,
+/// the host with records remaining is deemed to have won the tiebreak.
+/// ```
+///
+/// Returns [`Ordering::Less`] if `ours` lost the tiebreak, [`Ordering::Greater`] if `ours`
+/// won, and [`Ordering::Equal`] if both sets contain the same records, e.g. when reading
+/// our own probe.
+///
+/// `theirs` are read from the raw message `packet`, the names inside their rdata, which
+/// may be compressed, are compared uncompressed, see [`Rdata::cmp_raw`]. The sets are
+/// compared without allocating.
+pub fn tiebreak(
+  ours: &[ResourceRecord<'_>],
+  theirs: &[ResourceRecord<'_>],
+  packet: &[u8],
+) -> Ordering {
+  tiebreak_by(ours, theirs, packet, |_| true)
+}
+
+/// Compares the records of the two sets for which `f` returns `true`, see [`tiebreak`].
+fn tiebreak_by(
+  ours: &[ResourceRecord<'_>],
+  theirs: &[ResourceRecord<'_>],
+  packet: &[u8],
+  f: impl Fn(&ResourceRecord<'_>) -> bool,
+) -> Ordering {
+  let mut idx = 0;
+  loop {
+    match (nth(ours, idx, packet, &f), nth(theirs, idx, packet, &f)) {
+      (None, None) => return Ordering::Equal,
+      (Some(_), None) => return Ordering::Greater,
+      (None, Some(_)) => return Ordering::Less,
+      (Some(a), Some(b)) => match compare(packet, a, b) {
+        Ordering::Equal => idx += 1,
+        ord => return ord,
+      },
+    }
+  }
+}
+
+/// Returns the `n`th smallest record of `records` for which `f` returns `true`.
+fn nth<'a, 'innards>(
+  records: &'a [ResourceRecord<'innards>],
+  n: usize,
+  packet: &[u8],
+  f: &impl Fn(&ResourceRecord<'_>) -> bool,
+) -> Option<&'a ResourceRecord<'innards>> {
+  records.iter().filter(|r| f(r)).find(|record| {
+    let (less, equal) = records
+      .iter()
+      .filter(|r| f(r))
+      .fold((0, 0), |(less, equal), r| {
+        match compare(packet, r, record) {
+          Ordering::Less => (less + 1, equal),
+          Ordering::Equal => (less, equal + 1),
+          Ordering::Greater => (less, equal),
+        }
+      });
+    less <= n && n < less + equal
+  })
+}
+
+/// Compares two records by class, excluding the cache-flush bit, type and uncompressed
+/// rdata.
+fn compare(packet: &[u8], a: &ResourceRecord<'_>, b: &ResourceRecord<'_>) -> Ordering {
+  (a.class() & !TOP_BIT)
+    .cmp(&(b.class() & !TOP_BIT))
+    .then_with(|| u16::from(a.ty()).cmp(&u16::from(b.ty())))
+    .then_with(|| Rdata::new(packet, a).cmp_raw(&Rdata::new(packet, b)))
+}
+
+/// Returns the distinct names of `records`, in order of first occurrence.
+fn distinct_names<'a, 'innards>(
+  records: &'a [ResourceRecord<'innards>],
+) -> impl Iterator<Item = Label<'innards>> + 'a {
+  records
+    .iter()
+    .enumerate()
+    .filter(move |(idx, record)| {
+      !records[..*idx]
+        .iter()
+        .any(|prev| label_eq(&prev.name(), &record.name()))
+    })
+    .map(|(_, record)| record.name())
+}
+
+/// Returns the questions of a probe for the given unique records.
+///
+/// There is one question of type `ANY` for every distinct name in `records`.
+pub fn questions<'a, 'innards>(
+  records: &'a [ResourceRecord<'innards>],
+  unicast_response: bool,
+) -> impl Iterator<Item = Question<'innards>> + 'a {
+  let class = if unicast_response {
+    CLASS_IN | TOP_BIT
+  } else {
+    CLASS_IN
+  };
+
+  distinct_names(records).map(move |name| Question::new(name, ResourceType::Wildcard, class))
+}
+
+/// Returns the index of the first record of `ours` using the name of `record`, if `record`
+/// is not one of `ours`.
+fn claims(
+  packet: &[u8],
+  record: &ResourceRecord<'_>,
+  ours: &[ResourceRecord<'_>],
+) -> Option<usize> {
+  if ours.iter().any(|r| record_eq(packet, r, record)) {
+    return None;
+  }
+  ours
+    .iter()
+    .position(|r| label_eq(&r.name(), &record.name()))
+}
+
+/// Returns `true` if `record` has the same name, type and class as one of `ours`,
+/// but its data is not one of ours.
+///
+/// RFC 6762, section 9: a conflict occurs when a Multicast DNS responder has a unique
+/// record for which it is currently authoritative, and it receives a Multicast DNS
+/// response message containing a record with the same name, rrtype, and rrclass,
+/// but inconsistent rdata.
+///
+/// The rdata is compared uncompressed, a compressed copy of one of ours is not a conflict.
+fn conflicts(packet: &[u8], record: &ResourceRecord<'_>, ours: &[ResourceRecord<'_>]) -> bool {
+  ours.iter().any(|r| {
+    r.ty() == record.ty()
+      && (r.class() & !TOP_BIT) == (record.class() & !TOP_BIT)
+      && label_eq(&r.name(), &record.name())
+  }) && !ours.iter().any(|r| record_eq(packet, r, record))
+}
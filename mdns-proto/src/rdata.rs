@@ -0,0 +1,233 @@
+use core::{
+  cmp::Ordering,
+  hash::{Hash, Hasher},
+};
+
+use dns_protocol::{Cursor, Deserialize, Label, ResourceRecord, ResourceType};
+
+/// The maximum number of labels of a name, a name is at most 255 bytes long, see
+/// RFC 1035, section 3.1.
+const MAX_LABELS: usize = 128;
+
+/// The two top bits of a length byte, set for a compression pointer.
+const PTR_MASK: u8 = 0b1100_0000;
+
+/// The data of a record, along with the message it was read from.
+///
+/// ```text
+/// RFC 1035, section 4.1.4.  Message compression
+///
+/// In order to reduce the size of messages, the domain system utilizes a
+/// compression scheme which eliminates the repetition of domain names in a
+/// message.  In this scheme, an entire domain name or a list of labels at
+/// the end of a domain name is replaced with a pointer to a prior occurance
+/// of the same name.
+/// ```
+///
+/// The names inside the data of the `PTR`, `CNAME`, `NS` and `SRV` records may be
+/// compressed, e.g. by Bonjour and Avahi, a pointer then refers to a prior name of the
+/// message. The raw data of such a record only means something along with its message,
+/// the names are read from the message, and two records are compared with their names
+/// uncompressed, ignoring ASCII case. The data of the other record types is compared byte
+/// for byte.
+///
+/// ## Example
+///
+/// ```rust
+/// use mdns_proto::proto::{Rdata, ResourceRecord, ResourceType};
+///
+/// // `_http._tcp.local.`, then a PTR record pointing to `printer._http._tcp.local.`, with
+/// // the service type compressed as a pointer to offset 0
+/// let packet = [
+///   5, b'_', b'h', b't', b't', b'p', 4, b'_', b't', b'c', b'p', 5, b'l', b'o', b'c', b'a',
+///   b'l', 0, 7, b'p', b'r', b'i', b'n', b't', b'e', b'r', 0xc0, 0,
+/// ];
+/// let compressed = ResourceRecord::new("_http._tcp.local", ResourceType::Ptr, 1, 120, &packet[18..]);
+///
+/// let uncompressed = [
+///   7, b'p', b'r', b'i', b'n', b't', b'e', b'r', 5, b'_', b'h', b't', b't', b'p', 4, b'_',
+///   b't', b'c', b'p', 5, b'l', b'o', b'c', b'a', b'l', 0,
+/// ];
+/// let ours = ResourceRecord::new("_http._tcp.local", ResourceType::Ptr, 1, 120, &uncompressed);
+///
+/// assert_ne!(compressed.data(), ours.data());
+/// assert_eq!(Rdata::new(&packet, &compressed), Rdata::new(&[], &ours));
+/// assert!(Rdata::new(&packet, &compressed).bytes().eq(uncompressed));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Rdata<'a> {
+  /// The data before the name, or all the data of a record without a name.
+  fixed: &'a [u8],
+  /// The message the name is read from, and the offset of the name in it.
+  name: Option<(&'a [u8], usize)>,
+}
+
+impl<'a> Rdata<'a> {
+  /// Returns the data of `record`, read from the raw message `packet`.
+  ///
+  /// A record which is not part of `packet`, e.g. one of our own records, or a record
+  /// kept in a cache, is read on its own, its names must then be uncompressed. An empty
+  /// `packet` can be given for such records.
+  pub fn new(packet: &'a [u8], record: &ResourceRecord<'a>) -> Self {
+    let data = record.data();
+    let name_at = match record.ty() {
+      ResourceType::Ptr | ResourceType::CName | ResourceType::NS => 0,
+      // priority, weight and port come first, see RFC 2782
+      ResourceType::Srv => 6,
+      _ => {
+        return Self {
+          fixed: data,
+          name: None,
+        };
+      }
+    };
+
+    // a name is at least one byte long
+    if data.len() <= name_at {
+      return Self {
+        fixed: data,
+        name: None,
+      };
+    }
+
+    let name = match offset_in(packet, data) {
+      Some(offset) => (packet, offset + name_at),
+      None => (data, name_at),
+    };
+    Self {
+      fixed: &data[..name_at],
+      name: Some(name),
+    }
+  }
+
+  /// Returns the data before the name, e.g. the priority, weight and port of a `SRV`
+  /// record, or all the data of a record without a name.
+  #[inline]
+  pub const fn fixed(&self) -> &'a [u8] {
+    self.fixed
+  }
+
+  /// Returns the name of the data, which follows the compression pointers in the message
+  /// the record was read from.
+  pub fn name(&self) -> Option<Label<'a>> {
+    let (packet, offset) = self.name?;
+    let mut label = Label::default();
+    label
+      .deserialize(Cursor::new(packet).at(offset))
+      .ok()
+      .map(|_| label)
+  }
+
+  /// Returns the data, with its name uncompressed.
+  ///
+  /// A malformed name, e.g. a pointer which does not refer to a prior name, ends where
+  /// it stops making sense.
+  pub fn bytes(&self) -> impl Iterator<Item = u8> + 'a {
+    let name = self.name.map(|(packet, offset)| {
+      labels(packet, offset)
+        .flat_map(|label| core::iter::once(label.len() as u8).chain(label.iter().copied()))
+        .chain(core::iter::once(0))
+    });
+    self.fixed.iter().copied().chain(name.into_iter().flatten())
+  }
+
+  /// Returns the data, with its name uncompressed, see [`Rdata::bytes`].
+  #[cfg(any(feature = "std", feature = "alloc"))]
+  #[cfg_attr(docsrs, doc(cfg(any(feature = "std", feature = "alloc"))))]
+  pub fn to_vec(&self) -> std::vec::Vec<u8> {
+    self.bytes().collect()
+  }
+
+  /// Compares the data byte for byte, with its name uncompressed, as required by the
+  /// simultaneous probe tiebreaking of RFC 6762, section 8.2.
+  ///
+  /// Unlike the [`PartialEq`] implementation, the case of the names is not ignored.
+  pub fn cmp_raw(&self, other: &Rdata<'_>) -> Ordering {
+    self.bytes().cmp(other.bytes())
+  }
+
+  fn labels(&self) -> Option<impl Iterator<Item = &'a [u8]> + 'a> {
+    self.name.map(|(packet, offset)| labels(packet, offset))
+  }
+}
+
+impl PartialEq<Rdata<'_>> for Rdata<'_> {
+  fn eq(&self, other: &Rdata<'_>) -> bool {
+    if self.fixed != other.fixed {
+      return false;
+    }
+
+    match (self.labels(), other.labels()) {
+      (None, None) => true,
+      (Some(mut a), Some(mut b)) => loop {
+        match (a.next(), b.next()) {
+          (None, None) => return true,
+          (Some(a), Some(b)) if a.eq_ignore_ascii_case(b) => {}
+          _ => return false,
+        }
+      },
+      _ => false,
+    }
+  }
+}
+
+impl Eq for Rdata<'_> {}
+
+impl Hash for Rdata<'_> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    state.write(self.fixed);
+    if let Some(labels) = self.labels() {
+      for label in labels {
+        state.write_u8(label.len() as u8);
+        label
+          .iter()
+          .for_each(|b| state.write_u8(b.to_ascii_lowercase()));
+      }
+      state.write_u8(0);
+    }
+  }
+}
+
+/// Returns the offset of `data` in `packet`, if `data` is a slice of `packet`.
+fn offset_in(packet: &[u8], data: &[u8]) -> Option<usize> {
+  let start = (data.as_ptr() as usize).checked_sub(packet.as_ptr() as usize)?;
+  (start + data.len() <= packet.len()).then_some(start)
+}
+
+/// Returns the labels of the name at `offset` in `packet`, following the compression
+/// pointers.
+///
+/// A pointer must refer to a prior name, see RFC 1035, section 4.1.4, so a malformed
+/// message cannot make the labels loop forever.
+fn labels(packet: &[u8], offset: usize) -> impl Iterator<Item = &[u8]> {
+  let mut pos = offset;
+  let mut limit = offset;
+  let mut count = 0;
+  core::iter::from_fn(move || {
+    loop {
+      let len = *packet.get(pos)?;
+      match len {
+        0 => return None,
+        len if len & PTR_MASK == PTR_MASK => {
+          let ptr = (((len & !PTR_MASK) as usize) << 8) | *packet.get(pos + 1)? as usize;
+          if ptr >= limit {
+            return None;
+          }
+          limit = ptr;
+          pos = ptr;
+        }
+        // the other label types are obsolete, see RFC 6891, section 5
+        len if len & PTR_MASK != 0 => return None,
+        len => {
+          count += 1;
+          if count > MAX_LABELS {
+            return None;
+          }
+          let label = packet.get(pos + 1..pos + 1 + len as usize)?;
+          pos += 1 + len as usize;
+          return Some(label);
+        }
+      }
+    }
+  })
+}
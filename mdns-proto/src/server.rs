@@ -1,13 +1,81 @@
-use core::marker::PhantomData;
+use core::{marker::PhantomData, time::Duration};
 
 use super::{
   ConnectionHandle, Pool,
   error::ProtoError,
-  proto::{Flags, Message, Opcode, Question, ResponseCode},
+  proto::{Flags, Message, MessageType, Opcode, Question, ResourceRecord, ResponseCode},
+  utils::{TOP_BIT, record_eq},
 };
 
 const FORCE_UNICAST_RESPONSES: bool = false;
 
+/// The minimum time a responder holds a truncated query, waiting for the
+/// continuation packets carrying the rest of its known answers.
+///
+/// RFC 6762, section 7.2: if the TC bit is set, the responder SHOULD delay its
+/// response by a random amount of time selected with uniform random distribution
+/// in the range 400-500 ms.
+pub const TRUNCATED_QUERY_DELAY_MIN: Duration = Duration::from_millis(400);
+
+/// The maximum time a responder holds a truncated query, see
+/// [`TRUNCATED_QUERY_DELAY_MIN`].
+pub const TRUNCATED_QUERY_DELAY_MAX: Duration = Duration::from_millis(500);
+
+/// The minimum delay of a response to a question answered with shared records.
+///
+/// RFC 6762, section 6: in any case where there may be multiple responses, such as
+/// queries where the answer is a member of a shared resource record set, each responder
+/// SHOULD delay its response by a random amount of time selected with uniform random
+/// distribution in the range 20-120 ms.
+pub const SHARED_RESPONSE_DELAY_MIN: Duration = Duration::from_millis(20);
+
+/// The maximum delay of a response to a question answered with shared records, see
+/// [`SHARED_RESPONSE_DELAY_MIN`].
+pub const SHARED_RESPONSE_DELAY_MAX: Duration = Duration::from_millis(120);
+
+/// The TC bit, in the third byte of the header.
+const TC_BIT: u8 = 0x02;
+
+/// Returns `true` if the TC bit is set in the header of the raw message `packet`.
+///
+/// [`Message::read`] discards the content of truncated messages, the bit must be
+/// cleared with [`clear_truncated`] before reading them.
+#[inline]
+pub fn is_truncated(packet: &[u8]) -> bool {
+  packet.len() > 2 && packet[2] & TC_BIT != 0
+}
+
+/// Clears the TC bit in the header of the raw message `packet`, see [`is_truncated`].
+#[inline]
+pub fn clear_truncated(packet: &mut [u8]) {
+  if let Some(flags) = packet.get_mut(2) {
+    *flags &= !TC_BIT;
+  }
+}
+
+/// The maximum TTL of the records in a legacy unicast response.
+///
+/// RFC 6762, section 6.7: the resource record TTL given in a legacy unicast response
+/// SHOULD NOT be greater than ten seconds, even if the true TTL of the Multicast DNS
+/// resource record is higher.
+pub const LEGACY_UNICAST_TTL: u32 = 10;
+
+/// Returns `record` as it must be given in a legacy unicast response, with its TTL capped
+/// to [`LEGACY_UNICAST_TTL`] and without the cache-flush bit, see [`Endpoint::legacy_response`].
+///
+/// RFC 6762, section 10.2: the cache-flush bit MUST NOT be set in any resource records in
+/// a response message sent in legacy unicast responses to UDP ports other than 5353.
+#[inline]
+pub fn legacy_unicast(record: ResourceRecord<'_>) -> ResourceRecord<'_> {
+  ResourceRecord::new(
+    record.name(),
+    record.ty(),
+    record.class() & !TOP_BIT,
+    record.ttl().min(LEGACY_UNICAST_TTL),
+    record.data(),
+  )
+}
+
 /// An endpoint for handling mDNS queries and responses.
 ///
 /// This `Endpoint` is using a slab for managing connections and queries.
@@ -37,6 +105,10 @@ pub enum Error<S, Q> {
   #[error("invalid response code: {0:?}")]
   InvalidResponseCode(ResponseCode),
   /// Returned when a query with a high truncated bit is received.
+  #[deprecated(
+    since = "0.2.0",
+    note = "truncated queries are handled, the caller holds them until their known answers are received, see `is_truncated`"
+  )]
   #[error("support for DNS requests with high truncated bit not implemented")]
   TrancatedQuery,
   /// Protocol error
@@ -80,14 +152,23 @@ impl QueryHandle {
 /// A query event
 #[derive(Debug, Eq, PartialEq)]
 pub struct Query<'container, 'innards> {
+  packet: &'innards [u8],
   msg: Message<'container, 'innards>,
   query_handle: QueryHandle,
 }
 
 impl<'container, 'innards> Query<'container, 'innards> {
   #[inline]
-  const fn new(msg: Message<'container, 'innards>, query_handle: QueryHandle) -> Self {
-    Self { msg, query_handle }
+  const fn new(
+    packet: &'innards [u8],
+    msg: Message<'container, 'innards>,
+    query_handle: QueryHandle,
+  ) -> Self {
+    Self {
+      packet,
+      msg,
+      query_handle,
+    }
   }
 
   /// Returns the question associated with the query event.
@@ -96,6 +177,46 @@ impl<'container, 'innards> Query<'container, 'innards> {
     self.msg.questions()
   }
 
+  /// Returns the authority records associated with the query event.
+  ///
+  /// Probe queries carry the records the sender intends to claim in the
+  /// Authority section, see [`prober`](crate::prober).
+  #[inline]
+  pub fn authorities(&self) -> &[ResourceRecord<'innards>] {
+    self.msg.authorities()
+  }
+
+  /// Returns the known answers of the query, the records the querier already has
+  /// in its cache, see RFC 6762, section 7.1.
+  #[inline]
+  pub fn known_answers(&self) -> &[ResourceRecord<'innards>] {
+    self.msg.answers()
+  }
+
+  /// Returns `true` if `record` must not be sent in response to this query, because it
+  /// is already in the known answers of the query.
+  ///
+  /// ```text
+  /// RFC 6762, section 7.1.  Known-Answer Suppression
+  ///
+  /// A Multicast DNS responder MUST NOT answer a Multicast DNS query if
+  /// the answer it would give is already included in the Answer Section
+  /// with an RR TTL at least half the correct value.  If the RR TTL of the
+  /// answer as given in the Answer Section is less than half of the true
+  /// RR TTL as known by the Multicast DNS responder, the responder MUST
+  /// send an answer so as to update the querier's cache before the record
+  /// becomes in danger of expiration.
+  /// ```
+  ///
+  /// The names inside the data of the known answers may be compressed, they are read
+  /// from the packet of the query, see [`Rdata`](crate::proto::Rdata).
+  pub fn is_known_answer(&self, record: &ResourceRecord<'_>) -> bool {
+    self
+      .known_answers()
+      .iter()
+      .any(|known| record_eq(self.packet, known, record) && known.ttl() >= record.ttl().div_ceil(2))
+  }
+
   /// Returns the query handle associated with the query event.
   #[inline]
   pub const fn query_handle(&self) -> QueryHandle {
@@ -137,6 +258,7 @@ impl<'innards> Response<'innards> {
 pub struct Outgoing {
   flags: Flags,
   unicast: bool,
+  legacy: bool,
   id: u16,
 }
 
@@ -144,7 +266,12 @@ impl Outgoing {
   /// Creates a new outgoing event.
   #[inline]
   const fn new(flags: Flags, unicast: bool, id: u16) -> Self {
-    Self { flags, unicast, id }
+    Self {
+      flags,
+      unicast,
+      legacy: false,
+      id,
+    }
   }
 
   /// Returns the message flags should be used for the outgoing [`Message`].
@@ -159,6 +286,13 @@ impl Outgoing {
     self.unicast
   }
 
+  /// Returns `true` if the outgoing event is a legacy unicast response, see
+  /// [`Endpoint::legacy_response`].
+  #[inline]
+  pub const fn is_legacy(&self) -> bool {
+    self.legacy
+  }
+
   /// Returns the message id should be used for the outgoing [`Message`].
   ///
   /// - `0` for multicast response
@@ -242,9 +376,13 @@ where
   }
 
   /// Handle an incoming query message
+  ///
+  /// `msg` must be read from the raw `packet`, the records of the query are compared with
+  /// ours with the names inside their data uncompressed.
   pub fn recv<'container, 'innards>(
     &mut self,
     ch: ConnectionHandle,
+    packet: &'innards [u8],
     msg: Message<'container, 'innards>,
   ) -> Result<Query<'container, 'innards>, Error<S::Error, Q::Error>> {
     let id = msg.id();
@@ -271,23 +409,24 @@ where
       return Err(Error::InvalidResponseCode(resp_code));
     }
 
-    // TODO(reddaly): Handle "TC (Truncated) Bit":
+    // "TC (Truncated) Bit":
     //    In query messages, if the TC bit is set, it means that additional
     //    Known-Answer records may be following shortly.  A responder SHOULD
     //    record this fact, and wait for those additional Known-Answer records,
     //    before deciding whether to respond.  If the TC bit is clear, it means
     //    that the querying host has no additional Known Answers.
-    if flags.truncated() {
-      #[cfg(feature = "tracing")]
-      tracing::error!(
-        "mdns endpoint: support for mDNS requests with high truncated bit not implemented"
-      );
-      return Err(Error::TrancatedQuery);
-    }
+    //
+    // The caller holds the truncated query for `TRUNCATED_QUERY_DELAY_MIN` to
+    // `TRUNCATED_QUERY_DELAY_MAX`, and passes it here with the known answers of
+    // the continuation packets merged in its Answer section.
 
     if let Some(conn) = self.connections.get_mut(ch.0) {
       let qid = conn.insert(id).map_err(Error::Query)?;
-      return Ok(Query::new(msg, QueryHandle::new(ch.into(), qid, id)));
+      return Ok(Query::new(
+        packet,
+        msg,
+        QueryHandle::new(ch.into(), qid, id),
+      ));
     }
 
     Err(Error::ConnectionNotFound(ch))
@@ -301,14 +440,15 @@ where
   ) -> Result<Outgoing, Error<S::Error, Q::Error>> {
     let mut flags = Flags::new();
     flags
+      .set_qr(MessageType::Reply)
       .set_response_code(ResponseCode::NoError)
       .set_authoritative(true);
 
     // Handle unicast and multicast responses.
-    // TODO(reddaly): The decision about sending over unicast vs. multicast is not
-    // yet fully compliant with RFC 6762.  For example, the unicast bit should be
-    // ignored if the records in question are close to TTL expiration.  For now,
-    // we just use the unicast bit to make the decision, as per the spec:
+    // The decision is made from the unicast bit of the question, the caller is
+    // responsible for multicasting anyway the records which have not been multicast
+    // within one quarter of their TTL, see RFC 6762, section 5.4.
+    //
     //     RFC 6762, section 18.12.  Repurposing of Top Bit of qclass in Query
     //     Section
     //
@@ -328,6 +468,36 @@ where
     Ok(Outgoing::new(flags, unicast, id))
   }
 
+  /// Generate a legacy unicast response for a question.
+  ///
+  /// ```text
+  /// RFC 6762, section 6.7.  Legacy Unicast Responses
+  ///
+  /// If the source UDP port in a received Multicast DNS query is not port
+  /// 5353, this indicates that the querier originating the query is a
+  /// simple resolver such as described in Section 5.1, "One-Shot Multicast
+  /// DNS Queries", which does not fully implement all of Multicast DNS.
+  /// In this case, the Multicast DNS responder MUST send a UDP response
+  /// directly back to the querier, via unicast, to the query packet's
+  /// source IP address and port. This unicast response MUST be a
+  /// conventional unicast response as would be generated by a
+  /// conventional Unicast DNS server; for example, it MUST repeat the
+  /// query ID and the question given in the query message.
+  /// ```
+  ///
+  /// The records of the response must be passed to [`legacy_unicast`].
+  pub fn legacy_response(
+    &mut self,
+    qh: QueryHandle,
+    question: Question<'_>,
+  ) -> Result<Outgoing, Error<S::Error, Q::Error>> {
+    let mut outgoing = self.response(qh, question)?;
+    outgoing.unicast = true;
+    outgoing.legacy = true;
+    outgoing.id = qh.message_id();
+    Ok(outgoing)
+  }
+
   /// Handle a query drain event
   pub fn drain_query(&mut self, qh: QueryHandle) -> Result<(), Error<S::Error, Q::Error>> {
     match self.connections.get_mut(qh.cid) {
@@ -1,6 +1,9 @@
-use super::error::{ProtoError, not_enough_read_data};
+use super::{
+  error::{ProtoError, not_enough_read_data, proto_error_parse},
+  rdata::Rdata,
+};
 
-use dns_protocol::{Cursor, Deserialize, Label};
+use dns_protocol::Label;
 
 /// [RFC 2782, DNS SRV RR, February 2000](https://tools.ietf.org/html/rfc2782)
 ///
@@ -71,25 +74,20 @@ pub struct Srv<'a> {
 }
 
 impl<'a> Srv<'a> {
-  pub(super) fn from_bytes(buf: &'a [u8]) -> Result<Self, ProtoError> {
-    let len = buf.len();
+  /// Parses the data of a `SRV` record, its target is read from the message the record
+  /// was read from, see [`Rdata`].
+  pub(super) fn from_rdata(rdata: Rdata<'a>) -> Result<Self, ProtoError> {
+    let fixed = rdata.fixed();
+    let len = fixed.len();
     if len < 6 {
       return Err(not_enough_read_data(6, len));
     }
 
-    let mut cur = Cursor::new(buf);
-    let mut priority = 0u16;
-    cur = priority.deserialize(cur)?;
-    let mut weight = 0u16;
-    cur = weight.deserialize(cur)?;
-    let mut port = 0u16;
-    cur = port.deserialize(cur)?;
-
-    let mut target = Label::default();
-    target.deserialize(cur).map(|_| Self {
-      priority,
-      weight,
-      port,
+    let target = rdata.name().ok_or_else(|| proto_error_parse("SRV"))?;
+    Ok(Self {
+      priority: u16::from_be_bytes([fixed[0], fixed[1]]),
+      weight: u16::from_be_bytes([fixed[2], fixed[3]]),
+      port: u16::from_be_bytes([fixed[4], fixed[5]]),
       target,
     })
   }
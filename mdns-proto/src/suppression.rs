@@ -0,0 +1,271 @@
+use core::{
+  hash::{Hash, Hasher},
+  marker::PhantomData,
+  ops::Add,
+  time::Duration,
+};
+
+use dns_protocol::{Label, Message, MessageType, Question, ResourceRecord, ResourceType};
+
+use super::{Pool, rdata::Rdata, utils::TOP_BIT};
+
+/// How long an observed question or answer suppresses our own copy.
+///
+/// RFC 6762, section 6: a Multicast DNS responder MUST NOT multicast a record on a
+/// given interface until at least one second has elapsed since the last time that
+/// record was multicast on that particular interface.
+pub const SUPPRESSION_WINDOW: Duration = Duration::from_secs(1);
+
+/// A suppression tracker using a slab for the observed questions and answers.
+#[cfg(feature = "slab")]
+#[cfg_attr(docsrs, doc(cfg(feature = "slab")))]
+pub type SlabSuppression<I> = Suppression<I, slab::Slab<Observed<I>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+  Question,
+  Answer { ttl: u32 },
+}
+
+/// A question or an answer observed on the link, stored by a [`Suppression`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Observed<I> {
+  kind: Kind,
+  fingerprint: u64,
+  expires: I,
+}
+
+/// A sans-I/O tracker of the questions and answers sent by other hosts.
+///
+/// ```text
+/// RFC 6762, section 7.3.  Duplicate Question Suppression
+///
+/// If a host is planning to transmit (or has just transmitted) a query,
+/// and it sees another host on the network send a query containing the
+/// same "QM" question, and the Known-Answer Section of that query does
+/// not contain any records that this host would not also put in its own
+/// Known-Answer Section, then this host SHOULD treat its own query as
+/// having been sent.
+///
+/// RFC 6762, section 7.4.  Duplicate Answer Suppression
+///
+/// If a host is planning to send an answer, and it sees another host on
+/// the network send a response message containing the same answer
+/// record, and the TTL in that record is not less than the TTL this host
+/// would have given, then this host SHOULD treat its own answer as
+/// having been sent, and not also send an identical answer itself.
+/// ```
+///
+/// Both the queriers and the responders feed the tracker with the packets they receive,
+/// see [`Suppression::observe`], and check it before sending their own questions or
+/// answers.
+///
+/// The tracker does not own the observed names and data, it only keeps a fingerprint of
+/// them for [`SUPPRESSION_WINDOW`].
+///
+/// `I` is the instant type of the clock driving the tracker, e.g. `std::time::Instant`.
+pub struct Suppression<I, P> {
+  observed: P,
+  _i: PhantomData<I>,
+}
+
+impl<I, P> Default for Suppression<I, P>
+where
+  I: Copy + Ord + Add<Duration, Output = I>,
+  P: Pool<Observed<I>>,
+{
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<I, P> Suppression<I, P>
+where
+  I: Copy + Ord + Add<Duration, Output = I>,
+  P: Pool<Observed<I>>,
+{
+  /// Creates a new, empty tracker.
+  pub fn new() -> Self {
+    Self {
+      observed: P::new(),
+      _i: PhantomData,
+    }
+  }
+
+  /// Creates a new, empty tracker with a specific capacity.
+  pub fn with_capacity(capacity: usize) -> Result<Self, P::Error> {
+    Ok(Self {
+      observed: P::with_capacity(capacity)?,
+      _i: PhantomData,
+    })
+  }
+
+  /// Records the questions and answers of a packet received at `now`.
+  ///
+  /// Only the "QM" questions of the queries without known answers are recorded, as the
+  /// known answers of another host cannot be compared with ours. The records of the
+  /// Answer and Additional sections of the responses are recorded with their TTL.
+  ///
+  /// Only the packets sent from port 5353 should be observed, a query from another port
+  /// is answered by unicast, see RFC 6762, section 6.7. Our own packets, looped back by
+  /// the multicast group, may be observed too, a record is then not multicast twice
+  /// within one second, as required by RFC 6762, section 6.
+  ///
+  /// `msg` must be read from the raw `packet`, the names inside the data of the answers
+  /// may be compressed, see [`Rdata`].
+  ///
+  /// Returns an error if the tracker cannot hold any more entries.
+  pub fn observe(&mut self, packet: &[u8], msg: &Message<'_, '_>, now: I) -> Result<(), P::Error> {
+    self.forget_expired(now);
+
+    match msg.flags().qr() {
+      MessageType::Query => {
+        if !msg.answers().is_empty() {
+          return Ok(());
+        }
+
+        for question in msg.questions() {
+          if question.class() & TOP_BIT != 0 {
+            continue;
+          }
+
+          let fingerprint = fingerprint(question.name(), question.ty(), question.class(), None);
+          self.insert(Kind::Question, fingerprint, now)?;
+        }
+      }
+      MessageType::Reply => {
+        for record in msg.answers().iter().chain(msg.additional()) {
+          let data = Rdata::new(packet, record);
+          let fingerprint = fingerprint(record.name(), record.ty(), record.class(), Some(data));
+          self.insert(Kind::Answer { ttl: record.ttl() }, fingerprint, now)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Returns `true` if another host asked `question` within the last
+  /// [`SUPPRESSION_WINDOW`], our own query can be treated as having been sent.
+  ///
+  /// The unicast-response bit of the question is ignored.
+  pub fn is_question_suppressed(&self, question: &Question<'_>, now: I) -> bool {
+    let fingerprint = fingerprint(question.name(), question.ty(), question.class(), None);
+    self.observed.iter().any(|(_, observed)| {
+      observed.kind == Kind::Question
+        && observed.fingerprint == fingerprint
+        && observed.expires > now
+    })
+  }
+
+  /// Returns `true` if another host multicast `record` within the last
+  /// [`SUPPRESSION_WINDOW`], with a TTL not less than the TTL of `record`, our own answer
+  /// can be treated as having been sent.
+  ///
+  /// The cache-flush bit of the record is ignored. `record` is one of ours, the names
+  /// inside its data must be uncompressed.
+  pub fn is_answer_suppressed(&self, record: &ResourceRecord<'_>, now: I) -> bool {
+    let data = Rdata::new(&[], record);
+    let fingerprint = fingerprint(record.name(), record.ty(), record.class(), Some(data));
+    self
+      .observed
+      .iter()
+      .any(|(_, observed)| match observed.kind {
+        Kind::Answer { ttl } => {
+          observed.fingerprint == fingerprint && ttl >= record.ttl() && observed.expires > now
+        }
+        Kind::Question => false,
+      })
+  }
+
+  /// Returns `true` if nothing is being tracked.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.observed.is_empty()
+  }
+
+  fn insert(&mut self, kind: Kind, fingerprint: u64, now: I) -> Result<(), P::Error> {
+    let expires = now + SUPPRESSION_WINDOW;
+    let same = |observed: &Observed<I>| match (observed.kind, kind) {
+      (Kind::Question, Kind::Question) | (Kind::Answer { .. }, Kind::Answer { .. }) => {
+        observed.fingerprint == fingerprint
+      }
+      _ => false,
+    };
+
+    let key = self
+      .observed
+      .iter()
+      .find_map(|(key, observed)| same(observed).then_some(key));
+    if let Some(observed) = key.and_then(|key| self.observed.get_mut(key)) {
+      observed.kind = kind;
+      observed.expires = expires;
+      return Ok(());
+    }
+
+    self
+      .observed
+      .insert(Observed {
+        kind,
+        fingerprint,
+        expires,
+      })
+      .map(|_| ())
+  }
+
+  fn forget_expired(&mut self, now: I) {
+    loop {
+      let expired = self
+        .observed
+        .iter()
+        .find_map(|(key, observed)| (observed.expires <= now).then_some(key));
+      match expired {
+        Some(key) => {
+          self.observed.try_remove(key);
+        }
+        None => return,
+      }
+    }
+  }
+}
+
+/// Returns the FNV-1a hash of a name, ignoring ASCII case, a type, a class, ignoring its
+/// top bit, and the data of a record, with its name uncompressed.
+fn fingerprint(name: Label<'_>, ty: ResourceType, class: u16, data: Option<Rdata<'_>>) -> u64 {
+  let mut hasher = Fnv::default();
+  for segment in name.names() {
+    let segment = match segment {
+      Ok(s) => s.as_bytes(),
+      Err(bytes) => bytes,
+    };
+    hasher.write_usize(segment.len());
+    segment
+      .iter()
+      .for_each(|b| hasher.write_u8(b.to_ascii_lowercase()));
+  }
+  hasher.write_u16(ty.into());
+  hasher.write_u16(class & !TOP_BIT);
+  data.hash(&mut hasher);
+  hasher.finish()
+}
+
+struct Fnv(u64);
+
+impl Default for Fnv {
+  fn default() -> Self {
+    Self(0xcbf2_9ce4_8422_2325)
+  }
+}
+
+impl Hasher for Fnv {
+  fn finish(&self) -> u64 {
+    self.0
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    for b in bytes {
+      self.0 ^= *b as u64;
+      self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+    }
+  }
+}
@@ -0,0 +1,54 @@
+use dns_protocol::{Flags, MessageType, ResourceRecord, ResourceType};
+
+mod announcer;
+#[cfg(feature = "slab")]
+mod cache;
+mod client;
+mod prober;
+#[cfg(feature = "slab")]
+mod server;
+#[cfg(feature = "slab")]
+mod suppression;
+
+const SRV: &[u8] = &[
+  0, 10, 0, 1, 0, 80, 8, b't', b'e', b's', b't', b'h', b'o', b's', b't', 0,
+];
+const TXT: &[u8] = &[4, b'i', b'n', b'f', b'o'];
+const A: &[u8] = &[192, 168, 0, 42];
+
+fn unique_records() -> [ResourceRecord<'static>; 3] {
+  [
+    ResourceRecord::new("hostname._http._tcp.local.", ResourceType::Srv, 1, 120, SRV),
+    ResourceRecord::new("hostname._http._tcp.local.", ResourceType::Txt, 1, 120, TXT),
+    ResourceRecord::new("testhost.local.", ResourceType::A, 1, 120, A),
+  ]
+}
+
+/// Returns a packet carrying the `SRV` record of [`unique_records`], with its target
+/// compressed as a pointer to the name of the question, as Bonjour and Avahi do.
+///
+/// The record is in the Answer section of a response if `reply` is `true`, or in the
+/// Authority section of a probe otherwise.
+fn compressed_srv_packet(reply: bool) -> std::vec::Vec<u8> {
+  let (flags, an, ns) = if reply {
+    ([0x84, 0], 1, 0)
+  } else {
+    ([0, 0], 0, 1)
+  };
+  let mut packet = std::vec![0, 0, flags[0], flags[1], 0, 1, 0, an, 0, ns, 0, 0];
+  // the question, `testhost` at offset 12
+  packet.extend_from_slice(b"\x08testhost\x00");
+  packet.extend_from_slice(&[0, 1, 0, 1]);
+  // the record, `hostname._http._tcp.local`
+  packet.extend_from_slice(b"\x08hostname\x05_http\x04_tcp\x05local\x00");
+  packet.extend_from_slice(&[0, 33, 0, 1, 0, 0, 0, 120, 0, 8]);
+  packet.extend_from_slice(&SRV[..6]);
+  packet.extend_from_slice(&[0xc0, 12]);
+  packet
+}
+
+fn response_flags() -> Flags {
+  let mut flags = Flags::new();
+  flags.set_qr(MessageType::Reply).set_authoritative(true);
+  flags
+}
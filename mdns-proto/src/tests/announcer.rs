@@ -0,0 +1,53 @@
+use std::time::Instant;
+
+use super::unique_records;
+
+use crate::announcer::{ANNOUNCE_COUNT, ANNOUNCE_INTERVAL, Announcer, cache_flush, goodbye};
+
+#[test]
+fn announce_twice_one_second_apart() {
+  let start = Instant::now();
+  let mut announcer = Announcer::new(start);
+
+  assert_eq!(announcer.poll_timeout(), Some(start));
+  assert!(announcer.handle_timeout(start));
+  // not yet
+  assert!(!announcer.handle_timeout(start));
+
+  let mut now = start;
+  for _ in 1..ANNOUNCE_COUNT {
+    now += ANNOUNCE_INTERVAL;
+    assert_eq!(announcer.poll_timeout(), Some(now));
+    assert!(announcer.handle_timeout(now));
+  }
+
+  assert!(announcer.is_done());
+  assert_eq!(announcer.poll_timeout(), None);
+  assert!(!announcer.handle_timeout(now + ANNOUNCE_INTERVAL));
+
+  announcer.restart(now);
+  assert!(!announcer.is_done());
+  assert!(announcer.handle_timeout(now));
+}
+
+#[test]
+fn cache_flush_bit() {
+  for record in unique_records() {
+    let flushed = cache_flush(record);
+    assert_eq!(flushed.class(), record.class() | (1 << 15));
+    assert_eq!(flushed.ty(), record.ty());
+    assert_eq!(flushed.ttl(), record.ttl());
+    assert_eq!(flushed.data(), record.data());
+  }
+}
+
+#[test]
+fn goodbye_ttl_zero() {
+  for record in unique_records() {
+    let bye = goodbye(cache_flush(record));
+    assert_eq!(bye.ttl(), 0);
+    assert_eq!(bye.class(), record.class() | (1 << 15));
+    assert_eq!(bye.ty(), record.ty());
+    assert_eq!(bye.data(), record.data());
+  }
+}
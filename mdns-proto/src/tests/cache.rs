@@ -0,0 +1,186 @@
+use std::time::{Duration, Instant};
+
+use core::net::{Ipv4Addr, SocketAddr};
+
+use dns_protocol::{Message, Question, ResourceRecord, ResourceType};
+
+use super::{SRV, compressed_srv_packet, unique_records};
+
+use crate::{
+  cache::{CacheEvent, FLUSH_DELAY, REFRESH_PERCENTS, SlabCache},
+  client::{Endpoint, Response},
+};
+
+fn secs(secs: u64) -> Duration {
+  Duration::from_secs(secs)
+}
+
+#[test]
+fn refresh_then_expire() {
+  let start = Instant::now();
+  let mut cache = SlabCache::new();
+  let [srv, ..] = unique_records();
+  let srv = ResourceRecord::new(srv.name(), srv.ty(), srv.class(), 100, srv.data());
+  assert!(cache.insert(&[], &srv, start).unwrap());
+
+  for percent in REFRESH_PERCENTS {
+    let at = start + secs(percent as u64);
+    assert_eq!(cache.poll_timeout(), Some(at));
+    assert!(cache.handle_timeout(at - secs(1)).is_none());
+    match cache.handle_timeout(at) {
+      Some(CacheEvent::Refresh(entry)) => {
+        assert_eq!(entry.ty(), ResourceType::Srv);
+        assert_eq!(entry.data(), srv.data());
+      }
+      ev => panic!("unexpected event: {ev:?}"),
+    }
+    assert!(cache.handle_timeout(at).is_none());
+  }
+
+  let end = start + secs(100);
+  assert_eq!(cache.poll_timeout(), Some(end));
+  match cache.handle_timeout(end) {
+    Some(CacheEvent::Expired(entry)) => assert_eq!(entry.expires(), end),
+    ev => panic!("unexpected event: {ev:?}"),
+  }
+  assert!(cache.is_empty());
+  assert_eq!(cache.poll_timeout(), None);
+}
+
+#[test]
+fn renew_restarts_schedule() {
+  let start = Instant::now();
+  let mut cache = SlabCache::new();
+  let [srv, txt, _] = unique_records();
+  cache.insert(&[], &srv, start).unwrap();
+  cache.insert(&[], &txt, start).unwrap();
+
+  let refresh = start + secs(96);
+  assert!(cache.handle_timeout(refresh).unwrap().is_refresh());
+  assert!(cache.handle_timeout(refresh).unwrap().is_refresh());
+  assert!(cache.handle_timeout(refresh).is_none());
+
+  // the answer to the refresh query renews the record, with the cache-flush bit
+  let renewed = ResourceRecord::new(
+    srv.name(),
+    srv.ty(),
+    srv.class() | (1 << 15),
+    120,
+    srv.data(),
+  );
+  assert!(!cache.insert(&[], &renewed, refresh).unwrap());
+  assert_eq!(cache.len(), 2);
+
+  // the TXT record is not renewed
+  let next = start + secs(102);
+  assert_eq!(cache.poll_timeout(), Some(next));
+  assert!(cache.handle_timeout(next).unwrap().is_refresh());
+  // the last refreshes are due before the expiration
+  let end = start + secs(120);
+  assert!(cache.handle_timeout(end).unwrap().is_refresh());
+  assert!(cache.handle_timeout(end).unwrap().is_refresh());
+  assert!(cache.handle_timeout(end).unwrap().is_expired());
+  assert!(cache.handle_timeout(end).is_none());
+  assert_eq!(cache.poll_timeout(), Some(refresh + secs(96)));
+}
+
+#[test]
+fn goodbye() {
+  let start = Instant::now();
+  let mut cache = SlabCache::new();
+  let [srv, txt, _] = unique_records();
+  cache.insert(&[], &srv, start).unwrap();
+
+  // a goodbye for an unknown record is ignored
+  let bye =
+    |r: ResourceRecord<'static>| ResourceRecord::new(r.name(), r.ty(), r.class(), 0, r.data());
+  assert!(!cache.insert(&[], &bye(txt), start).unwrap());
+  assert_eq!(cache.len(), 1);
+
+  // the record expires one second later, without being refreshed
+  let now = start + secs(10);
+  assert!(!cache.insert(&[], &bye(srv), now).unwrap());
+  assert_eq!(cache.poll_timeout(), Some(now + FLUSH_DELAY));
+  assert!(
+    cache
+      .handle_timeout(now + FLUSH_DELAY)
+      .unwrap()
+      .is_expired()
+  );
+  assert!(cache.is_empty());
+}
+
+#[test]
+fn cache_flush() {
+  let start = Instant::now();
+  let mut cache = SlabCache::new();
+  let a = |data: &'static [u8], flush: bool| {
+    let class = if flush { 1 | (1 << 15) } else { 1 };
+    ResourceRecord::new("testhost.local.", ResourceType::A, class, 120, data)
+  };
+  cache
+    .insert(&[], &a(&[192, 168, 0, 1], false), start)
+    .unwrap();
+  cache
+    .insert(&[], &a(&[192, 168, 0, 2], false), start)
+    .unwrap();
+  // received less than one second before the flush, it belongs to the same set
+  let now = start + secs(2);
+  cache
+    .insert(&[], &a(&[192, 168, 0, 3], false), now - FLUSH_DELAY / 2)
+    .unwrap();
+  assert!(cache.insert(&[], &a(&[192, 168, 0, 4], true), now).unwrap());
+  assert_eq!(cache.len(), 4);
+
+  let mut expired = Vec::new();
+  while let Some(event) = cache.handle_timeout(now + FLUSH_DELAY) {
+    match event {
+      CacheEvent::Expired(entry) => expired.push(entry.data().to_vec()),
+      ev => panic!("unexpected event: {ev:?}"),
+    }
+  }
+  expired.sort();
+  assert_eq!(expired, [[192, 168, 0, 1], [192, 168, 0, 2]]);
+
+  // the names are compared case-insensitively, and the cache-flush bit is ignored
+  let set = cache
+    .get("TESTHOST.local".into(), ResourceType::A, 1)
+    .map(|entry| entry.data().to_vec())
+    .collect::<Vec<_>>();
+  assert_eq!(set.len(), 2);
+  assert!(set.contains(&vec![192, 168, 0, 3]));
+  assert!(set.contains(&vec![192, 168, 0, 4]));
+  assert!(
+    cache
+      .get("testhost.local".into(), ResourceType::AAAA, 1)
+      .next()
+      .is_none()
+  );
+}
+
+#[test]
+fn compressed_rdata() {
+  let start = Instant::now();
+  let mut cache = SlabCache::new();
+  let [srv, ..] = unique_records();
+
+  // the target of the SRV record is compressed as a pointer to the question
+  let packet = compressed_srv_packet(true);
+  let mut questions = [Question::default(); 1];
+  let mut answers = [ResourceRecord::default(); 1];
+  let msg = Message::read(&packet, &mut questions, &mut answers, &mut [], &mut []).unwrap();
+  assert!(cache.insert(&packet, &msg.answers()[0], start).unwrap());
+
+  // the record is cached uncompressed, and can be parsed on its own
+  let entry = cache.iter().next().unwrap();
+  assert_eq!(entry.data(), SRV);
+  let from = SocketAddr::from((Ipv4Addr::new(192, 168, 0, 42), 5353));
+  assert!(matches!(
+    Endpoint::parse(from, &[], &entry.record()).unwrap(),
+    Response::Srv { srv, .. } if srv.target().to_string() == "testhost"
+  ));
+
+  // the same record, uncompressed, renews the cached one
+  assert!(!cache.insert(&[], &srv, start + secs(10)).unwrap());
+  assert_eq!(cache.len(), 1);
+}
@@ -0,0 +1,248 @@
+use core::{
+  net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
+  time::Duration,
+};
+use std::time::Instant;
+
+use dns_protocol::{Message, Question, ResourceRecord, ResourceType};
+
+use super::{SRV, TXT, compressed_srv_packet, response_flags};
+
+use crate::client::{
+  Endpoint, QUERY_INTERVAL_MAX, QUERY_INTERVAL_MIN, QuerySchedule, Response, Section,
+};
+
+#[test]
+fn record_metadata() {
+  #[rustfmt::skip]
+  let packet = [
+    // header: a response with one answer and one additional record
+    0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 1,
+    // answer: testhost.local. A, cache-flush bit set, TTL 120
+    8, b't', b'e', b's', b't', b'h', b'o', b's', b't', 5, b'l', b'o', b'c', b'a', b'l', 0,
+    0, 1, 0x80, 1, 0, 0, 0, 120, 0, 4, 192, 168, 0, 42,
+    // additional: testhost.local. AAAA, a goodbye, TTL 0
+    0xc0, 12,
+    0, 28, 0, 1, 0, 0, 0, 0, 0, 16, 0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+  ];
+  let mut answers = [ResourceRecord::default(); 1];
+  let mut additionals = [ResourceRecord::default(); 1];
+  let msg = Message::read(&packet, &mut [], &mut answers, &mut [], &mut additionals).unwrap();
+  let from = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 5353, 0, 3));
+
+  let mut records = Endpoint::recv(from, &packet, &msg);
+  let a = records.next().unwrap().unwrap();
+  assert_eq!(a.name().to_string(), "testhost.local");
+  assert_eq!(a.ttl(), 120);
+  assert_eq!(a.class(), 1);
+  assert!(a.cache_flush());
+  assert_eq!(a.section(), Section::Answer);
+  assert!(matches!(
+    a.response(),
+    Response::A { addr, .. } if *addr == Ipv4Addr::new(192, 168, 0, 42)
+  ));
+
+  let aaaa = records.next().unwrap().unwrap();
+  assert_eq!(aaaa.name().to_string(), "testhost.local");
+  assert_eq!(aaaa.ttl(), 0);
+  assert_eq!(aaaa.class(), 1);
+  assert!(!aaaa.cache_flush());
+  assert_eq!(aaaa.section(), Section::Additional);
+  // the zone of the link-local address is the one of the source address
+  assert!(matches!(
+    aaaa.into_response(),
+    Response::AAAA { zone: Some(3), .. }
+  ));
+
+  assert!(records.next().is_none());
+}
+
+#[test]
+fn record_sections() {
+  const PTR: &[u8] = &[
+    8, b'h', b'o', b's', b't', b'n', b'a', b'm', b'e', 5, b'_', b'h', b't', b't', b'p', 4, b'_',
+    b't', b'c', b'p', 5, b'l', b'o', b'c', b'a', b'l', 0,
+  ];
+
+  let mut answers = [
+    ResourceRecord::new("_http._tcp.local.", ResourceType::Ptr, 1, 4500, PTR),
+    // a record of another type is returned undecoded
+    ResourceRecord::new(
+      "hostname._http._tcp.local.",
+      ResourceType::HInfo,
+      1,
+      120,
+      &[],
+    ),
+  ];
+  let mut additionals = [
+    ResourceRecord::new(
+      "hostname._http._tcp.local.",
+      ResourceType::Srv,
+      1 | (1 << 15),
+      120,
+      SRV,
+    ),
+    ResourceRecord::new(
+      "hostname._http._tcp.local.",
+      ResourceType::Txt,
+      1,
+      4500,
+      TXT,
+    ),
+  ];
+  let msg = Message::new(
+    0,
+    response_flags(),
+    &mut [],
+    &mut answers,
+    &mut [],
+    &mut additionals,
+  );
+  let mut buf = [0; 512];
+  let len = msg.write(&mut buf).unwrap();
+
+  let mut answers = [ResourceRecord::default(); 2];
+  let mut additionals = [ResourceRecord::default(); 2];
+  let msg = Message::read(
+    &buf[..len],
+    &mut [],
+    &mut answers,
+    &mut [],
+    &mut additionals,
+  )
+  .unwrap();
+  let from = SocketAddr::from((Ipv4Addr::new(192, 168, 0, 42), 5353));
+  let records = Endpoint::recv(from, &buf[..len], &msg)
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap();
+  assert_eq!(records.len(), 4);
+
+  // the name of a PTR record is the service, its data the instance
+  let ptr = records[0];
+  assert_eq!(ptr.name().to_string(), "_http._tcp.local");
+  assert_eq!(ptr.ttl(), 4500);
+  assert!(!ptr.cache_flush());
+  assert!(ptr.section().is_answer());
+  assert!(matches!(
+    ptr.response(),
+    Response::Ptr(instance) if instance.to_string() == "hostname._http._tcp.local"
+  ));
+
+  let hinfo = records[1];
+  assert!(hinfo.section().is_answer());
+  assert!(matches!(
+    hinfo.into_response(),
+    Response::Other {
+      ty: ResourceType::HInfo,
+      data: &[],
+      ..
+    }
+  ));
+
+  let srv = records[2];
+  assert_eq!(srv.ttl(), 120);
+  assert_eq!(srv.class(), 1);
+  assert!(srv.cache_flush());
+  assert!(srv.section().is_additional());
+  assert!(matches!(srv.response(), Response::Srv { srv, .. } if srv.port() == 80));
+
+  let txt = records[3];
+  assert_eq!(txt.ttl(), 4500);
+  assert!(!txt.cache_flush());
+  assert!(txt.section().is_additional());
+  assert!(matches!(txt.response(), Response::Txt { .. }));
+}
+
+#[test]
+fn typed_question_and_other_record() {
+  let q = Endpoint::prepare_typed_question("testhost.local".into(), ResourceType::HInfo, true);
+  assert_eq!(q.ty(), ResourceType::HInfo);
+  // RFC 6762, section 18.12: the top bit of the qclass asks for a unicast response
+  assert_eq!(q.class(), 1 | (1 << 15));
+
+  let q = Endpoint::prepare_question("_http._tcp.local".into(), false);
+  assert_eq!(q.ty(), ResourceType::Ptr);
+  assert_eq!(q.class(), 1);
+
+  // the data of a type the endpoint does not decode is left as is
+  let data = [3, b'a', b'r', b'm', 5, b'l', b'i', b'n', b'u', b'x'];
+  let record = ResourceRecord::new("testhost.local", ResourceType::HInfo, 1, 120, &data);
+  let from = SocketAddr::from((Ipv4Addr::new(192, 168, 0, 42), 5353));
+  assert!(matches!(
+    Endpoint::parse(from, &[], &record).unwrap(),
+    Response::Other { ty: ResourceType::HInfo, data: d, .. } if d == data
+  ));
+}
+
+#[test]
+fn binary_txt_record() {
+  // a binary value, a quote and a backslash, then a plain string
+  let data = [5, b'i', b'd', b'=', 0xff, 0, 4, b'"', b'\\', b'o', b'k'];
+  let record = ResourceRecord::new("printer._ipp._tcp.local", ResourceType::Txt, 1, 120, &data);
+  let from = SocketAddr::from((Ipv4Addr::new(192, 168, 0, 42), 5353));
+  let Response::Txt { txt, .. } = Endpoint::parse(from, &[], &record).unwrap() else {
+    panic!("not a TXT record");
+  };
+
+  let strings = txt.strings().collect::<Result<Vec<_>, _>>().unwrap();
+  assert_eq!(strings.len(), 2);
+  // the bytes are kept as is, not escaped
+  assert_eq!(strings[0].as_bytes(), [b'i', b'd', b'=', 0xff, 0]);
+  assert!(strings[0].to_str().is_err());
+  assert_eq!(strings[0].to_string(), "id=\\255\\000");
+  assert_eq!(strings[1].as_bytes(), b"\"\\ok");
+  assert_eq!(strings[1].to_str().unwrap(), "\"\\ok");
+}
+
+#[test]
+fn query_schedule() {
+  let start = Instant::now();
+  let mut schedule = QuerySchedule::new(start);
+
+  assert_eq!(schedule.poll_timeout(), start);
+  assert!(schedule.handle_timeout(start));
+  // not yet
+  assert!(!schedule.handle_timeout(start));
+
+  // one second, then doubling every time
+  let mut now = start;
+  let mut interval = QUERY_INTERVAL_MIN;
+  for _ in 0..4 {
+    now += interval;
+    assert_eq!(schedule.poll_timeout(), now);
+    assert!(schedule.handle_timeout(now));
+    interval *= 2;
+  }
+  assert_eq!(schedule.poll_timeout(), now + Duration::from_secs(16));
+
+  // capped at 60 minutes
+  while schedule.interval() < QUERY_INTERVAL_MAX {
+    now = schedule.poll_timeout();
+    assert!(schedule.handle_timeout(now));
+  }
+  now = schedule.poll_timeout();
+  assert!(schedule.handle_timeout(now));
+  assert_eq!(schedule.poll_timeout(), now + QUERY_INTERVAL_MAX);
+
+  schedule.restart(now);
+  assert!(schedule.handle_timeout(now));
+  assert_eq!(schedule.poll_timeout(), now + QUERY_INTERVAL_MIN);
+}
+
+#[test]
+fn compressed_srv_target() {
+  let packet = compressed_srv_packet(true);
+  let mut questions = [Question::default(); 1];
+  let mut answers = [ResourceRecord::default(); 1];
+  let msg = Message::read(&packet, &mut questions, &mut answers, &mut [], &mut []).unwrap();
+  assert_ne!(msg.answers()[0].data(), SRV);
+  let from = SocketAddr::from((Ipv4Addr::new(192, 168, 0, 42), 5353));
+
+  // the target is read from the packet, following the pointer to the question
+  let srv = Endpoint::recv(from, &packet, &msg).next().unwrap().unwrap();
+  assert!(matches!(
+    srv.response(),
+    Response::Srv { srv, .. } if srv.port() == 80 && srv.target().to_string() == "testhost"
+  ));
+}
@@ -0,0 +1,294 @@
+use std::time::{Duration, Instant};
+
+use dns_protocol::{Flags, Message, Question, ResourceRecord, ResourceType};
+
+use super::{A, SRV, compressed_srv_packet, response_flags, unique_records};
+use core::cmp::Ordering;
+
+use crate::prober::{
+  PROBE_DEFER, PROBE_INTERVAL, ProbeEvent, ProbeState, Prober, questions, tiebreak,
+};
+
+#[test]
+fn probe_then_win() {
+  let start = Instant::now();
+  let mut prober = Prober::new(start);
+
+  assert_eq!(prober.poll_timeout(), Some(start));
+  assert_eq!(
+    prober.handle_timeout(start),
+    Some(ProbeEvent::Probe {
+      unicast_response: true
+    })
+  );
+  // not yet
+  assert_eq!(prober.handle_timeout(start), None);
+
+  let mut now = start;
+  for _ in 0..2 {
+    now += PROBE_INTERVAL;
+    assert_eq!(prober.poll_timeout(), Some(now));
+    assert_eq!(
+      prober.handle_timeout(now),
+      Some(ProbeEvent::Probe {
+        unicast_response: false
+      })
+    );
+  }
+
+  now += PROBE_INTERVAL;
+  assert_eq!(prober.handle_timeout(now), Some(ProbeEvent::Won));
+  assert_eq!(prober.state(), ProbeState::Won);
+  assert_eq!(prober.poll_timeout(), None);
+}
+
+#[test]
+fn probe_questions_dedup_names() {
+  let records = unique_records();
+  let qs = questions(&records, true).collect::<Vec<_>>();
+  assert_eq!(qs.len(), 2);
+  assert!(qs.iter().all(|q| q.ty() == ResourceType::Wildcard));
+  assert!(qs.iter().all(|q| q.class() == 1 | (1 << 15)));
+
+  let qs = questions(&records, false).collect::<Vec<_>>();
+  assert!(qs.iter().all(|q| q.class() == 1));
+}
+
+#[test]
+fn lost_while_probing() {
+  let start = Instant::now();
+  let records = unique_records();
+  let mut prober = Prober::new(start);
+  prober.handle_timeout(start);
+
+  // our own records are not a conflict
+  let mut answers = records;
+  let msg = Message::new(0, response_flags(), &mut [], &mut answers, &mut [], &mut []);
+  assert_eq!(prober.recv(start, &[], &msg, &records), None);
+
+  // a query is never a conflict
+  let mut answers = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::A,
+    1,
+    120,
+    &[10, 0, 0, 1],
+  )];
+  let msg = Message::new(0, Flags::new(), &mut [], &mut answers, &mut [], &mut []);
+  assert_eq!(prober.recv(start, &[], &msg, &records), None);
+
+  // another host answers for our host name with any type, names are case insensitive
+  let mut additionals = [ResourceRecord::new(
+    "TestHost.local.",
+    ResourceType::AAAA,
+    1,
+    120,
+    &[0; 16],
+  )];
+  let msg = Message::new(
+    0,
+    response_flags(),
+    &mut [],
+    &mut [],
+    &mut [],
+    &mut additionals,
+  );
+  assert_eq!(
+    prober.recv(start, &[], &msg, &records),
+    Some(ProbeEvent::Lost { index: 2 })
+  );
+  assert_eq!(prober.state(), ProbeState::Lost);
+  assert_eq!(prober.poll_timeout(), None);
+  assert_eq!(prober.handle_timeout(start + Duration::from_secs(1)), None);
+}
+
+#[test]
+fn conflict_after_won() {
+  let start = Instant::now();
+  let records = unique_records();
+  let mut prober = Prober::new(start);
+  let mut now = start;
+  while !prober.state().is_won() {
+    prober.handle_timeout(now);
+    now += PROBE_INTERVAL;
+  }
+  assert_eq!(prober, Prober::claimed());
+
+  // a record with the same name but a different type is not a conflict once won
+  let mut answers = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::AAAA,
+    1,
+    120,
+    &[0; 16],
+  )];
+  let msg = Message::new(0, response_flags(), &mut [], &mut answers, &mut [], &mut []);
+  assert_eq!(prober.recv(now, &[], &msg, &records), None);
+
+  // same record with the cache-flush bit set
+  let mut answers = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::A,
+    1 | (1 << 15),
+    120,
+    A,
+  )];
+  let msg = Message::new(0, response_flags(), &mut [], &mut answers, &mut [], &mut []);
+  assert_eq!(prober.recv(now, &[], &msg, &records), None);
+
+  let mut answers = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::A,
+    1 | (1 << 15),
+    120,
+    &[10, 0, 0, 1],
+  )];
+  let msg = Message::new(0, response_flags(), &mut [], &mut answers, &mut [], &mut []);
+  assert_eq!(
+    prober.recv(now, &[], &msg, &records),
+    Some(ProbeEvent::Conflict)
+  );
+  assert_eq!(prober.state(), ProbeState::Conflict);
+
+  prober.restart(now);
+  assert_eq!(prober.state(), ProbeState::Probing);
+  assert_eq!(prober.poll_timeout(), Some(now));
+}
+
+#[test]
+fn tiebreak_order() {
+  let records = unique_records();
+  assert_eq!(tiebreak(&records, &records, &[]), Ordering::Equal);
+
+  // the order of the records does not matter
+  let mut reversed = records;
+  reversed.reverse();
+  assert_eq!(tiebreak(&records, &reversed, &[]), Ordering::Equal);
+
+  // the sorted sets are A, TXT, SRV and A, TXT, the host with records remaining wins
+  assert_eq!(tiebreak(&records, &records[1..], &[]), Ordering::Greater);
+  assert_eq!(tiebreak(&records[1..], &records, &[]), Ordering::Less);
+
+  // rdata is compared as raw bytes, 192.168.0.42 > 10.0.0.1
+  let theirs = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::A,
+    1,
+    120,
+    &[10, 0, 0, 1],
+  )];
+  assert_eq!(tiebreak(&records[2..], &theirs, &[]), Ordering::Greater);
+
+  // the type is compared before the rdata, AAAA (28) > A (1)
+  let theirs = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::AAAA,
+    1,
+    120,
+    &[0; 16],
+  )];
+  assert_eq!(tiebreak(&records[2..], &theirs, &[]), Ordering::Less);
+
+  // the cache-flush bit is ignored
+  let theirs = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::A,
+    1 | (1 << 15),
+    120,
+    A,
+  )];
+  assert_eq!(tiebreak(&records[2..], &theirs, &[]), Ordering::Equal);
+}
+
+#[test]
+fn compressed_rdata() {
+  let records = unique_records();
+
+  // a probe for our SRV record, with its target compressed
+  let packet = compressed_srv_packet(false);
+  let mut qs = [Question::default(); 1];
+  let mut ns = [ResourceRecord::default(); 1];
+  let msg = Message::read(&packet, &mut qs, &mut [], &mut ns, &mut []).unwrap();
+  assert_ne!(msg.authorities()[0].data(), SRV);
+  assert_eq!(
+    tiebreak(&records[..1], msg.authorities(), &packet),
+    Ordering::Equal
+  );
+
+  // compared raw, the pointer (0xc0) would be later than the length of the target (8)
+  let mut prober = Prober::new(Instant::now());
+  prober.handle_timeout(Instant::now());
+  assert_eq!(
+    prober.recv(Instant::now(), &packet, &msg, &records[..1]),
+    None
+  );
+
+  // a response with a compressed copy of our SRV record is not a conflict
+  let packet = compressed_srv_packet(true);
+  let mut an = [ResourceRecord::default(); 1];
+  let msg = Message::read(&packet, &mut qs, &mut an, &mut [], &mut []).unwrap();
+  let mut prober = Prober::claimed();
+  assert_eq!(prober.recv(Instant::now(), &packet, &msg, &records), None);
+  assert_eq!(prober.state(), ProbeState::Won);
+}
+
+#[test]
+fn deferred_by_simultaneous_probe() {
+  let start = Instant::now();
+  let records = unique_records();
+  let mut prober = Prober::new(start);
+  prober.handle_timeout(start);
+
+  // our own probe
+  let mut qs = questions(&records, false).collect::<Vec<_>>();
+  let mut authorities = records;
+  let msg = Message::new(0, Flags::new(), &mut qs, &mut [], &mut authorities, &mut []);
+  assert_eq!(prober.recv(start, &[], &msg, &records), None);
+
+  // a probe for another name
+  let mut authorities = [ResourceRecord::new(
+    "otherhost.local.",
+    ResourceType::A,
+    1,
+    120,
+    &[192, 168, 0, 43],
+  )];
+  let msg = Message::new(0, Flags::new(), &mut [], &mut [], &mut authorities, &mut []);
+  assert_eq!(prober.recv(start, &[], &msg, &records), None);
+
+  // a lexicographically earlier probe for our host name
+  let mut authorities = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::A,
+    1,
+    120,
+    &[10, 0, 0, 1],
+  )];
+  let msg = Message::new(0, Flags::new(), &mut [], &mut [], &mut authorities, &mut []);
+  assert_eq!(prober.recv(start, &[], &msg, &records), None);
+
+  // a lexicographically later probe for our host name
+  let mut authorities = [ResourceRecord::new(
+    "testhost.local.",
+    ResourceType::A,
+    1,
+    120,
+    &[192, 168, 0, 43],
+  )];
+  let msg = Message::new(0, Flags::new(), &mut [], &mut [], &mut authorities, &mut []);
+  assert_eq!(
+    prober.recv(start, &[], &msg, &records),
+    Some(ProbeEvent::Deferred)
+  );
+  assert_eq!(prober.state(), ProbeState::Probing);
+  assert_eq!(prober.poll_timeout(), Some(start + PROBE_DEFER));
+
+  // probing starts over
+  let now = start + PROBE_DEFER;
+  assert_eq!(
+    prober.handle_timeout(now),
+    Some(ProbeEvent::Probe {
+      unicast_response: true
+    })
+  );
+}
@@ -0,0 +1,143 @@
+use dns_protocol::{Flags, Message, Question, ResourceRecord, ResourceType};
+
+use super::{compressed_srv_packet, unique_records};
+
+use crate::server::{
+  LEGACY_UNICAST_TTL, SlabEndpoint, clear_truncated, is_truncated, legacy_unicast,
+};
+
+#[test]
+fn known_answer_suppression() {
+  let [srv, txt, a] = unique_records();
+  let mut endpoint = SlabEndpoint::new();
+  let ch = endpoint.accept().unwrap();
+
+  let mut questions = [Question::new(
+    "hostname._http._tcp.local.",
+    ResourceType::Wildcard,
+    1,
+  )];
+  let mut answers = [
+    // the querier already knows the SRV record, with more than half of its TTL left
+    ResourceRecord::new(srv.name(), srv.ty(), srv.class(), 60, srv.data()),
+    // but the TXT record is about to expire
+    ResourceRecord::new(txt.name(), txt.ty(), txt.class(), 59, txt.data()),
+    // and an A record with other data
+    ResourceRecord::new(a.name(), a.ty(), a.class(), 120, &[192, 168, 0, 1]),
+  ];
+  let msg = Message::new(
+    0,
+    Flags::new(),
+    &mut questions,
+    &mut answers,
+    &mut [],
+    &mut [],
+  );
+  let query = endpoint.recv(ch, &[], msg).unwrap();
+
+  assert_eq!(query.known_answers().len(), 3);
+  assert!(query.is_known_answer(&srv));
+  assert!(!query.is_known_answer(&txt));
+  assert!(!query.is_known_answer(&a));
+
+  // the cache-flush bit and the case of the name are ignored
+  let flushed = ResourceRecord::new(
+    "HOSTNAME._http._tcp.local.",
+    srv.ty(),
+    srv.class() | (1 << 15),
+    srv.ttl(),
+    srv.data(),
+  );
+  assert!(query.is_known_answer(&flushed));
+}
+
+#[test]
+fn compressed_known_answer() {
+  let [srv, txt, _] = unique_records();
+  let mut endpoint = SlabEndpoint::new();
+  let ch = endpoint.accept().unwrap();
+
+  // a query carrying our SRV record as a known answer, with its target compressed
+  let mut packet = compressed_srv_packet(true);
+  packet[2] = 0;
+  let mut questions = [Question::default(); 1];
+  let mut answers = [ResourceRecord::default(); 1];
+  let msg = Message::read(&packet, &mut questions, &mut answers, &mut [], &mut []).unwrap();
+  assert_ne!(msg.answers()[0].data(), srv.data());
+
+  let query = endpoint.recv(ch, &packet, msg).unwrap();
+  assert!(query.is_known_answer(&srv));
+  assert!(!query.is_known_answer(&txt));
+
+  // another target is not suppressed
+  let mut other = srv.data()[..6].to_vec();
+  other.extend_from_slice(b"\x09otherhost\x00");
+  let other = ResourceRecord::new(srv.name(), srv.ty(), srv.class(), srv.ttl(), &other);
+  assert!(!query.is_known_answer(&other));
+}
+
+#[test]
+fn truncated_bit() {
+  let mut flags = Flags::new();
+  flags.set_truncated(true);
+  let mut questions = [Question::new("_http._tcp.local.", ResourceType::Ptr, 1)];
+  let msg = Message::new(0, flags, &mut questions, &mut [], &mut [], &mut []);
+  let mut buf = [0; 64];
+  let len = msg.write(&mut buf).unwrap();
+  let packet = &mut buf[..len];
+
+  assert!(is_truncated(packet));
+  clear_truncated(packet);
+  assert!(!is_truncated(packet));
+
+  let mut questions = [Question::default(); 1];
+  let msg = Message::read(packet, &mut questions, &mut [], &mut [], &mut []).unwrap();
+  assert_eq!(msg.questions().len(), 1);
+}
+
+#[test]
+fn legacy_unicast_response() {
+  let mut endpoint = SlabEndpoint::new();
+  let ch = endpoint.accept().unwrap();
+
+  // a one-shot query, without the unicast-response bit
+  let question = Question::new("hostname._http._tcp.local.", ResourceType::Srv, 1);
+  let mut questions = [question];
+  let msg = Message::new(
+    0x1234,
+    Flags::new(),
+    &mut questions,
+    &mut [],
+    &mut [],
+    &mut [],
+  );
+  let query = endpoint.recv(ch, &[], msg).unwrap();
+
+  let outgoing = endpoint.response(query.query_handle(), question).unwrap();
+  assert!(!outgoing.is_unicast());
+  assert!(!outgoing.is_legacy());
+  assert_eq!(outgoing.id(), 0);
+
+  let outgoing = endpoint
+    .legacy_response(query.query_handle(), question)
+    .unwrap();
+  assert!(outgoing.is_unicast());
+  assert!(outgoing.is_legacy());
+  assert_eq!(outgoing.id(), 0x1234);
+
+  for record in unique_records() {
+    let legacy = legacy_unicast(ResourceRecord::new(
+      record.name(),
+      record.ty(),
+      record.class() | (1 << 15),
+      record.ttl(),
+      record.data(),
+    ));
+    assert_eq!(legacy.ttl(), LEGACY_UNICAST_TTL);
+    assert_eq!(legacy.class(), record.class());
+    assert_eq!(legacy.data(), record.data());
+
+    let short = ResourceRecord::new(record.name(), record.ty(), record.class(), 5, record.data());
+    assert_eq!(legacy_unicast(short).ttl(), 5);
+  }
+}
@@ -0,0 +1,124 @@
+use std::time::Instant;
+
+use dns_protocol::{Flags, Message, Question, ResourceRecord, ResourceType};
+
+use super::{compressed_srv_packet, response_flags, unique_records};
+
+use crate::suppression::{SUPPRESSION_WINDOW, SlabSuppression};
+
+#[test]
+fn duplicate_question_suppression() {
+  let start = Instant::now();
+  let mut suppression = SlabSuppression::new();
+  let ptr = Question::new("_http._tcp.local.", ResourceType::Ptr, 1);
+  let srv = Question::new("hostname._http._tcp.local.", ResourceType::Srv, 1);
+  let txt = Question::new("hostname._http._tcp.local.", ResourceType::Txt, 1);
+
+  // a "QU" question does not suppress ours, its answers are sent to the other host only
+  let mut questions = [ptr, srv, Question::new(txt.name(), txt.ty(), 1 | (1 << 15))];
+  let msg = Message::new(0, Flags::new(), &mut questions, &mut [], &mut [], &mut []);
+  suppression.observe(&[], &msg, start).unwrap();
+
+  assert!(suppression.is_question_suppressed(&ptr, start));
+  assert!(!suppression.is_question_suppressed(&txt, start));
+  // the case of the name and the unicast-response bit are ignored
+  let upper = Question::new(
+    "HOSTNAME._http._tcp.local.",
+    ResourceType::Srv,
+    1 | (1 << 15),
+  );
+  assert!(suppression.is_question_suppressed(&upper, start));
+  // another type
+  let a = Question::new(srv.name(), ResourceType::A, 1);
+  assert!(!suppression.is_question_suppressed(&a, start));
+
+  let later = start + SUPPRESSION_WINDOW;
+  assert!(!suppression.is_question_suppressed(&ptr, later));
+
+  // the known answers of another host may not be ours
+  let [record, ..] = unique_records();
+  let mut questions = [srv];
+  let mut answers = [record];
+  let msg = Message::new(
+    0,
+    Flags::new(),
+    &mut questions,
+    &mut answers,
+    &mut [],
+    &mut [],
+  );
+  suppression.observe(&[], &msg, later).unwrap();
+  assert!(!suppression.is_question_suppressed(&srv, later));
+  assert!(suppression.is_empty());
+}
+
+#[test]
+fn duplicate_answer_suppression() {
+  let start = Instant::now();
+  let mut suppression = SlabSuppression::new();
+  let [srv, txt, a] = unique_records();
+
+  let mut answers = [srv];
+  let mut additionals = [a];
+  let msg = Message::new(
+    0,
+    response_flags(),
+    &mut [],
+    &mut answers,
+    &mut [],
+    &mut additionals,
+  );
+  suppression.observe(&[], &msg, start).unwrap();
+
+  assert!(suppression.is_answer_suppressed(&srv, start));
+  assert!(suppression.is_answer_suppressed(&a, start));
+  assert!(!suppression.is_answer_suppressed(&txt, start));
+
+  // the cache-flush bit is ignored
+  let flushed = ResourceRecord::new(
+    srv.name(),
+    srv.ty(),
+    srv.class() | (1 << 15),
+    120,
+    srv.data(),
+  );
+  assert!(suppression.is_answer_suppressed(&flushed, start));
+  // a longer TTL than the one of the other host
+  let longer = ResourceRecord::new(srv.name(), srv.ty(), srv.class(), 4500, srv.data());
+  assert!(!suppression.is_answer_suppressed(&longer, start));
+  // other data
+  let other = ResourceRecord::new(a.name(), a.ty(), a.class(), a.ttl(), &[192, 168, 0, 1]);
+  assert!(!suppression.is_answer_suppressed(&other, start));
+
+  // a question for the same name and type is not an answer
+  let mut questions = [Question::new(txt.name(), txt.ty(), 1)];
+  let msg = Message::new(0, Flags::new(), &mut questions, &mut [], &mut [], &mut []);
+  suppression.observe(&[], &msg, start).unwrap();
+  assert!(!suppression.is_answer_suppressed(&txt, start));
+
+  // observing the record again extends the window
+  let now = start + SUPPRESSION_WINDOW / 2;
+  let mut answers = [srv];
+  let msg = Message::new(0, response_flags(), &mut [], &mut answers, &mut [], &mut []);
+  suppression.observe(&[], &msg, now).unwrap();
+  let later = start + SUPPRESSION_WINDOW;
+  assert!(suppression.is_answer_suppressed(&srv, later));
+  assert!(!suppression.is_answer_suppressed(&a, later));
+}
+
+#[test]
+fn compressed_answer_suppression() {
+  let start = Instant::now();
+  let mut suppression = SlabSuppression::new();
+  let [srv, ..] = unique_records();
+
+  // another host answers with our SRV record, its target compressed
+  let packet = compressed_srv_packet(true);
+  let mut questions = [Question::default(); 1];
+  let mut answers = [ResourceRecord::default(); 1];
+  let msg = Message::read(&packet, &mut questions, &mut answers, &mut [], &mut []).unwrap();
+  assert_ne!(msg.answers()[0].data(), srv.data());
+  suppression.observe(&packet, &msg, start).unwrap();
+
+  assert!(suppression.is_answer_suppressed(&srv, start));
+}
@@ -181,7 +181,7 @@ impl<'a> Str<'a> {
     }
   }
 
-  /// Get the raw bytes of this segment
+  /// Get the raw bytes of this segment, which are not required to be UTF-8
   pub fn as_bytes(&self) -> &'a [u8] {
     match self.repr {
       StrRepr::Bytes {
@@ -193,6 +193,14 @@ impl<'a> Str<'a> {
     }
   }
 
+  /// Get this segment as a string, if its bytes are valid UTF-8
+  pub fn to_str(&self) -> Result<&'a str, ProtoError> {
+    match self.repr {
+      StrRepr::Bytes { .. } => simdutf8::compat::from_utf8(self.as_bytes()).map_err(Into::into),
+      StrRepr::String(s) => Ok(s),
+    }
+  }
+
   /// Create a new segment from a pre-parsed string
   #[inline]
   pub const fn new(s: &'a str) -> Self {
@@ -260,7 +268,8 @@ impl<'innards> Iterator for Strings<'_, 'innards> {
   }
 }
 
-/// Decodes a single TXT segment from a byte slice without UTF-8 validation
+/// Decodes a single TXT segment from a byte slice without UTF-8 validation, the bytes of
+/// a segment are opaque, see RFC 6763, section 6.5
 fn decode_txt_segment(
   msg: &[u8],
   mut offset: usize,
@@ -283,23 +292,6 @@ fn decode_txt_segment(
     return Err(not_enough_read_data(length, content_end - end));
   }
 
-  let mut consumed = 0;
-  for (i, &b) in msg[offset..offset + length].iter().enumerate() {
-    match () {
-      () if (b == b'"' || b == b'\\') || !(b' '..=b'~').contains(&b) => {
-        consumed = i + 1;
-      }
-      _ => {}
-    }
-  }
-
-  if consumed == 0 {
-    // no escaping needed
-    return simdutf8::compat::from_utf8(&msg[offset..offset + length])
-      .map(|s| (Str::new(s), offset + length))
-      .map_err(Into::into);
-  }
-
   let segment = Str::from_bytes(msg, content_start, length);
   Ok((segment, content_end))
 }
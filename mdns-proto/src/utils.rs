@@ -0,0 +1,51 @@
+use dns_protocol::{Label, ResourceRecord};
+
+use super::rdata::Rdata;
+
+/// The class of the Internet.
+pub(crate) const CLASS_IN: u16 = 1;
+
+/// RFC 6762, section 10.2 and 18.12, the top bit of the rrclass or qclass field.
+pub(crate) const TOP_BIT: u16 = 1 << 15;
+
+/// Compares two labels, ignoring ASCII case, as DNS names are case insensitive.
+///
+/// Unlike the `PartialEq` implementation of [`Label`], this comparison follows
+/// compression pointers, so a label read from a packet can be compared with
+/// a label built from a string.
+pub fn label_eq(a: &Label<'_>, b: &Label<'_>) -> bool {
+  let mut a = a.names();
+  let mut b = b.names();
+
+  loop {
+    match (a.next(), b.next()) {
+      (None, None) => return true,
+      (Some(a), Some(b)) => {
+        let a = match a {
+          Ok(s) => s.as_bytes(),
+          Err(bytes) => bytes,
+        };
+        let b = match b {
+          Ok(s) => s.as_bytes(),
+          Err(bytes) => bytes,
+        };
+
+        if !a.eq_ignore_ascii_case(b) {
+          return false;
+        }
+      }
+      _ => return false,
+    }
+  }
+}
+
+/// Returns `true` if the two records have the same name, type, class and data.
+///
+/// The records are read from the raw message `packet`, the names inside their data are
+/// compared uncompressed, see [`Rdata`]. The cache-flush bit of the class is ignored.
+pub fn record_eq(packet: &[u8], a: &ResourceRecord<'_>, b: &ResourceRecord<'_>) -> bool {
+  a.ty() == b.ty()
+    && (a.class() & !TOP_BIT) == (b.class() & !TOP_BIT)
+    && label_eq(&a.name(), &b.name())
+    && Rdata::new(packet, a) == Rdata::new(packet, b)
+}